@@ -0,0 +1,169 @@
+-- Dummy data for local development, load with `cargo run -- seed`.
+-- Every user's password is "password".
+--chatGPT generated dummy data
+
+-- Inserting users
+INSERT INTO users (first_name, last_name, email, password_hash, phone)
+VALUES
+  ('Alice', 'Johnson', 'alice.johnson@example.com', '$2b$12$klCGqx1hxFFIvmQANrgJmOzDih7HlR0sHYBOvl7KpUTq5BlmthIj.', '123-456-7890'),
+  ('Bob', 'Smith', 'bob.smith@example.com', '$2b$12$klCGqx1hxFFIvmQANrgJmOzDih7HlR0sHYBOvl7KpUTq5BlmthIj.', '987-654-3210');
+
+-- Alice and Bob share a household, Alice owns it and Bob looks after the plants
+INSERT INTO households (name) VALUES ('Alice and Bob''s flat');
+INSERT INTO household_members (household_id, user_id, role)
+VALUES
+  (1, 1, 'owner'),
+  (1, 2, 'caretaker');
+
+-- Inserting plants
+INSERT INTO plants (user_id, household_id, botanical_name, common_name)
+VALUES
+  (1, 1, 'Ficus lyrata', 'Fiddle leaf'),
+  (2, 1, 'Monstera deliciosa', 'Swiss Cheese'),
+  (1, 1, 'Sanseveria trifasciata', 'Snake Plant'),
+  (1, 1, 'Epiprenum aureum', 'Pothos'),
+  (1, 1, 'Musa spp.', 'Banana'),
+  (1, 1, 'Calathea spp.', 'Calathea'),
+  (1, 1, 'Asplenium nidus', 'Birds Nest Fern');
+
+-- Inserting a comment
+INSERT INTO comments (plant_id, user_id, time_made, comment)
+VALUES
+  (1, 2, '2023-09-11 10:30:00', 'This plant looks very healthy.');
+
+-- Inserting a photo
+INSERT INTO photos (plant_id, user_id, photo_uri)
+VALUES
+  (2, 1, 'https://example.com/photos/monstera1.jpg');
+
+
+-- Inserting another user
+INSERT INTO users (first_name, last_name, email, password_hash, phone)
+VALUES
+  ('Charlie', 'Davis', 'charlie.davis@example.com', '$2b$12$klCGqx1hxFFIvmQANrgJmOzDih7HlR0sHYBOvl7KpUTq5BlmthIj.', '555-444-3333');
+
+-- Charlie has plants of his own
+INSERT INTO households (name) VALUES ('Charlie''s plants');
+INSERT INTO household_members (household_id, user_id, role) VALUES (2, 3, 'owner');
+
+-- Charlie is keeping an eye on Alice and Bob's plants for a while
+INSERT INTO household_members (household_id, user_id, role, expires_at)
+VALUES (1, 3, 'viewer', NOW() + INTERVAL '14 days');
+
+-- Inserting another plant
+INSERT INTO plants (user_id, household_id, botanical_name, common_name)
+VALUES
+  (3, 2, 'Kalanchoe tomentosa', 'Succulent');
+
+-- Inserting another comment
+INSERT INTO comments (plant_id, user_id, time_made, comment)
+VALUES
+  (3, 3, '2023-09-11 11:00:00', 'Need to remember to water this less frequently.');
+
+-- Inserting another photo
+INSERT INTO photos (plant_id, user_id, photo_uri)
+VALUES
+  (3, 3, 'https://example.com/photos/succulent1.jpg');
+
+-- Inserting another photo
+INSERT INTO photos (plant_id, user_id, photo_uri)
+VALUES
+  (1, 3, 'https://example.com/photos/ficus2.jpg');
+
+
+-- Inserting plants
+INSERT INTO plants (user_id, household_id, botanical_name, common_name)
+VALUES
+  (1, 1, 'Sanseveria trifasciata', 'Snake Plant'),
+  (1, 1, 'Epiprenum aureum', 'Pothos'),
+  (1, 1, 'Musa spp.', 'Banana'),
+  (1, 1, 'Calathea spp.', 'Calathea'),
+  (1, 1, 'Asplenium nidus', 'Birds Nest Fern'),
+  (1, 1, 'Hoya carnosa', 'Hoya'),
+  (1, 1, 'Zamioculcas zamiifolia', 'Zanzibar Gem'),
+  (1, 1, 'Ctenanthe setosa "Grey Star"', 'Grey Star Ctenanthe');
+
+-- Where Alice and Bob's plants live
+INSERT INTO locations (household_id, name, kind, light_level, orientation)
+VALUES
+  (1, 'Living room', 'room', 'medium', NULL),
+  (1, 'Kitchen windowsill', 'windowsill', 'bright', 'south'),
+  (1, 'Balcony', 'balcony', 'direct', 'west');
+UPDATE plants SET location_id = 1 WHERE plant_id IN (1, 2, 5, 9, 13);
+UPDATE plants SET location_id = 2 WHERE plant_id IN (3, 4, 10, 11, 14);
+UPDATE plants SET location_id = 3 WHERE plant_id IN (6, 12, 15);
+
+-- Tags, and a filter Alice uses to check on the plants the cat shouldn't get at
+INSERT INTO plant_tags (plant_id, tag)
+VALUES
+  (1, 'pet-toxic'),
+  (2, 'pet-toxic'),
+  (4, 'pet-toxic'),
+  (4, 'propagation'),
+  (8, 'succulent'),
+  (11, 'propagation'),
+  (15, 'pet-toxic');
+INSERT INTO saved_filters (user_id, name, tags, due)
+VALUES (1, 'Pet-toxic and due', '{pet-toxic}', 'due');
+
+-- Each plant's feed, potting and pruning schedule from back when those were columns on plants
+CREATE TEMPORARY TABLE old_schedules
+  (plant_id, fed, feed_interval, potted, potting_interval, pruned, pruning_interval) AS
+VALUES
+  (1, DATE '2022-01-01', 30, DATE '2023-01-01', 180, DATE '2023-07-01', 60),
+  (2, DATE '2023-09-01', 30, DATE '2023-03-01', 180, DATE '2023-08-01', 90),
+  (3, DATE '2023-08-01', 30, DATE '2023-01-01', 180, DATE '2023-07-01', 60),
+  (4, DATE '2023-08-01', 30, DATE '2023-01-01', 180, DATE '2023-07-01', 60),
+  (5, DATE '2023-08-01', 30, DATE '2023-01-01', 180, DATE '2023-07-01', 60),
+  (6, DATE '2023-08-01', 30, DATE '2023-01-01', 180, DATE '2023-07-01', 60),
+  (7, DATE '2023-08-01', 30, DATE '2023-01-01', 180, DATE '2023-07-01', 60),
+  (8, DATE '2023-08-15', 40, DATE '2023-01-10', 365, DATE '2023-06-20', 120),
+  (9, DATE '2023-08-01', 30, DATE '2023-01-01', 180, DATE '2023-07-01', 60),
+  (10, DATE '2023-08-01', 30, DATE '2023-01-01', 180, DATE '2023-07-01', 60),
+  (11, DATE '2023-08-01', 30, DATE '2023-01-01', 180, DATE '2023-07-01', 60),
+  (12, DATE '2023-08-01', 30, DATE '2023-01-01', 180, DATE '2023-07-01', 60),
+  (13, DATE '2023-08-01', 30, DATE '2023-01-01', 180, DATE '2023-07-01', 60),
+  (14, DATE '2023-08-01', 30, DATE '2023-01-01', 180, DATE '2023-07-01', 60),
+  (15, DATE '2023-08-01', 30, DATE '2023-01-01', 180, DATE '2023-07-01', 60);
+
+-- Seeding the usual tasks for every plant
+INSERT INTO care_tasks (plant_id, name, interval_days)
+SELECT plant_id, 'Fertilise', feed_interval FROM old_schedules;
+INSERT INTO care_tasks (plant_id, name, interval_days)
+SELECT plant_id, 'Repot', potting_interval FROM old_schedules;
+INSERT INTO care_tasks (plant_id, name, interval_days, optional)
+SELECT plant_id, 'Prune', pruning_interval, TRUE FROM old_schedules;
+INSERT INTO care_tasks (plant_id, name, interval_days)
+VALUES
+  (1, 'Water', 7),
+  (1, 'Rotate', 14),
+  (6, 'Mist', 3),
+  (6, 'Pest check', 21);
+
+-- Seeding care history for every task from the same schedules, the other tasks were last done
+-- on 2023-09-01
+INSERT INTO care_events (task_id, user_id, time_made)
+SELECT t.task_id, p.user_id,
+  CASE t.name
+    WHEN 'Fertilise' THEN o.fed
+    WHEN 'Repot' THEN o.potted
+    WHEN 'Prune' THEN o.pruned
+    ELSE DATE '2023-09-01'
+  END
+FROM care_tasks t
+JOIN plants p ON p.plant_id = t.plant_id
+JOIN old_schedules o ON o.plant_id = t.plant_id;
+DROP TABLE old_schedules;
+
+-- The fiddle leaf's last feed was a weak one
+UPDATE care_events SET note = 'Half strength fertiliser'
+WHERE task_id = (SELECT task_id FROM care_tasks WHERE plant_id = 1 AND name = 'Fertilise');
+
+-- Default notification preferences for everyone, Bob only wants a weekly digest of watering
+INSERT INTO notification_preferences (user_id) SELECT user_id FROM users;
+UPDATE notification_preferences
+SET frequency = 'weekly', weekly_day = 6, send_hour = 9, timezone = 'Europe/London', task_names = '{Water}'
+WHERE user_id = 2;
+
+-- Everyone has followed the link in their verification email
+UPDATE users SET email_verified_at = NOW();
@@ -0,0 +1,35 @@
+-- The schema from the old resources/script.sql, so databases made from it are taken as the
+-- starting point. 20231001000001_upgrade_legacy_schema.sql brings them, and fresh ones, up to date.
+CREATE TABLE IF NOT EXISTS users (
+	user_id SERIAL PRIMARY KEY,
+	first_name TEXT NOT NULL,
+	last_name TEXT NOT NULL,
+	email TEXT NOT NULL,
+	phone TEXT -- potentially for phone alerts? todo later
+);
+
+CREATE TABLE IF NOT EXISTS plants (
+	plant_id SERIAL PRIMARY KEY,
+	user_id INTEGER REFERENCES users(user_id) NOT NULL,
+	botanical_name TEXT NOT NULL,
+	common_name TEXT NOT NULL,
+	last_fed DATE NOT NULL,
+	feed_interval INTEGER NOT NULL, -- days until next feed
+	last_potted DATE NOT NULL,
+	potting_interval INTEGER NOT NULL, -- days until next potting
+	last_pruned DATE NOT NULL,
+	pruning_interval INTEGER NOT NULL --days until next pruning
+);
+-- represents a single comment
+CREATE TABLE IF NOT EXISTS comments (
+	plant_id INTEGER REFERENCES plants(plant_id) NOT NULL,
+	user_id INTEGER REFERENCES users(user_id) NOT NULL,
+	time_made TIMESTAMP NOT NULL,
+	comment TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS photos (
+	plant_id INTEGER REFERENCES plants(plant_id) NOT NULL,
+	user_id INTEGER REFERENCES users(user_id) NOT NULL,
+	photo_uri TEXT NOT NULL
+);
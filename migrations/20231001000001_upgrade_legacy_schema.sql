@@ -0,0 +1,83 @@
+-- Everything since the old resources/script.sql. Each step is skipped when it's already been
+-- done, as databases made from later versions of that script have some of it.
+
+-- accounts from before passwords can't sign in until they reset theirs, '!' never matches a bcrypt hash
+ALTER TABLE users ADD COLUMN IF NOT EXISTS password_hash TEXT; -- bcrypt
+UPDATE users SET password_hash = '!' WHERE password_hash IS NULL;
+ALTER TABLE users ALTER COLUMN password_hash SET NOT NULL;
+DO $$
+BEGIN
+	IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'users_email_key') THEN
+		ALTER TABLE users ADD CONSTRAINT users_email_key UNIQUE (email);
+	END IF;
+END $$;
+
+-- a recurring job for a plant, e.g. watering, misting, repotting
+CREATE TABLE IF NOT EXISTS care_tasks (
+	task_id SERIAL PRIMARY KEY,
+	plant_id INTEGER REFERENCES plants(plant_id) ON DELETE CASCADE NOT NULL,
+	name TEXT NOT NULL,
+	interval_days INTEGER, -- days between each time the task is done, NULL when not tracked
+	optional BOOLEAN NOT NULL DEFAULT FALSE, -- optional tasks never flag a plant as needing attention
+	UNIQUE (plant_id, name)
+);
+-- every time a task was done. a task's last done date is derived from the latest row
+CREATE TABLE IF NOT EXISTS care_events (
+	event_id SERIAL PRIMARY KEY,
+	task_id INTEGER REFERENCES care_tasks(task_id) ON DELETE CASCADE NOT NULL,
+	user_id INTEGER REFERENCES users(user_id) NOT NULL, -- who did the work
+	time_made TIMESTAMPTZ NOT NULL,
+	note TEXT
+);
+
+-- the fixed feed/pot/prune schedules become tasks, and each plant's last dates their first events,
+-- before the columns go. all three flagged a plant as needing attention, so none are optional
+DO $$
+BEGIN
+	IF EXISTS (
+		SELECT 1 FROM information_schema.columns
+		WHERE table_schema = current_schema() AND table_name = 'plants' AND column_name = 'last_fed'
+	) THEN
+		-- the old add form filled in 100000 and 3000-11-11 for anything left blank, those tasks
+		-- aren't tracked and were never done
+		INSERT INTO care_tasks (plant_id, name, interval_days)
+		SELECT plant_id, 'Fertilise', NULLIF(feed_interval, 100000) FROM plants;
+		INSERT INTO care_tasks (plant_id, name, interval_days)
+		SELECT plant_id, 'Repot', NULLIF(potting_interval, 100000) FROM plants;
+		INSERT INTO care_tasks (plant_id, name, interval_days)
+		SELECT plant_id, 'Prune', NULLIF(pruning_interval, 100000) FROM plants;
+
+		INSERT INTO care_events (task_id, user_id, time_made)
+		SELECT task_id, user_id, last_done
+		FROM (
+			SELECT t.task_id, p.user_id,
+				CASE t.name
+					WHEN 'Fertilise' THEN p.last_fed
+					WHEN 'Repot' THEN p.last_potted
+					ELSE p.last_pruned
+				END AS last_done
+			FROM care_tasks t JOIN plants p ON p.plant_id = t.plant_id
+		) AS legacy
+		WHERE last_done <> DATE '3000-11-11';
+
+		ALTER TABLE plants
+			DROP COLUMN last_fed,
+			DROP COLUMN feed_interval,
+			DROP COLUMN last_potted,
+			DROP COLUMN potting_interval,
+			DROP COLUMN last_pruned,
+			DROP COLUMN pruning_interval;
+	END IF;
+END $$;
+
+-- represents a single comment, i.e. a journal note on a plant
+ALTER TABLE comments ADD COLUMN IF NOT EXISTS comment_id SERIAL PRIMARY KEY;
+ALTER TABLE comments ALTER COLUMN time_made TYPE TIMESTAMPTZ;
+ALTER TABLE comments ADD COLUMN IF NOT EXISTS time_edited TIMESTAMPTZ; -- NULL until the comment is edited
+ALTER TABLE comments ADD COLUMN IF NOT EXISTS search_vector TSVECTOR
+	GENERATED ALWAYS AS (to_tsvector('english', comment)) STORED;
+CREATE INDEX IF NOT EXISTS comments_search_idx ON comments USING GIN (search_vector);
+
+ALTER TABLE photos ADD COLUMN IF NOT EXISTS photo_id SERIAL PRIMARY KEY;
+ALTER TABLE photos ADD COLUMN IF NOT EXISTS thumbnail_uri TEXT; -- NULL for external photos
+ALTER TABLE photos ADD COLUMN IF NOT EXISTS time_made TIMESTAMPTZ NOT NULL DEFAULT NOW();
@@ -0,0 +1,7 @@
+-- one row per reminder digest sent, so a restart never emails a user twice on the same day
+CREATE TABLE reminder_digests (
+	user_id INTEGER REFERENCES users(user_id) ON DELETE CASCADE NOT NULL,
+	digest_date DATE NOT NULL, -- the UTC day the digest was for
+	sent_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	PRIMARY KEY (user_id, digest_date)
+);
@@ -0,0 +1,18 @@
+CREATE TYPE digest_frequency AS ENUM ('daily', 'weekly');
+
+-- how and when each user wants to hear about plants that need attention. every user has a row
+CREATE TABLE notification_preferences (
+	user_id INTEGER PRIMARY KEY REFERENCES users(user_id) ON DELETE CASCADE,
+	channels TEXT[] NOT NULL DEFAULT '{email}', -- empty turns reminders off
+	frequency digest_frequency NOT NULL DEFAULT 'daily',
+	weekly_day SMALLINT NOT NULL DEFAULT 1 CHECK (weekly_day BETWEEN 1 AND 7), -- ISO weekday, 1 is Monday
+	send_hour SMALLINT NOT NULL DEFAULT 8 CHECK (send_hour BETWEEN 0 AND 23), -- in the user's timezone
+	timezone TEXT NOT NULL DEFAULT 'UTC', -- a name from pg_timezone_names
+	task_names TEXT[], -- NULL includes every task
+	quiet_days SMALLINT[] NOT NULL DEFAULT '{}' -- ISO weekdays that never get a digest
+);
+
+INSERT INTO notification_preferences (user_id) SELECT user_id FROM users;
+
+-- digests are now sent on the user's own calendar day
+COMMENT ON COLUMN reminder_digests.digest_date IS 'the day the digest was for, in the user''s timezone';
@@ -0,0 +1,8 @@
+CREATE TYPE push_service AS ENUM ('ntfy', 'gotify');
+
+-- where the webhook and push channels send to, only used while the channel is enabled
+ALTER TABLE notification_preferences
+	ADD COLUMN webhook_url TEXT,
+	ADD COLUMN push_service push_service NOT NULL DEFAULT 'ntfy',
+	ADD COLUMN push_url TEXT, -- ntfy: the topic URL, gotify: the server URL
+	ADD COLUMN push_token TEXT; -- ntfy access token or gotify application token, optional for ntfy
@@ -0,0 +1,13 @@
+-- one row per signed in device. access tokens name the session they were issued for, and the
+-- refresh token is replaced every time it is used
+CREATE TABLE sessions (
+	session_id SERIAL PRIMARY KEY,
+	user_id INTEGER REFERENCES users(user_id) ON DELETE CASCADE NOT NULL,
+	refresh_token_hash TEXT NOT NULL, -- sha256, hex
+	previous_token_hash TEXT, -- the one it replaced. seeing it again means the old one was stolen
+	time_made TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	time_refreshed TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	expires_at TIMESTAMPTZ NOT NULL, -- pushed back on every refresh
+	revoked_at TIMESTAMPTZ
+);
+CREATE INDEX sessions_user_id ON sessions (user_id);
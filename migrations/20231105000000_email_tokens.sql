@@ -0,0 +1,17 @@
+-- NULL until the user follows the link emailed after signup. everyone who signed up before
+-- there was a link to follow keeps getting their reminders
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMPTZ;
+UPDATE users SET email_verified_at = NOW();
+
+CREATE TYPE email_token_purpose AS ENUM ('verify_email', 'reset_password');
+
+-- links emailed to users. the link holds a signed token naming its row, the row is what makes
+-- it single use
+CREATE TABLE email_tokens (
+	token_id SERIAL PRIMARY KEY,
+	user_id INTEGER REFERENCES users(user_id) ON DELETE CASCADE NOT NULL,
+	purpose email_token_purpose NOT NULL,
+	time_made TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	expires_at TIMESTAMPTZ NOT NULL,
+	used_at TIMESTAMPTZ
+);
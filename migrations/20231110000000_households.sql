@@ -0,0 +1,39 @@
+-- a shared collection of plants. everyone in a household sees and looks after all of its plants
+CREATE TABLE households (
+	household_id SERIAL PRIMARY KEY,
+	name TEXT NOT NULL,
+	time_made TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE household_members (
+	household_id INTEGER REFERENCES households(household_id) ON DELETE CASCADE NOT NULL,
+	user_id INTEGER REFERENCES users(user_id) ON DELETE CASCADE NOT NULL,
+	joined_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	PRIMARY KEY (household_id, user_id)
+);
+CREATE INDEX household_members_user_id ON household_members (user_id);
+
+-- emailed invitations to join a household. the link holds a signed token naming its row, the
+-- row is what makes it single use
+CREATE TABLE household_invitations (
+	invitation_id SERIAL PRIMARY KEY,
+	household_id INTEGER REFERENCES households(household_id) ON DELETE CASCADE NOT NULL,
+	email TEXT NOT NULL,
+	invited_by INTEGER REFERENCES users(user_id) ON DELETE CASCADE NOT NULL,
+	time_made TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	expires_at TIMESTAMPTZ NOT NULL,
+	accepted_at TIMESTAMPTZ
+);
+
+-- every existing user gets a household of their own holding the plants they already have.
+-- plants.user_id stays as who added the plant
+ALTER TABLE households ADD COLUMN founder_id INTEGER;
+INSERT INTO households (name, founder_id)
+SELECT first_name || '''s plants', user_id FROM users;
+INSERT INTO household_members (household_id, user_id)
+SELECT household_id, founder_id FROM households;
+ALTER TABLE plants ADD COLUMN household_id INTEGER REFERENCES households(household_id) ON DELETE CASCADE;
+UPDATE plants p SET household_id = h.household_id FROM households h WHERE h.founder_id = p.user_id;
+ALTER TABLE plants ALTER COLUMN household_id SET NOT NULL;
+ALTER TABLE households DROP COLUMN founder_id;
+CREATE INDEX plants_household_id ON plants (household_id);
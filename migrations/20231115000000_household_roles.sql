@@ -0,0 +1,15 @@
+-- what a member may do in a household. viewers can only look, caretakers can also log care,
+-- write notes and add photos, owners can also change plants, invite people and manage members
+CREATE TYPE household_role AS ENUM ('owner', 'caretaker', 'viewer');
+
+-- everyone in a household so far could do anything in it
+ALTER TABLE household_members ADD COLUMN role household_role NOT NULL DEFAULT 'owner';
+ALTER TABLE household_members ALTER COLUMN role DROP DEFAULT;
+-- a temporary grant, such as for a plant-sitter, stops working at this time. NULL for members
+-- who stay
+ALTER TABLE household_members ADD COLUMN expires_at TIMESTAMPTZ;
+
+-- the role and how long it lasts are decided when inviting
+ALTER TABLE household_invitations ADD COLUMN role household_role NOT NULL DEFAULT 'caretaker';
+ALTER TABLE household_invitations ALTER COLUMN role DROP DEFAULT;
+ALTER TABLE household_invitations ADD COLUMN grant_expires_at TIMESTAMPTZ;
@@ -0,0 +1,17 @@
+-- a link that lets someone without an account tick off care in a household for a while, such
+-- as a neighbour looking after the plants over a holiday. the link holds a signed token naming
+-- its row, the row is what can be revoked
+CREATE TABLE sitter_links (
+	link_id SERIAL PRIMARY KEY,
+	household_id INTEGER REFERENCES households(household_id) ON DELETE CASCADE NOT NULL,
+	created_by INTEGER REFERENCES users(user_id) ON DELETE CASCADE NOT NULL,
+	-- who it's for, shown in the care history
+	sitter_name TEXT NOT NULL,
+	starts_at TIMESTAMPTZ NOT NULL,
+	expires_at TIMESTAMPTZ NOT NULL,
+	time_made TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	revoked_at TIMESTAMPTZ
+);
+
+-- care ticked off through a sitter link. user_id is whoever made the link
+ALTER TABLE care_events ADD COLUMN sitter_link_id INTEGER REFERENCES sitter_links(link_id) ON DELETE SET NULL;
@@ -0,0 +1,18 @@
+-- where plants live in a household, so they can be looked after room by room
+CREATE TYPE location_kind AS ENUM ('room', 'windowsill', 'balcony', 'greenhouse');
+CREATE TYPE light_level AS ENUM ('low', 'medium', 'bright', 'direct');
+-- the way the window or balcony faces
+CREATE TYPE orientation AS ENUM ('north', 'east', 'south', 'west');
+
+CREATE TABLE locations (
+	location_id SERIAL PRIMARY KEY,
+	household_id INTEGER REFERENCES households(household_id) ON DELETE CASCADE NOT NULL,
+	name TEXT NOT NULL,
+	kind location_kind NOT NULL,
+	light_level light_level,
+	orientation orientation,
+	UNIQUE (household_id, name)
+);
+
+-- a plant's location is in its own household, NULL for a plant that hasn't been placed
+ALTER TABLE plants ADD COLUMN location_id INTEGER REFERENCES locations(location_id) ON DELETE SET NULL;
@@ -0,0 +1,24 @@
+-- free-form labels on plants such as pet-toxic or propagation, stored lower case with hyphens
+-- for spaces so the same tag is always spelt the same way
+CREATE TABLE plant_tags (
+	plant_id INTEGER REFERENCES plants(plant_id) ON DELETE CASCADE NOT NULL,
+	tag TEXT NOT NULL,
+	PRIMARY KEY (plant_id, tag)
+);
+CREATE INDEX plant_tags_tag_idx ON plant_tags (tag);
+
+-- which plants a filter keeps by their required tasks
+CREATE TYPE due_filter AS ENUM ('any', 'this_week', 'due', 'overdue');
+
+-- a search the user has named, shown as a button so it can be run again in one click. a plant
+-- has to have every one of the tags to match
+CREATE TABLE saved_filters (
+	filter_id SERIAL PRIMARY KEY,
+	user_id INTEGER REFERENCES users(user_id) ON DELETE CASCADE NOT NULL,
+	name TEXT NOT NULL,
+	search_string TEXT NOT NULL DEFAULT '',
+	tags TEXT[] NOT NULL DEFAULT '{}',
+	due due_filter NOT NULL DEFAULT 'any',
+	time_made TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	UNIQUE (user_id, name)
+);
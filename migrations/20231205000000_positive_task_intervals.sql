@@ -0,0 +1,4 @@
+-- An interval under a day can't be scheduled. The web form used to let them through, those
+-- tasks stop being tracked rather than being due forever
+UPDATE care_tasks SET interval_days = NULL WHERE interval_days <= 0;
+ALTER TABLE care_tasks ADD CONSTRAINT care_tasks_interval_days_check CHECK (interval_days > 0);
@@ -0,0 +1,20 @@
+#!/usr/bin/env bash
+# Measures how /api/v1/plants throughput scales with concurrent clients, against a server that
+# is already running with the dev fixtures loaded (`cargo run -- seed`, then `cargo run`).
+# Needs curl, jq and oha (https://github.com/hatoo/oha).
+#
+#   scripts/load-test.sh [base url] [requests per run]
+set -euo pipefail
+
+BASE_URL=${1:-http://localhost:3000}
+REQUESTS=${2:-2000}
+
+TOKEN=$(curl -sf "$BASE_URL/api/v1/token" -H 'Content-Type: application/json' \
+  -d '{"email": "alice.johnson@example.com", "password": "password"}' | jq -r .access_token)
+
+printf '%-12s %s\n' concurrency 'requests/s'
+for concurrency in 1 5 20 50; do
+  rps=$(oha --no-tui --json -n "$REQUESTS" -c "$concurrency" \
+    -H "Authorization: Bearer $TOKEN" "$BASE_URL/api/v1/plants" | jq .summary.requestsPerSec)
+  printf '%-12s %.0f\n' "$concurrency" "$rps"
+done
@@ -0,0 +1,185 @@
+use anyhow::{anyhow, Result};
+use jsonwebtoken::{decode, encode, Header, Validation};
+use leptos::view;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use time::{Duration, OffsetDateTime};
+
+use crate::components::*;
+use crate::error::{AppError, AppResult};
+use crate::{config, db_api, notify, KEYS};
+
+/// What an emailed link is for. Each has its own page and lifetime, and a token made for one
+/// is rejected by the other.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "email_token_purpose", rename_all = "snake_case")]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+}
+
+impl TokenPurpose {
+    fn lifetime(self) -> Duration {
+        match self {
+            TokenPurpose::VerifyEmail => Duration::days(7),
+            TokenPurpose::ResetPassword => Duration::hours(1),
+        }
+    }
+
+    fn path(self) -> &'static str {
+        match self {
+            TokenPurpose::VerifyEmail => "/verify-email",
+            TokenPurpose::ResetPassword => "/reset-password",
+        }
+    }
+}
+
+/// The token in an emailed link, signed with the JWT secret so it can't be made up. The
+/// `email_tokens` row it names is what makes it single use.
+#[derive(Serialize, Deserialize, Debug)]
+struct EmailClaims {
+    purpose: TokenPurpose,
+    token_id: i32,
+    user_id: i32,
+    exp: usize,
+}
+
+/// Emails `to` a link that verifies it as the address of `user_id`.
+pub async fn send_verification(pool: &Pool<Postgres>, user_id: i32, to: &str) -> Result<()> {
+    let link = link(pool, user_id, TokenPurpose::VerifyEmail).await?;
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <VerifyEmailMessage
+                link=link
+            />
+        }
+    });
+    notify::send_email(&config().smtp, to, "Verify your email address", html).await
+}
+
+/// Emails `to` a link to choose a new password for `user_id`.
+pub async fn send_password_reset(pool: &Pool<Postgres>, user_id: i32, to: &str) -> Result<()> {
+    let link = link(pool, user_id, TokenPurpose::ResetPassword).await?;
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <ResetPasswordMessage
+                link=link
+            />
+        }
+    });
+    notify::send_email(&config().smtp, to, "Reset your password", html).await
+}
+
+async fn link(pool: &Pool<Postgres>, user_id: i32, purpose: TokenPurpose) -> Result<String> {
+    if !config().smtp.is_enabled() {
+        return Err(anyhow!("Email isn't set up on this server"));
+    }
+    let expires_at = OffsetDateTime::now_utc() + purpose.lifetime();
+    let token_id = db_api::insert_email_token(pool, user_id, purpose, expires_at).await?;
+    let token = sign(&EmailClaims {
+        purpose,
+        token_id,
+        user_id,
+        exp: expires_at.unix_timestamp() as usize,
+    })?;
+    Ok(format!(
+        "{}{}?token={}",
+        config().server.public_url.trim_end_matches('/'),
+        purpose.path(),
+        token
+    ))
+}
+
+/// Whether `token` looks like a live link for `purpose`, without using it up. For showing a
+/// form that `redeem` then checks properly.
+pub fn is_plausible(token: &str, purpose: TokenPurpose) -> bool {
+    verify(token, purpose).is_some()
+}
+
+/// Uses up the link, returning the user it was for.
+pub async fn redeem(pool: &Pool<Postgres>, token: &str, purpose: TokenPurpose) -> AppResult<i32> {
+    let expired = || AppError::bad_request("That link has expired or was already used");
+    let claims = verify(token, purpose).ok_or_else(expired)?;
+    if !db_api::use_email_token(pool, claims.token_id, claims.user_id, purpose).await? {
+        return Err(expired());
+    }
+    Ok(claims.user_id)
+}
+
+fn sign(claims: &EmailClaims) -> Result<String> {
+    Ok(encode(&Header::default(), claims, &KEYS.encoding)?)
+}
+
+fn verify(token: &str, purpose: TokenPurpose) -> Option<EmailClaims> {
+    let claims = decode::<EmailClaims>(token, &KEYS.decoding, &Validation::default())
+        .ok()?
+        .claims;
+    Some(claims).filter(|claims| claims.purpose == purpose)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(purpose: TokenPurpose, lifetime: Duration) -> EmailClaims {
+        EmailClaims {
+            purpose,
+            token_id: 3,
+            user_id: 5,
+            exp: (OffsetDateTime::now_utc() + lifetime).unix_timestamp() as usize,
+        }
+    }
+
+    #[test]
+    fn tokens_only_work_for_their_purpose() {
+        crate::tests::init_config();
+        let token = sign(&claims(TokenPurpose::ResetPassword, Duration::hours(1))).unwrap();
+        assert_eq!(
+            verify(&token, TokenPurpose::ResetPassword).unwrap().user_id,
+            5
+        );
+        assert!(verify(&token, TokenPurpose::VerifyEmail).is_none());
+        assert!(verify(&token.replace('.', ".x"), TokenPurpose::ResetPassword).is_none());
+    }
+
+    #[test]
+    fn expired_tokens_are_rejected() {
+        crate::tests::init_config();
+        let token = sign(&claims(TokenPurpose::VerifyEmail, Duration::hours(-2))).unwrap();
+        assert!(verify(&token, TokenPurpose::VerifyEmail).is_none());
+    }
+
+    #[test]
+    fn session_tokens_arent_links() {
+        crate::tests::init_config();
+        let token = crate::auth_memes::create_token(5, 3).unwrap();
+        assert!(verify(&token, TokenPurpose::ResetPassword).is_none());
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn links_work_once(pool: Pool<Postgres>) {
+        let f = crate::tests::fixture(pool).await;
+        let pool = &f.app.db_pool;
+        let purpose = TokenPurpose::ResetPassword;
+        let expires_at = OffsetDateTime::now_utc() + purpose.lifetime();
+        let token_id = db_api::insert_email_token(pool, f.owner, purpose, expires_at)
+            .await
+            .unwrap();
+        let token = sign(&EmailClaims {
+            purpose,
+            token_id,
+            user_id: f.owner,
+            exp: expires_at.unix_timestamp() as usize,
+        })
+        .unwrap();
+
+        assert_eq!(redeem(pool, &token, purpose).await.unwrap(), f.owner);
+        assert!(matches!(
+            redeem(pool, &token, purpose).await,
+            Err(AppError::BadRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,1007 @@
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use axum_extra::extract::WithRejection;
+use serde::Deserialize;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+use crate::auth_memes::{check_password, check_role, AuthBody, AuthError, Claims};
+use crate::error::{ApiError, ApiResult, AppError, AppResult, ErrorBody};
+use crate::households::{Household, HouseholdRole};
+use crate::locations::{LightLevel, Location, LocationKind, Orientation};
+use crate::{
+    db_api, filters, photos, read_photo_upload, remove_photo, save_photo, sessions, AppState,
+    CareEvent, CareTask, CommentForm, Comments, Plant, PlantPhoto, User, UserLogin,
+    MAX_PHOTO_BYTES,
+};
+
+/// The versioned JSON API, nested under `/api/v1`. Everything but `/token` and
+/// `/token/refresh` needs an `Authorization: Bearer` header holding an access token from one of
+/// them. Errors are a status code with a `{"error": "..."}` body.
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/token", post(post_token))
+        .route("/token/refresh", post(post_refresh_token))
+        .route("/logout", post(post_logout))
+        .route("/logout-everywhere", post(post_logout_everywhere))
+        .route("/me", get(get_me))
+        .route("/households", get(list_households))
+        .route("/locations", get(list_locations))
+        .route("/plants", get(list_plants).post(create_plant))
+        .route("/plants/:plant_id", get(get_plant).put(update_plant))
+        .route("/plants/:plant_id/events", get(list_care_events))
+        .route(
+            "/plants/:plant_id/tasks/:task_id/events",
+            post(create_care_event),
+        )
+        .route(
+            "/plants/:plant_id/comments",
+            get(list_comments).post(create_comment),
+        )
+        .route(
+            "/plants/:plant_id/comments/:comment_id",
+            put(update_comment).delete(delete_comment),
+        )
+        .route(
+            "/plants/:plant_id/photos",
+            get(list_photos)
+                .post(upload_photo)
+                .layer(DefaultBodyLimit::max(MAX_PHOTO_BYTES)),
+        )
+        .route("/plants/:plant_id/photos/:photo_id", delete(delete_photo))
+        .route("/photos/:key", get(get_photo))
+}
+
+/// The OpenAPI document of everything in `router`, served at `/api/openapi.json`.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Plant Track API"),
+    paths(
+        post_token,
+        post_refresh_token,
+        post_logout,
+        post_logout_everywhere,
+        get_me,
+        list_households,
+        list_locations,
+        list_plants,
+        create_plant,
+        get_plant,
+        update_plant,
+        list_care_events,
+        create_care_event,
+        list_comments,
+        create_comment,
+        update_comment,
+        delete_comment,
+        list_photos,
+        upload_photo,
+        delete_photo,
+        get_photo,
+    ),
+    components(schemas(
+        AuthBody,
+        CareEvent,
+        CareEventBody,
+        CareTask,
+        CommentForm,
+        Comments,
+        ErrorBody,
+        Household,
+        LightLevel,
+        Location,
+        LocationKind,
+        Orientation,
+        PhotoUpload,
+        Plant,
+        PlantBody,
+        PlantPhoto,
+        RefreshBody,
+        TaskBody,
+        User,
+        UserLogin,
+    )),
+    modifiers(&BearerAuth),
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+pub async fn get_openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// A browsable page for the OpenAPI document, rendered by Redoc from its CDN.
+pub async fn get_api_docs() -> Html<&'static str> {
+    Html(
+        r#"<!DOCTYPE html>
+<html>
+  <head>
+    <title>Plant Track API</title>
+    <meta charset="utf-8"/>
+  </head>
+  <body>
+    <redoc spec-url="/api/openapi.json"></redoc>
+    <script src="https://cdn.redoc.ly/redoc/latest/bundles/redoc.standalone.js"></script>
+  </body>
+</html>"#,
+    )
+}
+
+/// What `require_role` checks for the HTML routes. The API has no middleware between `Claims`
+/// and the handler, so handlers that change anything call this first.
+async fn require_plant_role(
+    app: &AppState,
+    user_id: i32,
+    plant_id: i32,
+    needed: HouseholdRole,
+) -> AppResult<()> {
+    check_role(
+        db_api::get_plant_role(&app.db_pool, user_id, plant_id).await?,
+        needed,
+    )
+}
+
+/// A plant to create, or the new state of one to update. On update, tasks without a
+/// `task_id` are added and tasks left out are kept as they are.
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct PlantBody {
+    /// Which of the user's households to add the plant to, by default the one they've been in
+    /// longest. Ignored on update, a plant stays in its household.
+    pub household_id: Option<i32>,
+    /// One of the household's locations, or `null` to leave the plant unplaced.
+    pub location_id: Option<i32>,
+    pub botanical_name: String,
+    pub common_name: String,
+    /// Replaces the plant's tags, leaving it out clears them.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub tasks: Vec<TaskBody>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct TaskBody {
+    pub task_id: Option<i32>,
+    pub name: String,
+    pub interval_days: Option<i32>,
+    pub last_done: Option<time::Date>,
+    #[serde(default)]
+    pub optional: bool,
+}
+
+impl PlantBody {
+    pub fn into_plant(self, user_id: i32, plant_id: i32) -> AppResult<Plant> {
+        if self.common_name.trim().is_empty() && self.botanical_name.trim().is_empty() {
+            return Err(AppError::bad_request(
+                "A plant needs a common or botanical name",
+            ));
+        }
+        let mut tasks = Vec::new();
+        for task in self.tasks {
+            let task = CareTask {
+                task_id: task.task_id.unwrap_or(-1),
+                plant_id,
+                name: task.name.trim().to_owned(),
+                interval_days: task.interval_days,
+                last_done: task.last_done,
+                optional: task.optional,
+            };
+            task.validate()?;
+            tasks.push(task);
+        }
+        Ok(Plant {
+            plant_id,
+            user_id,
+            household_id: self.household_id.unwrap_or(-1),
+            location_id: self.location_id,
+            botanical_name: self.botanical_name,
+            common_name: self.common_name,
+            tags: filters::tidy_tags(self.tags.iter().map(String::as_str)),
+            tasks,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct RefreshBody {
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct CareEventBody {
+    pub note: Option<String>,
+}
+
+/// The multipart form a photo is uploaded with.
+#[derive(ToSchema)]
+pub struct PhotoUpload {
+    /// A JPEG, PNG, WebP or GIF image.
+    #[schema(value_type = String, format = Binary)]
+    pub photo: Vec<u8>,
+}
+
+/// Exchanges an email and password for an access token and a refresh token, starting a new
+/// session.
+#[utoipa::path(
+    post,
+    path = "/api/v1/token",
+    tag = "auth",
+    request_body = UserLogin,
+    responses(
+        (status = 200, description = "Tokens for a new session", body = AuthBody),
+        (status = 401, description = "Wrong email or password", body = ErrorBody),
+    ),
+)]
+async fn post_token(
+    State(app): State<AppState>,
+    WithRejection(Json(login), _): WithRejection<Json<UserLogin>, ApiError>,
+) -> ApiResult<Json<AuthBody>> {
+    let pool = &app.db_pool;
+    let user = db_api::get_user_from_email(pool, login.email)
+        .await
+        .map_err(|_| AuthError::WrongCredentials)?;
+    if !check_password(&login.password, &user.password_hash) {
+        return Err(AuthError::WrongCredentials.into());
+    }
+    Ok(Json(AuthBody::new(
+        sessions::start(pool, user.user_id).await?,
+    )))
+}
+
+/// Exchanges a refresh token for a new access token and refresh token. The one sent stops
+/// working, and sending it again later signs the whole session out.
+#[utoipa::path(
+    post,
+    path = "/api/v1/token/refresh",
+    tag = "auth",
+    request_body = RefreshBody,
+    responses(
+        (status = 200, description = "New tokens for the same session", body = AuthBody),
+        (status = 401, description = "Unknown, used or revoked refresh token", body = ErrorBody),
+    ),
+)]
+async fn post_refresh_token(
+    State(app): State<AppState>,
+    WithRejection(Json(body), _): WithRejection<Json<RefreshBody>, ApiError>,
+) -> ApiResult<Json<AuthBody>> {
+    let tokens = sessions::refresh(&app.db_pool, &body.refresh_token).await?;
+    Ok(Json(AuthBody::new(tokens)))
+}
+
+/// Ends the session the bearer token belongs to. Its access tokens keep working until they
+/// expire, but it can't be refreshed any more.
+#[utoipa::path(
+    post,
+    path = "/api/v1/logout",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Signed out"),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn post_logout(State(app): State<AppState>, claims: Claims) -> ApiResult<StatusCode> {
+    db_api::revoke_session(&app.db_pool, claims.user_id, claims.session_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Ends every session of the user, on every device and in the browser too.
+#[utoipa::path(
+    post,
+    path = "/api/v1/logout-everywhere",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Signed out everywhere"),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn post_logout_everywhere(
+    State(app): State<AppState>,
+    claims: Claims,
+) -> ApiResult<StatusCode> {
+    db_api::revoke_all_sessions(&app.db_pool, claims.user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/me",
+    tag = "auth",
+    responses(
+        (status = 200, description = "The signed in user", body = User),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn get_me(State(app): State<AppState>, claims: Claims) -> ApiResult<Json<User>> {
+    let pool = &app.db_pool;
+    Ok(Json(db_api::get_user(pool, claims.user_id).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/households",
+    tag = "households",
+    responses(
+        (status = 200, description = "The user's households, the one they've been in longest first", body = [Household]),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn list_households(
+    State(app): State<AppState>,
+    claims: Claims,
+) -> ApiResult<Json<Vec<Household>>> {
+    let pool = &app.db_pool;
+    Ok(Json(db_api::get_households(pool, claims.user_id).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/locations",
+    tag = "households",
+    responses(
+        (status = 200, description = "Where plants can live in each of the user's households, by household and then name", body = [Location]),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn list_locations(
+    State(app): State<AppState>,
+    claims: Claims,
+) -> ApiResult<Json<Vec<Location>>> {
+    let pool = &app.db_pool;
+    Ok(Json(db_api::get_locations(pool, claims.user_id).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/plants",
+    tag = "plants",
+    responses(
+        (status = 200, description = "Every plant in the user's households", body = [Plant]),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn list_plants(State(app): State<AppState>, claims: Claims) -> ApiResult<Json<Vec<Plant>>> {
+    let pool = &app.db_pool;
+    let plants = db_api::get_all_plants(pool, claims.user_id, "-1".to_owned()).await?;
+    Ok(Json(plants))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/plants",
+    tag = "plants",
+    request_body = PlantBody,
+    responses(
+        (status = 201, description = "The new plant", body = Plant),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+        (status = 403, description = "The user's role in the household doesn't allow it", body = ErrorBody),
+        (status = 400, description = "The request failed validation", body = ErrorBody),
+        (status = 404, description = "The location isn't in the household", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn create_plant(
+    State(app): State<AppState>,
+    claims: Claims,
+    WithRejection(Json(body), _): WithRejection<Json<PlantBody>, ApiError>,
+) -> ApiResult<(StatusCode, Json<Plant>)> {
+    let pool = &app.db_pool;
+    let mut plant = body.into_plant(claims.user_id, -1)?;
+    if plant.household_id == -1 {
+        plant.household_id = db_api::get_default_household(pool, claims.user_id).await?;
+    }
+    let role = db_api::get_role(pool, claims.user_id, plant.household_id).await?;
+    check_role(role, HouseholdRole::Owner)?;
+    let plant_id = db_api::add_plant_to_db(pool, plant).await?;
+    let plant = db_api::get_plant_from_id(pool, claims.user_id, plant_id).await?;
+    Ok((StatusCode::CREATED, Json(plant)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/plants/{plant_id}",
+    tag = "plants",
+    params(
+        ("plant_id" = i32, Path, description = "A plant of the signed in user"),
+    ),
+    responses(
+        (status = 200, description = "The plant and its care tasks", body = Plant),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+        (status = 404, description = "Not found or not the user's", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn get_plant(
+    State(app): State<AppState>,
+    claims: Claims,
+    Path(plant_id): Path<i32>,
+) -> ApiResult<Json<Plant>> {
+    let pool = &app.db_pool;
+    Ok(Json(
+        db_api::get_plant_from_id(pool, claims.user_id, plant_id).await?,
+    ))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/plants/{plant_id}",
+    tag = "plants",
+    params(
+        ("plant_id" = i32, Path, description = "A plant of the signed in user"),
+    ),
+    request_body = PlantBody,
+    responses(
+        (status = 200, description = "The updated plant", body = Plant),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+        (status = 403, description = "The user's role in the household doesn't allow it", body = ErrorBody),
+        (status = 404, description = "Not found or not the user's", body = ErrorBody),
+        (status = 400, description = "The request failed validation", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn update_plant(
+    State(app): State<AppState>,
+    claims: Claims,
+    Path(plant_id): Path<i32>,
+    WithRejection(Json(body), _): WithRejection<Json<PlantBody>, ApiError>,
+) -> ApiResult<Json<Plant>> {
+    let pool = &app.db_pool;
+    require_plant_role(&app, claims.user_id, plant_id, HouseholdRole::Owner).await?;
+    let current = db_api::get_plant_from_id(pool, claims.user_id, plant_id).await?;
+    let plant = body.into_plant(claims.user_id, plant_id)?;
+    if let Some(task) = plant.tasks.iter().find(|task| {
+        task.task_id != -1 && current.tasks.iter().all(|old| old.task_id != task.task_id)
+    }) {
+        return Err(AppError::bad_request(format!(
+            "Task {} doesn't belong to this plant",
+            task.task_id
+        ))
+        .into());
+    }
+    db_api::update_plant(pool, plant, claims.user_id).await?;
+    Ok(Json(
+        db_api::get_plant_from_id(pool, claims.user_id, plant_id).await?,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/plants/{plant_id}/events",
+    tag = "care events",
+    params(
+        ("plant_id" = i32, Path, description = "A plant of the signed in user"),
+    ),
+    responses(
+        (status = 200, description = "Care history, newest first", body = [CareEvent]),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+        (status = 404, description = "Not found or not the user's", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn list_care_events(
+    State(app): State<AppState>,
+    claims: Claims,
+    Path(plant_id): Path<i32>,
+) -> ApiResult<Json<Vec<CareEvent>>> {
+    let pool = &app.db_pool;
+    // an empty history and someone else's plant would otherwise look the same
+    db_api::get_plant_from_id(pool, claims.user_id, plant_id).await?;
+    Ok(Json(
+        db_api::get_care_events(pool, claims.user_id, plant_id).await?,
+    ))
+}
+
+/// Records that the task was done just now.
+#[utoipa::path(
+    post,
+    path = "/api/v1/plants/{plant_id}/tasks/{task_id}/events",
+    tag = "care events",
+    params(
+        ("plant_id" = i32, Path, description = "A plant of the signed in user"),
+        ("task_id" = i32, Path, description = "One of the plant's care tasks"),
+    ),
+    request_body = CareEventBody,
+    responses(
+        (status = 201, description = "The recorded event", body = CareEvent),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+        (status = 403, description = "The user's role in the household doesn't allow it", body = ErrorBody),
+        (status = 404, description = "Not found or not the user's", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn create_care_event(
+    State(app): State<AppState>,
+    claims: Claims,
+    Path((plant_id, task_id)): Path<(i32, i32)>,
+    WithRejection(Json(body), _): WithRejection<Json<CareEventBody>, ApiError>,
+) -> ApiResult<(StatusCode, Json<CareEvent>)> {
+    let pool = &app.db_pool;
+    require_plant_role(&app, claims.user_id, plant_id, HouseholdRole::Caretaker).await?;
+    let event_id =
+        db_api::add_care_event(pool, claims.user_id, plant_id, task_id, body.note).await?;
+    let event = db_api::get_care_events(pool, claims.user_id, plant_id)
+        .await?
+        .into_iter()
+        .find(|event| event.event_id == event_id)
+        .ok_or(AppError::NotFound)?;
+    Ok((StatusCode::CREATED, Json(event)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/plants/{plant_id}/comments",
+    tag = "comments",
+    params(
+        ("plant_id" = i32, Path, description = "A plant of the signed in user"),
+    ),
+    responses(
+        (status = 200, description = "Journal notes, newest first", body = [Comments]),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+        (status = 404, description = "Not found or not the user's", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn list_comments(
+    State(app): State<AppState>,
+    claims: Claims,
+    Path(plant_id): Path<i32>,
+) -> ApiResult<Json<Vec<Comments>>> {
+    let pool = &app.db_pool;
+    db_api::get_plant_from_id(pool, claims.user_id, plant_id).await?;
+    Ok(Json(
+        db_api::get_comments(pool, claims.user_id, plant_id).await?,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/plants/{plant_id}/comments",
+    tag = "comments",
+    params(
+        ("plant_id" = i32, Path, description = "A plant of the signed in user"),
+    ),
+    request_body = CommentForm,
+    responses(
+        (status = 201, description = "The new note", body = Comments),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+        (status = 403, description = "The user's role in the household doesn't allow it", body = ErrorBody),
+        (status = 404, description = "Not found or not the user's", body = ErrorBody),
+        (status = 400, description = "The request failed validation", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn create_comment(
+    State(app): State<AppState>,
+    claims: Claims,
+    Path(plant_id): Path<i32>,
+    WithRejection(Json(form), _): WithRejection<Json<CommentForm>, ApiError>,
+) -> ApiResult<(StatusCode, Json<Comments>)> {
+    let pool = &app.db_pool;
+    if form.comment.trim().is_empty() {
+        return Err(AppError::bad_request("A comment can't be empty").into());
+    }
+    require_plant_role(&app, claims.user_id, plant_id, HouseholdRole::Caretaker).await?;
+    let comment_id = db_api::add_comment(pool, claims.user_id, plant_id, form.comment).await?;
+    let comment = db_api::get_comment(pool, claims.user_id, plant_id, comment_id).await?;
+    Ok((StatusCode::CREATED, Json(comment)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/plants/{plant_id}/comments/{comment_id}",
+    tag = "comments",
+    params(
+        ("plant_id" = i32, Path, description = "A plant of the signed in user"),
+        ("comment_id" = i32, Path, description = "A journal note on the plant"),
+    ),
+    request_body = CommentForm,
+    responses(
+        (status = 200, description = "The edited note", body = Comments),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+        (status = 403, description = "The user's role in the household doesn't allow it", body = ErrorBody),
+        (status = 404, description = "Not found or not the user's", body = ErrorBody),
+        (status = 400, description = "The request failed validation", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn update_comment(
+    State(app): State<AppState>,
+    claims: Claims,
+    Path((plant_id, comment_id)): Path<(i32, i32)>,
+    WithRejection(Json(form), _): WithRejection<Json<CommentForm>, ApiError>,
+) -> ApiResult<Json<Comments>> {
+    let pool = &app.db_pool;
+    if form.comment.trim().is_empty() {
+        return Err(AppError::bad_request("A comment can't be empty").into());
+    }
+    require_plant_role(&app, claims.user_id, plant_id, HouseholdRole::Caretaker).await?;
+    db_api::get_comment(pool, claims.user_id, plant_id, comment_id).await?;
+    db_api::update_comment(pool, claims.user_id, plant_id, comment_id, form.comment).await?;
+    Ok(Json(
+        db_api::get_comment(pool, claims.user_id, plant_id, comment_id).await?,
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/plants/{plant_id}/comments/{comment_id}",
+    tag = "comments",
+    params(
+        ("plant_id" = i32, Path, description = "A plant of the signed in user"),
+        ("comment_id" = i32, Path, description = "A journal note on the plant"),
+    ),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+        (status = 403, description = "The user's role in the household doesn't allow it", body = ErrorBody),
+        (status = 404, description = "Not found or not the user's", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn delete_comment(
+    State(app): State<AppState>,
+    claims: Claims,
+    Path((plant_id, comment_id)): Path<(i32, i32)>,
+) -> ApiResult<StatusCode> {
+    let pool = &app.db_pool;
+    require_plant_role(&app, claims.user_id, plant_id, HouseholdRole::Caretaker).await?;
+    db_api::get_comment(pool, claims.user_id, plant_id, comment_id).await?;
+    db_api::delete_comment(pool, claims.user_id, plant_id, comment_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/plants/{plant_id}/photos",
+    tag = "photos",
+    params(
+        ("plant_id" = i32, Path, description = "A plant of the signed in user"),
+    ),
+    responses(
+        (status = 200, description = "Photos, newest first", body = [PlantPhoto]),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+        (status = 404, description = "Not found or not the user's", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn list_photos(
+    State(app): State<AppState>,
+    claims: Claims,
+    Path(plant_id): Path<i32>,
+) -> ApiResult<Json<Vec<PlantPhoto>>> {
+    let pool = &app.db_pool;
+    db_api::get_plant_from_id(pool, claims.user_id, plant_id).await?;
+    Ok(Json(
+        db_api::get_photos(pool, claims.user_id, plant_id).await?,
+    ))
+}
+
+/// Takes a multipart upload with the image in a `photo` field, like the gallery form.
+#[utoipa::path(
+    post,
+    path = "/api/v1/plants/{plant_id}/photos",
+    tag = "photos",
+    params(
+        ("plant_id" = i32, Path, description = "A plant of the signed in user"),
+    ),
+    request_body = (content = PhotoUpload, content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "The stored photo", body = PlantPhoto),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+        (status = 403, description = "The user's role in the household doesn't allow it", body = ErrorBody),
+        (status = 404, description = "Not found or not the user's", body = ErrorBody),
+        (status = 400, description = "No photo, or not an image that can be read", body = ErrorBody),
+        (status = 413, description = "The photo is over the 10 MB limit", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn upload_photo(
+    State(app): State<AppState>,
+    claims: Claims,
+    Path(plant_id): Path<i32>,
+    multipart: Multipart,
+) -> ApiResult<(StatusCode, Json<PlantPhoto>)> {
+    require_plant_role(&app, claims.user_id, plant_id, HouseholdRole::Caretaker).await?;
+
+    let bytes = read_photo_upload(multipart).await?;
+    let photo_id = save_photo(
+        &app.db_pool,
+        app.storage.as_ref(),
+        claims.user_id,
+        plant_id,
+        bytes,
+    )
+    .await?;
+
+    let photo = db_api::get_photos(&app.db_pool, claims.user_id, plant_id)
+        .await?
+        .into_iter()
+        .find(|photo| photo.photo_id == photo_id)
+        .ok_or(AppError::NotFound)?;
+    Ok((StatusCode::CREATED, Json(photo)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/plants/{plant_id}/photos/{photo_id}",
+    tag = "photos",
+    params(
+        ("plant_id" = i32, Path, description = "A plant of the signed in user"),
+        ("photo_id" = i32, Path, description = "A photo of the plant"),
+    ),
+    responses(
+        (status = 204, description = "Deleted, along with its files"),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+        (status = 403, description = "The user's role in the household doesn't allow it", body = ErrorBody),
+        (status = 404, description = "Not found or not the user's", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn delete_photo(
+    State(app): State<AppState>,
+    claims: Claims,
+    Path((plant_id, photo_id)): Path<(i32, i32)>,
+) -> ApiResult<StatusCode> {
+    remove_photo(&app, claims.user_id, plant_id, photo_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The bytes of a photo or thumbnail, `key` being the last part of its `photo_uri` or
+/// `thumbnail_uri`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/photos/{key}",
+    tag = "photos",
+    params(
+        ("key" = String, Path, description = "The last part of a `photo_uri` or `thumbnail_uri`"),
+    ),
+    responses(
+        (status = 200, description = "The image", content_type = "image/*", body = Vec<u8>),
+        (status = 401, description = "Missing or invalid bearer token", body = ErrorBody),
+        (status = 404, description = "Not found or not the user's", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+async fn get_photo(
+    State(app): State<AppState>,
+    claims: Claims,
+    Path(key): Path<String>,
+) -> ApiResult<Response> {
+    let uri = format!("/photos/{}", key);
+    if !db_api::user_has_photo(&app.db_pool, claims.user_id, &uri).await? {
+        return Err(AppError::NotFound.into());
+    }
+    let bytes = app
+        .storage
+        .load(&key)
+        .await
+        .map_err(|_| AppError::NotFound)?;
+    Ok((
+        [(header::CONTENT_TYPE, photos::content_type_for(&key))],
+        bytes,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(tasks: Vec<TaskBody>) -> PlantBody {
+        PlantBody {
+            household_id: None,
+            location_id: None,
+            botanical_name: "Ficus lyrata".to_owned(),
+            common_name: "Fiddle leaf".to_owned(),
+            tags: Vec::new(),
+            tasks,
+        }
+    }
+
+    fn task(task_id: Option<i32>, name: &str, interval_days: Option<i32>) -> TaskBody {
+        TaskBody {
+            task_id,
+            name: name.to_owned(),
+            interval_days,
+            last_done: None,
+            optional: false,
+        }
+    }
+
+    #[test]
+    fn openapi_documents_every_route() {
+        let doc = ApiDoc::openapi();
+        let documented: Vec<&String> = doc.paths.paths.keys().collect();
+        for path in [
+            "/api/v1/token",
+            "/api/v1/token/refresh",
+            "/api/v1/logout",
+            "/api/v1/logout-everywhere",
+            "/api/v1/me",
+            "/api/v1/households",
+            "/api/v1/locations",
+            "/api/v1/plants",
+            "/api/v1/plants/{plant_id}",
+            "/api/v1/plants/{plant_id}/events",
+            "/api/v1/plants/{plant_id}/tasks/{task_id}/events",
+            "/api/v1/plants/{plant_id}/comments",
+            "/api/v1/plants/{plant_id}/comments/{comment_id}",
+            "/api/v1/plants/{plant_id}/photos",
+            "/api/v1/plants/{plant_id}/photos/{photo_id}",
+            "/api/v1/photos/{key}",
+        ] {
+            assert!(
+                doc.paths.paths.contains_key(path),
+                "{} isn't in {:?}",
+                path,
+                documented
+            );
+        }
+    }
+
+    #[test]
+    fn openapi_never_exposes_password_hashes() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let user = &doc["components"]["schemas"]["User"]["properties"];
+        assert_eq!(user["password_hash"]["writeOnly"], true);
+        assert!(doc["components"]["securitySchemes"]["bearer"].is_object());
+    }
+
+    #[test]
+    fn new_tasks_get_placeholder_ids() {
+        let plant = body(vec![
+            task(None, " Water ", Some(7)),
+            task(Some(3), "Prune", None),
+        ])
+        .into_plant(1, 5)
+        .unwrap();
+        assert_eq!(plant.plant_id, 5);
+        assert_eq!(plant.tasks[0].task_id, -1);
+        assert_eq!(plant.tasks[0].name, "Water");
+        assert_eq!(plant.tasks[1].task_id, 3);
+        assert!(plant.tasks.iter().all(|task| task.plant_id == 5));
+    }
+
+    #[test]
+    fn invalid_tasks_are_rejected() {
+        let blank = body(vec![task(None, "  ", Some(7))]).into_plant(1, 5);
+        assert_eq!(blank.unwrap_err().status(), StatusCode::BAD_REQUEST);
+        let zero = body(vec![task(None, "Water", Some(0))]).into_plant(1, 5);
+        assert_eq!(zero.unwrap_err().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn other_users_plants_are_not_found(pool: sqlx::PgPool) {
+        use axum::body::Body;
+        use axum::http::{Method, Request};
+        use tower::ServiceExt;
+
+        let f = crate::tests::fixture(pool).await;
+        let plant_id = f.plant.plant_id;
+        let task_id = f.plant.tasks[0].task_id;
+        let comment_id = db_api::add_comment(&f.app.db_pool, f.owner, plant_id, "Mine".into())
+            .await
+            .unwrap();
+        let photo_id = db_api::add_photo(
+            &f.app.db_pool,
+            f.owner,
+            plant_id,
+            "https://example.com/ficus.jpg".to_owned(),
+            None,
+        )
+        .await
+        .unwrap();
+        let token = crate::auth_memes::create_token(f.other, 0).unwrap();
+        let plant = serde_json::json!({
+            "botanical_name": "Hijacked",
+            "common_name": "Hijacked",
+            "tasks": [],
+        });
+        let comment = serde_json::json!({ "comment": "Hijacked" });
+        let into_their_household = serde_json::json!({
+            "household_id": f.plant.household_id,
+            "botanical_name": "Cuckoo",
+            "common_name": "Cuckoo",
+        });
+
+        for (method, uri, body) in [
+            (
+                Method::POST,
+                "/plants".to_owned(),
+                Some(&into_their_household),
+            ),
+            (Method::GET, format!("/plants/{plant_id}"), None),
+            (Method::PUT, format!("/plants/{plant_id}"), Some(&plant)),
+            (Method::GET, format!("/plants/{plant_id}/events"), None),
+            (
+                Method::POST,
+                format!("/plants/{plant_id}/tasks/{task_id}/events"),
+                Some(&serde_json::json!({})),
+            ),
+            (
+                Method::POST,
+                format!("/plants/{plant_id}/comments"),
+                Some(&comment),
+            ),
+            (
+                Method::PUT,
+                format!("/plants/{plant_id}/comments/{comment_id}"),
+                Some(&comment),
+            ),
+            (
+                Method::DELETE,
+                format!("/plants/{plant_id}/comments/{comment_id}"),
+                None,
+            ),
+            (
+                Method::DELETE,
+                format!("/plants/{plant_id}/photos/{photo_id}"),
+                None,
+            ),
+        ] {
+            let request = Request::builder()
+                .method(&method)
+                .uri(&uri)
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    body.map(|body| body.to_string()).unwrap_or_default(),
+                ))
+                .unwrap();
+            let response = router()
+                .with_state(f.app.clone())
+                .oneshot(request)
+                .await
+                .unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::NOT_FOUND,
+                "{} {}",
+                method,
+                uri
+            );
+        }
+
+        let plant = db_api::get_plant_from_id(&f.app.db_pool, f.owner, plant_id)
+            .await
+            .unwrap();
+        assert_eq!(plant.common_name, f.plant.common_name);
+        assert_eq!(plant.tasks.len(), 1);
+        let comments = db_api::get_comments(&f.app.db_pool, f.owner, plant_id)
+            .await
+            .unwrap();
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].comment, "Mine");
+        let photos = db_api::get_photos(&f.app.db_pool, f.owner, plant_id)
+            .await
+            .unwrap();
+        assert_eq!(photos.len(), 1);
+    }
+}
@@ -1,14 +1,10 @@
 use axum::{
     async_trait,
-    extract::{FromRequestParts, Request},
-    http::{
-        header::{self},
-        request::Parts,
-        StatusCode,
-    },
+    extract::{FromRequestParts, Path, Request, State},
+    http::{request::Parts, Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Redirect, Response},
-    Json, RequestPartsExt,
+    Extension, Json, RequestPartsExt,
 };
 
 use axum_extra::{
@@ -17,100 +13,135 @@ use axum_extra::{
 };
 
 use headers::{Cookie, HeaderMapExt};
+use std::collections::HashMap;
 use time::Duration;
 
-use crate::KEYS;
+use crate::error::{AppError, AppResult, ErrorBody};
+use crate::households::HouseholdRole;
+use crate::sessions::{self, Tokens, ACCESS_COOKIE, REFRESH_COOKIE};
+use crate::{config, db_api, AppState, KEYS};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-
-pub async fn check_client(request: Request, next: Next) -> Result<impl IntoResponse, Redirect> {
-    //is user logged in
-    let request = check_auth(request).await;
-    if request.is_ok() {
-        Ok(next
-            .run(request.expect("We just checked is user is logged in"))
-            .await)
-    } else {
-        Err(Redirect::temporary("/login"))
-    }
-}
+use utoipa::ToSchema;
 
-async fn check_auth(request: Request) -> Result<Request, Response> {
-    let (mut head, body) = request.into_parts();
+/// Lets signed in requests through with their user id as an extension. An expired access
+/// cookie is replaced using the refresh cookie on the way, anyone else is sent to log in.
+pub async fn check_client(
+    State(app): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let cookies = request.headers().typed_get::<Cookie>();
+    let cookie = |name: &str| {
+        cookies
+            .as_ref()
+            .and_then(|c| c.get(name))
+            .map(str::to_owned)
+    };
 
-    if let Some(cookies) = head.headers.typed_get::<Cookie>() {
-        let user_id =
-            extract_user_id(cookies).map_err(|_| AuthError::MissingCredentials.into_response())?;
-        head.extensions.insert(user_id);
-        Ok(Request::from_parts(head, body))
-    } else {
-        Err(AuthError::InvalidToken.into_response())
+    if let Some(claims) = cookie(ACCESS_COOKIE).and_then(|token| decode_token(&token).ok()) {
+        request.extensions_mut().insert(claims.user_id);
+        return next.run(request).await;
+    }
+    let refreshed = match cookie(REFRESH_COOKIE) {
+        Some(token) => sessions::refresh(&app.db_pool, &token).await,
+        None => Err(AuthError::MissingCredentials.into()),
+    };
+    match refreshed {
+        Ok(tokens) => {
+            request.extensions_mut().insert(tokens.user_id);
+            let response = next.run(request).await;
+            (sessions::set_cookies(&tokens), response).into_response()
+        }
+        // the database being down shouldn't sign anyone out
+        Err(error @ AppError::Internal(_)) => error.into_response(),
+        Err(_) => (sessions::clear_cookies(), Redirect::temporary("/login")).into_response(),
     }
 }
 
-fn extract_user_id(cookie: Cookie) -> Result<i32, AuthError> {
-    if let Some(token) = cookie.get("token") {
-        let token = jsonwebtoken::decode::<Claims>(token, &KEYS.decoding, &Validation::default())
-            .map_err(|_| AuthError::InvalidToken)?;
-        Ok(token.claims.user_id)
+/// Checks the signed in user's role in the household named by the `household_id` path parameter,
+/// or holding the plant named by `plant_id`. A `GET` only needs them to be in it, anything else
+/// needs at least the role this is layered with. Goes inside `check_client`, and `db_api`
+/// checks the same again.
+pub async fn require_role(
+    State((app, min_role)): State<(AppState, HouseholdRole)>,
+    Extension(user_id): Extension<i32>,
+    Path(params): Path<HashMap<String, String>>,
+    request: Request,
+    next: Next,
+) -> AppResult<Response> {
+    let pool = &app.db_pool;
+    let id = |name: &str| params.get(name).and_then(|id| id.parse::<i32>().ok());
+    let role = if let Some(household_id) = id("household_id") {
+        db_api::get_role(pool, user_id, household_id).await?
+    } else if let Some(plant_id) = id("plant_id") {
+        db_api::get_plant_role(pool, user_id, plant_id).await?
     } else {
-        Err(AuthError::InvalidToken)
-    }
-}
-//TODO - This code is not used but the boilerplate may be usefuil for other methods. this is the existing user login, change it to actually check data base
-pub async fn authorize(Json(payload): Json<AuthPayload>) -> impl IntoResponse {
-    // Check if the user sent the credentials
-    if payload.client_id.is_empty() || payload.client_secret.is_empty() {
-        return Err(AuthError::MissingCredentials);
-    }
-    // Here you can check the user credentials from a database
-    if payload.client_id != "foo" || payload.client_secret != "bar" {
-        return Err(AuthError::WrongCredentials);
-    }
-    let claims = Claims {
-        sub: "b@b.com".to_owned(),
-        user_id: 1,
-        exp: 2000000000, // May 2033
+        None
     };
-    // Create the authorization token
-    let token = encode(&Header::default(), &claims, &KEYS.encoding)
-        .map_err(|_| AuthError::TokenCreation)?;
-
-    println!("ok {:?}", payload);
 
-    let html = leptos::ssr::render_to_string(move |_cx| {});
-    let cookie_str = format!("token={}; HttpOnly; SameSite=Strict", token);
+    let needed = if request.method() == Method::GET {
+        HouseholdRole::Viewer
+    } else {
+        min_role
+    };
+    check_role(role, needed)?;
+    Ok(next.run(request).await)
+}
 
-    let response = Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "text/html")
-        .header(header::SET_COOKIE, cookie_str)
-        .body(html)
-        .unwrap();
-    Ok(response)
+/// Not found for someone outside the household, so that doesn't give away what's in it, and
+/// forbidden for a member whose role isn't enough.
+pub fn check_role(role: Option<HouseholdRole>, needed: HouseholdRole) -> AppResult<()> {
+    match role {
+        None => Err(AppError::NotFound),
+        Some(role) if role < needed => Err(AppError::Forbidden),
+        Some(_) => Ok(()),
+    }
 }
 
-pub fn get_jwt_cookie_for_new_user(payload: AuthPayload) -> String {
-    let user_id = payload.client_id;
+fn decode_token(token: &str) -> Result<Claims, AuthError> {
+    let token_data = decode::<Claims>(token, &KEYS.decoding, &Validation::default())
+        .map_err(|_| AuthError::InvalidToken)?;
+    Ok(token_data.claims)
+}
 
+/// A signed JWT for `user_id` in `session_id`, good for `auth.access_token_minutes`. Used as
+/// the access cookie and as the bearer token of the JSON API. It isn't checked against the
+/// session, so signing out takes up to that long to reach tokens already handed out.
+pub fn create_token(user_id: i32, session_id: i32) -> Result<String, AuthError> {
     let claims = Claims {
-        sub: "b@b".to_owned(),                    //TODO - maybe user email here?
-        user_id: user_id.parse::<i32>().unwrap(), // should never fail as user id is an integer.
+        sub: "b@b".to_owned(), //TODO - maybe user email here?
+        user_id,
+        session_id,
         exp: time::OffsetDateTime::now_utc().unix_timestamp() as usize
-            + Duration::days(30).whole_seconds() as usize,
+            + Duration::minutes(config().auth.access_token_minutes).whole_seconds() as usize,
     };
-    // Create the authorization token
-    let token = encode(&Header::default(), &claims, &KEYS.encoding)
-        .map_err(|_| AuthError::TokenCreation)
-        .unwrap();
-    let cookie_str = format!("token={}; HttpOnly; SameSite=Strict", token);
-    cookie_str
+    encode(&Header::default(), &claims, &KEYS.encoding).map_err(|_| AuthError::TokenCreation)
+}
+
+/// A signed JWT for a plant-sitter link, good until the link runs out. Revoking the link is
+/// checked against `sitter_links`, the token can't know about it.
+pub fn create_sitter_token(
+    link_id: i32,
+    household_id: i32,
+    expires_at: time::OffsetDateTime,
+) -> Result<String, AuthError> {
+    let claims = SitterClaims {
+        link_id,
+        household_id,
+        exp: expires_at.unix_timestamp() as usize,
+    };
+    encode(&Header::default(), &claims, &KEYS.encoding).map_err(|_| AuthError::TokenCreation)
+}
+
+pub fn decode_sitter_token(token: &str) -> Result<SitterClaims, AuthError> {
+    let token_data = decode::<SitterClaims>(token, &KEYS.decoding, &Validation::default())
+        .map_err(|_| AuthError::InvalidToken)?;
+    Ok(token_data.claims)
 }
 
-pub fn hash_password(pasword: String) -> String {
-    let hash = bcrypt::hash(pasword, bcrypt::DEFAULT_COST).unwrap();
-    hash
+pub fn hash_password(pasword: String) -> anyhow::Result<String> {
+    Ok(bcrypt::hash(pasword, bcrypt::DEFAULT_COST)?)
 }
 //This means any encryption error will result in the user being unable
 //to login. Maybe fix, maybe security feature
@@ -122,10 +153,12 @@ pub fn check_password(password: &String, hash: &String) -> bool {
 }
 
 impl AuthBody {
-    fn new(access_token: String) -> Self {
+    pub fn new(tokens: Tokens) -> Self {
         Self {
-            access_token,
+            access_token: tokens.access_token,
             token_type: "Bearer".to_string(),
+            expires_in: Duration::minutes(config().auth.access_token_minutes).whole_seconds(),
+            refresh_token: tokens.refresh_token,
         }
     }
 }
@@ -143,11 +176,7 @@ where
             .extract::<TypedHeader<Authorization<Bearer>>>()
             .await
             .map_err(|_| AuthError::InvalidToken)?;
-        // Decode the user data
-        let token_data = decode::<Claims>(bearer.token(), &KEYS.decoding, &Validation::default())
-            .map_err(|_| AuthError::InvalidToken)?;
-
-        Ok(token_data.claims)
+        decode_token(bearer.token())
     }
 }
 
@@ -157,11 +186,11 @@ impl IntoResponse for AuthError {
             AuthError::WrongCredentials => (StatusCode::UNAUTHORIZED, "Wrong credentials"),
             AuthError::MissingCredentials => (StatusCode::BAD_REQUEST, "Missing credentials"),
             AuthError::TokenCreation => (StatusCode::INTERNAL_SERVER_ERROR, "Token creation error"),
-            AuthError::InvalidToken => (StatusCode::BAD_REQUEST, "Invalid token"),
+            AuthError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
         };
-        let body = Json(json!({
-            "error": error_message,
-        }));
+        let body = Json(ErrorBody {
+            error: error_message.to_owned(),
+        });
         (status, body).into_response()
     }
 }
@@ -184,19 +213,28 @@ impl Keys {
 pub struct Claims {
     sub: String, //subject
     exp: usize,  //expiry
-    user_id: i32,
+    pub user_id: i32,
+    pub session_id: i32,
 }
 
-#[derive(Debug, Serialize)]
+/// What a plant-sitter link carries instead of a user, see `sitters`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SitterClaims {
+    pub link_id: i32,
+    pub household_id: i32,
+    exp: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct AuthBody {
     access_token: String,
     token_type: String,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct AuthPayload {
-    pub client_id: String,
-    pub client_secret: String,
+    /// Seconds until `access_token` expires.
+    expires_in: i64,
+    /// Exchange it for new tokens at `/api/v1/token/refresh`, each one works once. Missing when
+    /// another request refreshed the same session a moment ago, that one has the new token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
 }
 
 #[derive(Debug)]
@@ -206,3 +244,116 @@ pub enum AuthError {
     TokenCreation,
     InvalidToken,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::{get, post};
+    use axum::{middleware, Router};
+    use tower::ServiceExt;
+
+    async fn status(router: &Router, method: Method, uri: String) -> StatusCode {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        router.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[test]
+    fn sitter_tokens_arent_sessions() {
+        crate::tests::init_config();
+        let expires_at = time::OffsetDateTime::now_utc() + Duration::days(3);
+        let sitter = create_sitter_token(4, 2, expires_at).unwrap();
+        assert_eq!(decode_sitter_token(&sitter).unwrap().link_id, 4);
+        assert!(decode_token(&sitter).is_err());
+        let session = create_token(5, 3).unwrap();
+        assert!(decode_sitter_token(&session).is_err());
+
+        let expired = time::OffsetDateTime::now_utc() - Duration::days(1);
+        let expired = create_sitter_token(4, 2, expired).unwrap();
+        assert!(decode_sitter_token(&expired).is_err());
+    }
+
+    #[test]
+    fn roles_include_the_ones_below() {
+        assert!(HouseholdRole::Viewer < HouseholdRole::Caretaker);
+        assert!(HouseholdRole::Caretaker < HouseholdRole::Owner);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn roles_are_checked_before_handlers(pool: sqlx::PgPool) {
+        let f = crate::tests::fixture(pool).await;
+        let pool = &f.app.db_pool;
+        let household_id = f.plant.household_id;
+        let expires_at = time::OffsetDateTime::now_utc() + Duration::days(1);
+        let invitation_id = db_api::insert_invitation(
+            pool,
+            f.owner,
+            household_id,
+            "other@example.com",
+            HouseholdRole::Viewer,
+            None,
+            expires_at,
+        )
+        .await
+        .unwrap();
+        db_api::accept_invitation(pool, invitation_id, f.other)
+            .await
+            .unwrap();
+
+        let as_user = |user_id: i32| {
+            Router::new()
+                .route(
+                    "/plants/:plant_id/comments",
+                    get(|| async { "ok" }).post(|| async { "ok" }),
+                )
+                .route("/households/:household_id/invite", post(|| async { "ok" }))
+                .route_layer(middleware::from_fn_with_state(
+                    (f.app.clone(), HouseholdRole::Caretaker),
+                    require_role,
+                ))
+                .layer(Extension(user_id))
+        };
+        let comments = format!("/plants/{}/comments", f.plant.plant_id);
+        let invite = format!("/households/{}/invite", household_id);
+
+        let viewer = as_user(f.other);
+        assert_eq!(
+            status(&viewer, Method::GET, comments.clone()).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&viewer, Method::POST, comments.clone()).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(&viewer, Method::POST, invite.clone()).await,
+            StatusCode::FORBIDDEN
+        );
+        let owner = as_user(f.owner);
+        assert_eq!(
+            status(&owner, Method::POST, comments.clone()).await,
+            StatusCode::OK
+        );
+
+        // a temporary grant stops working once it's up
+        db_api::update_member(
+            pool,
+            f.owner,
+            household_id,
+            f.other,
+            HouseholdRole::Caretaker,
+            Some(time::OffsetDateTime::now_utc() - Duration::minutes(1)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            status(&viewer, Method::GET, comments).await,
+            StatusCode::NOT_FOUND
+        );
+    }
+}
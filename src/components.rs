@@ -1,4 +1,11 @@
-use super::Plant;
+use super::filters::{DueFilter, SavedFilter};
+use super::households::{self, Household, HouseholdMember, HouseholdRole};
+use super::locations::{LightLevel, Location, LocationKind, Orientation};
+use super::sitters::SitterLink;
+use super::{
+    schedule, CareEvent, CareTask, CommentMatch, Comments, DigestFrequency,
+    NotificationPreferences, Plant, PlantPhoto, PushService, NOTIFICATION_CHANNELS,
+};
 use leptos::*;
 
 #[component]
@@ -10,7 +17,8 @@ pub fn Index(cx: Scope, plants: Vec<Plant>) -> impl IntoView {
             <link href="https://fonts.googleapis.com/css?family=Roboto:100,300,400,500,700,900" rel="stylesheet"/>
             <link rel="stylesheet" href="./css/styles.css"/>
         </head>
-        <body>
+        <body hx-on=SWAP_ERRORS>
+            <ErrorBanner message=None/>
             <MainView
                 plants=plants
             />
@@ -18,6 +26,19 @@ pub fn Index(cx: Scope, plants: Vec<Plant>) -> impl IntoView {
     }
 }
 
+/// htmx leaves 4xx and 5xx responses alone by default. `AppError` responses are retargeted at
+/// the `ErrorBanner`, so let them through.
+const SWAP_ERRORS: &str = "htmx:beforeSwap: if (event.detail.xhr.status >= 400) { event.detail.shouldSwap = true; event.detail.isError = false; }";
+
+/// Shows what went wrong with the last request. Every page starts with an empty one, which
+/// `AppError` responses replace.
+#[component]
+pub fn ErrorBanner(cx: Scope, message: Option<String>) -> impl IntoView {
+    view! { cx,
+        <div id="error-banner" class="error-banner" role="alert">{message}</div>
+    }
+}
+
 #[component]
 pub fn MainView(cx: Scope, plants: Vec<Plant>) -> impl IntoView {
     view! {cx,
@@ -31,6 +52,13 @@ pub fn MainView(cx: Scope, plants: Vec<Plant>) -> impl IntoView {
                         hx-target="#main-view"
                         hx-swap="innerHTML"
                     >"View plants"</button>
+                    <button id="view-by-location-button"
+                        class="main-buttons"
+                        hx-get="/plant-view/by-location"
+                        hx-trigger="click"
+                        hx-target="#main-view"
+                        hx-swap="innerHTML"
+                    >"Room by room"</button>
 
                     <button id="add-button"
                         class="main-buttons"
@@ -46,27 +74,39 @@ pub fn MainView(cx: Scope, plants: Vec<Plant>) -> impl IntoView {
                         hx-target="#main-view"
                         hx-swap="innerHTML"
                     >"See plants that need attention"</button>
-                </div>
-                <div class="button-bar-child button-bar-child-right">
-                    <button id="sort-by-feed"
-                        hx-get="/sort-by-feed"
-                        hx-trigger="click"
-                        hx-target="#plants"
-                        hx-swap="outerHTML"
-                    >"Sort by least recently fed"</button>
-                    <button id="sort-by-pot"
-                        hx-get="/sort-by-pot"
+                    <button id="settings-button"
+                        class="main-buttons"
+                        hx-get="/settings"
                         hx-trigger="click"
-                        hx-target="#plants"
-                        hx-swap="outerHTML"
-                    >"Sort by least recently potted"</button>
-                    <button id="sort-by-prune"
-                        hx-get="/sort-by-prune"
+                        hx-target="#main-view"
+                        hx-swap="innerHTML"
+                    >"Settings"</button>
+                    <button id="households-button"
+                        class="main-buttons"
+                        hx-get="/households"
                         hx-trigger="click"
-                        hx-target="#plants"
-                        hx-swap="outerHTML"
-                    >"Sort by least recently pruned"</button>
-                    <div class="search-bar">
+                        hx-target="#main-view"
+                        hx-swap="innerHTML"
+                    >"Households"</button>
+                    <form class="logout-form" method="post" action="/logout">
+                        <button class="main-buttons" type="submit">"Log out"</button>
+                    </form>
+                    <div
+                        id="saved-filters"
+                        hx-get="/filters"
+                        hx-trigger="load"
+                        hx-swap="innerHTML"
+                    ></div>
+                </div>
+                <div class="button-bar-child button-bar-child-right">
+                    <div
+                        hx-get="/sort-options"
+                        hx-trigger="load"
+                        hx-swap="innerHTML"
+                    ></div>
+                    // every input searches with the whole form, htmx sends the enclosing form's
+                    // values along with a non-GET request
+                    <form id="plant-filter" class="search-bar" onsubmit="return false">
                         <input
                             class="search-input"
                             type="search"
@@ -78,9 +118,39 @@ pub fn MainView(cx: Scope, plants: Vec<Plant>) -> impl IntoView {
                             hx-swap="outerHTML"
                             hx-indicator=".htmx-indicator"
                         />
+                        <input
+                            type="text"
+                            name="tags"
+                            list="known-tags"
+                            placeholder="Tags, comma separated"
+                            hx-post="/search-plants"
+                            hx-trigger="keyup changed delay:500ms"
+                            hx-target="#plants"
+                            hx-swap="outerHTML"
+                            hx-indicator=".htmx-indicator"
+                        />
+                        <select
+                            name="due"
+                            hx-post="/search-plants"
+                            hx-trigger="change"
+                            hx-target="#plants"
+                            hx-swap="outerHTML"
+                            hx-indicator=".htmx-indicator"
+                        >
+                            {DueFilter::ALL
+                                .into_iter()
+                                .map(|due| view! { cx, <option value=due.as_str()>{due.label()}</option> })
+                                .collect::<Vec<_>>()}
+                        </select>
                         <span class="htmx-indicator">"Searching..."</span> //TODO - animated svg
-
-                    </div>
+                        <input type="text" name="filter_name" placeholder="Name this filter"/>
+                        <button
+                            type="button"
+                            hx-post="/filters"
+                            hx-target="#saved-filters"
+                            hx-swap="innerHTML"
+                        >"Save filter"</button>
+                    </form>
                 </div>
             </div>
             <main id="main-view">
@@ -111,47 +181,70 @@ pub fn PlantAddFailure(cx: Scope, error: String) -> impl IntoView {
     }
 }
 
-/// Form for adding plants, user_id is prefilled on server.
+/// Form for adding plants, user_id is prefilled on server. Starts with the usual tasks, more
+/// can be added or removed before submitting. The plant goes in the first of `households`
+/// unless another is picked, and in one of its `locations` if one is picked.
 #[component]
-pub fn AddPlantView(cx: Scope, user_id: i32, plant_id: Option<i32>, text: String) -> impl IntoView {
+pub fn AddPlantView(
+    cx: Scope,
+    user_id: i32,
+    plant_id: Option<i32>,
+    text: String,
+    households: Vec<Household>,
+    locations: Vec<Location>,
+) -> impl IntoView {
     let plant_id = plant_id.unwrap_or(-1);
+    let pick_household = households.len() > 1;
+    // with more than one household, say which each location is in
+    let location_options = locations
+        .into_iter()
+        .filter_map(|location| {
+            let household = households
+                .iter()
+                .find(|household| household.household_id == location.household_id)?;
+            let label = if pick_household {
+                format!("{} - {}", location.name, household.name)
+            } else {
+                location.name
+            };
+            Some(view! { cx, <option value=location.location_id>{label}</option> })
+        })
+        .collect::<Vec<_>>();
     view! { cx,
         <div id="add-view">
             <form>
                 <input type="hidden" name="plant_id" value=plant_id/>
                 <input type="hidden" name="user_id" value=user_id.to_string()/>
+                <label for="household_id" hidden=!pick_household>Household: </label>
+                <select name="household_id" id="household_id" hidden=!pick_household>
+                    {households
+                        .into_iter()
+                        .map(|household| view! { cx,
+                            <option value=household.household_id>{household.name}</option>
+                        })
+                        .collect::<Vec<_>>()}
+                </select>
+                <label for="location_id">Location: </label>
+                <select name="location_id" id="location_id">
+                    <option value="">"Not placed"</option>
+                    {location_options}
+                </select>
                 <label for="botanical_name">Botanical name: </label>
                 <input type="text" name="botanical_name" id="botanical_name" required />
 
                 <label for="common_name">Common name: </label>
                 <input type="text" name="common_name" id="common_name" required />
 
-                <div>
-                    <label for="feed_optional">Is fertilizing optional?</label>
-                    <input type="checkbox" name="feed_optional" id="feed_optional"/>
-                </div>
-                <label class="feed-vis" for="last_fed">Last fertilized: </label>
-                <input class="feed-vis" type="date" name="last_fed" id="last_fed" required />
-                <label class="feed-vis" for="feed_interval">Fertilizing interval in days: </label>
-                <input class="feed-vis" type="number" name="feed_interval" id="feed_interval" required />
-
-                <div>
-                    <label for="pot_optional">Is potting optional?</label>
-                    <input type="checkbox" name="pot_optional" id="pot_optional"/>
-                </div>
-                <label class="pot-vis" for="last_potted">Last Potted: </label>
-                <input class="pot-vis" type="date" name="last_potted" id="last_potted" required />
-                <label class="pot-vis" for="potting_interval">Potting interval in days: </label>
-                <input class="pot-vis" type="number" name="potting_interval" id="potting_interval" required />
+                <label for="tags">Tags: </label>
+                <input type="text" name="tags" id="tags" placeholder="e.g. pet-toxic, succulent" />
 
-                <div>
-                    <label for="prune_optional">Is pruning optional?</label>
-                    <input type="checkbox" name="prune_optional" id="prune_optional"/>
+                <div id="task-rows">
+                    <TaskRow task_id=-1 name="Water".into() interval_days=Some(7) last_done=None optional=false/>
+                    <TaskRow task_id=-1 name="Fertilise".into() interval_days=Some(30) last_done=None optional=false/>
+                    <TaskRow task_id=-1 name="Repot".into() interval_days=Some(365) last_done=None optional=false/>
+                    <TaskRow task_id=-1 name="Prune".into() interval_days=Some(60) last_done=None optional=true/>
                 </div>
-                <label class="prune-vis" for="last_pruned">Last pruned: </label>
-                <input class="prune-vis" type="date" name="last_pruned" id="last_pruned" required />
-                <label class="prune-vis" for="pruning_interval">Pruning interval in days: </label>
-                <input class="prune-vis" type="number" name="pruning_interval" id="pruning_interval" required />
+                <AddTaskButton/>
 
                 <input type="submit"
                     value=text
@@ -162,48 +255,81 @@ pub fn AddPlantView(cx: Scope, user_id: i32, plant_id: Option<i32>, text: String
                     />
 
             </form>
-            <script>
-            r#"
-                document.getElementById('feed_optional').addEventListener('change', (event) => {
-                    toggleFields('feed-vis', event.target.checked);
-                });    
-                document.getElementById('pot_optional').addEventListener('change', (event) => {
-                    toggleFields('pot-vis', event.target.checked);
-                });    
-                document.getElementById('prune_optional').addEventListener('change', () => {
-                    toggleFields('prune-vis', event.target.checked);
-                });    
-
-                function toggleFields(className, isChecked) {
-                    console.log('pressed ' + className);
-                    Array.from(document.getElementsByClassName(className)).forEach((field) => {
-                        console.log('is checked: ' + isChecked);
-                        if (isChecked) {
-                            if (field.tagName == 'input') {
-                                if (field.type == 'date') {
-                                    field.value = '3000-11-11';
-                                } else if (field.type == 'number') {
-                                    field.value = 100000;
-                                }
-                            }
-                            field.style.display = 'none';
-                        } else {
-                            if (field.tagName == 'input') {
-                                if (field.type == 'date') {
-                                    field.value = '';
-                                } else if (field.type == 'number') {
-                                    field.value = '';
-                                }
-                            }
-                            field.style.display = 'block';
-                        }
-                    });
-                }
+        </div>
 
-            "#
-            </script>
+    }
+}
+
+/// One row of task inputs on the add/update forms. Rows for saved tasks delete through the
+/// server, unsaved rows are just removed from the page.
+#[component]
+pub fn TaskRow(
+    cx: Scope,
+    task_id: i32,
+    name: String,
+    interval_days: Option<i32>,
+    last_done: Option<time::Date>,
+    optional: bool,
+    #[prop(optional)] plant_id: Option<i32>,
+) -> impl IntoView {
+    let format = time::format_description::parse("[year]-[month]-[day]").unwrap();
+    let last_done = last_done.map(|date| date.format(&format).unwrap());
+    let interval_days = interval_days.map(|days| days.to_string());
+    let required = !optional;
+    let remove_button = match plant_id {
+        Some(plant_id) if task_id >= 0 => {
+            let delete_url = format!("/plants/{}/tasks/{}/delete", plant_id, task_id);
+            view! { cx,
+                <button type="button"
+                    hx-post=delete_url
+                    hx-trigger="click"
+                    hx-target="closest .task-row"
+                    hx-swap="outerHTML"
+                    hx-confirm="Delete this task and its history?"
+                >"Delete task"</button>
+            }
+            .into_view(cx)
+        }
+        _ => view! { cx,
+            <button type="button" onclick="this.closest('.task-row').remove()">"Remove"</button>
+        }
+        .into_view(cx),
+    };
+
+    view! { cx,
+        <div class="task-row">
+            <input type="hidden" name="task_id" value=task_id/>
+            <label>
+                "Task: "
+                <input type="text" name="task_name" value=name required/>
+            </label>
+            <label>
+                "Every (days): "
+                <input type="number" name="task_interval" min="1" value=interval_days placeholder="Not tracked"/>
+            </label>
+            <label>
+                "Last done: "
+                <input type="date" name="task_last_done" value=last_done/>
+            </label>
+            <select name="task_optional">
+                <option value="false" selected=required>"Required"</option>
+                <option value="true" selected=optional>"Optional"</option>
+            </select>
+            {remove_button}
         </div>
+    }
+}
 
+/// Appends a blank `TaskRow` to `#task-rows`, cloned from a template rendered on the server.
+#[component]
+pub fn AddTaskButton(cx: Scope) -> impl IntoView {
+    view! { cx,
+        <template id="task-row-template">
+            <TaskRow task_id=-1 name=String::new() interval_days=None last_done=None optional=false/>
+        </template>
+        <button type="button"
+            onclick="document.getElementById('task-rows').appendChild(document.getElementById('task-row-template').content.cloneNode(true))"
+        >"Add another task"</button>
     }
 }
 
@@ -227,26 +353,150 @@ pub fn PlantView(cx: Scope, plants: Vec<Plant>) -> impl IntoView {
     }
 }
 
+/// Every plant under a heading for where it lives, for going round room by room. The plants
+/// that haven't been placed come last.
+#[component]
+pub fn PlantsByLocation(cx: Scope, groups: Vec<(Option<Location>, Vec<Plant>)>) -> impl IntoView {
+    view! { cx,
+        <div id="plants-by-location">
+            {groups
+                .into_iter()
+                .map(|(location, plants)| {
+                    let heading = location
+                        .map(|location| location.describe())
+                        .unwrap_or_else(|| "Not placed".to_owned());
+                    view! { cx,
+                        <section class="location">
+                            <h3>{heading}</h3>
+                            <ul class="plant-view">
+                                {plants
+                                    .into_iter()
+                                    .map(|plant| view! { cx, <li><PlantItem plant=plant/></li> })
+                                    .collect::<Vec<_>>()}
+                            </ul>
+                        </section>
+                    }
+                })
+                .collect::<Vec<_>>()}
+        </div>
+    }
+}
+
+/// A button for each of the user's saved filters, showing its plants in the main view, and the
+/// tags in use for the search bar to suggest.
+#[component]
+pub fn SavedFilters(cx: Scope, filters: Vec<SavedFilter>, tags: Vec<String>) -> impl IntoView {
+    view! { cx,
+        {filters
+            .into_iter()
+            .map(|filter| {
+                let run_url = format!("/filters/{}", filter.filter_id);
+                let delete_url = format!("/filters/{}/delete", filter.filter_id);
+                view! { cx,
+                    <span class="saved-filter">
+                        <button
+                            class="main-buttons"
+                            hx-get=run_url
+                            hx-trigger="click"
+                            hx-target="#main-view"
+                            hx-swap="innerHTML"
+                        >{filter.name}</button>
+                        <button
+                            title="Delete this filter"
+                            hx-post=delete_url
+                            hx-target="#saved-filters"
+                            hx-swap="innerHTML"
+                            hx-confirm="Delete this filter?"
+                        >"x"</button>
+                    </span>
+                }
+            })
+            .collect::<Vec<_>>()}
+        <datalist id="known-tags">
+            {tags
+                .into_iter()
+                .map(|tag| view! { cx, <option value=tag/> })
+                .collect::<Vec<_>>()}
+        </datalist>
+    }
+}
+
+/// Same layout as `PlantView`, with the journal notes that matched listed under each plant.
+#[component]
+pub fn SearchResults(cx: Scope, plants: Vec<Plant>, matches: Vec<CommentMatch>) -> impl IntoView {
+    view! { cx,
+        <ul id="plants" class="plant-view">
+            {plants
+                .into_iter()
+                .map(|plant| {
+                    let plant_matches: Vec<CommentMatch> = matches
+                        .iter()
+                        .filter(|m| m.plant_id == plant.plant_id)
+                        .cloned()
+                        .collect();
+                    view! { cx,
+                        <li>
+                            <PlantItem plant=plant />
+                            <ul class="comment-matches">
+                                {plant_matches
+                                    .into_iter()
+                                    .map(|m| view! { cx, <li><Snippet comment_match=m/></li> })
+                                    .collect::<Vec<_>>()}
+                            </ul>
+                        </li>
+                    }
+                })
+                .collect::<Vec<_>>()}
+        </ul>
+    }
+}
+
+#[component]
+pub fn Snippet(cx: Scope, comment_match: CommentMatch) -> impl IntoView {
+    view! { cx,
+        <span class="snippet">
+            "Note: "
+            {comment_match
+                .snippet_parts()
+                .into_iter()
+                .map(|(text, highlighted)| {
+                    if highlighted {
+                        view! { cx, <mark>{text}</mark> }.into_view(cx)
+                    } else {
+                        text.into_view(cx)
+                    }
+                })
+                .collect::<Vec<_>>()}
+        </span>
+    }
+}
+
 #[component]
 pub fn PlantItem(cx: Scope, plant: Plant) -> impl IntoView {
-    let (feed_days, pot_days, prune_days) = super::get_days_till_next_feed(&plant);
-    let format = time::format_description::parse("[year]-[month]-[day]").unwrap();
-    let feed_date = (plant.last_fed.format(&format)).unwrap();
-    let pot_date = (plant.last_potted.format(&format)).unwrap();
-    let prune_date = (plant.last_pruned.format(&format)).unwrap();
+    let plant_id = plant.plant_id;
     //let plant_id_json = format!(r#"{{"plant_id": {} }}"#, plant.plant_id);
+    let comments_url = format!("/plants/{}/comments", plant_id);
+    let photos_url = format!("/plants/{}/photos", plant_id);
 
     view! { cx,
         <div class="plant-container">
             <div>Plant id: {plant.plant_id}</div>
             <div>Botanical name: {plant.botanical_name}</div>
             <div>Common name: {plant.common_name}</div>
-            <div>Last fed: {feed_date}</div>
-            <div>Time to next feeding cycle: {feed_days}</div>
-            <div>Last potted: {pot_date}</div>
-            <div>Time to next potting cycle: {pot_days}</div>
-            <div>Last pruned: {prune_date}</div>
-            <div>Time to next pruning cycle: {prune_days}</div>
+            <div class="plant-tags">
+                {plant
+                    .tags
+                    .into_iter()
+                    .map(|tag| view! { cx, <span class="tag">{tag}</span> " " })
+                    .collect::<Vec<_>>()}
+            </div>
+            <ul class="plant-tasks">
+                {plant
+                    .tasks
+                    .into_iter()
+                    .map(|task| view! { cx, <li><TaskItem plant_id=plant_id task=task/></li> })
+                    .collect::<Vec<_>>()}
+            </ul>
             <form>
                 <input type="number" name="plant_id" hidden="true" value=plant.plant_id/>
                 <input type="submit"
@@ -257,6 +507,51 @@ pub fn PlantItem(cx: Scope, plant: Plant) -> impl IntoView {
                 hx-swap="innerHTML"
                 />
             </form>
+            <div
+                hx-get=photos_url
+                hx-trigger="revealed"
+                hx-swap="outerHTML"
+            ></div>
+            <div class="comments-placeholder">
+                <button
+                    hx-get=comments_url
+                    hx-trigger="click"
+                    hx-target="closest .comments-placeholder"
+                    hx-swap="innerHTML"
+                >"Show journal"</button>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+pub fn TaskItem(cx: Scope, plant_id: i32, task: CareTask) -> impl IntoView {
+    let format = time::format_description::parse("[year]-[month]-[day]").unwrap();
+    let last_done = task
+        .last_done
+        .map_or("Never".to_owned(), |date| date.format(&format).unwrap());
+    let today = schedule::today();
+    let next_due = match (
+        schedule::task_next_due(&task, today),
+        schedule::task_due_status(&task, today),
+    ) {
+        (Some(date), Some(status)) => format!("{} ({})", date.format(&format).unwrap(), status),
+        _ => "Not tracked".to_owned(),
+    };
+    let done_url = format!("/plants/{}/tasks/{}/done", plant_id, task.task_id);
+    let optional = if task.optional { " (optional)" } else { "" };
+
+    view! { cx,
+        <div class="task-item">
+            <div>{task.name} {optional}</div>
+            <div>Last done: {last_done}</div>
+            <div>Next due: {next_due}</div>
+            <button
+                hx-post=done_url
+                hx-trigger="click"
+                hx-target="closest .plant-container"
+                hx-swap="outerHTML"
+            >"Done today"</button>
         </div>
     }
 }
@@ -273,11 +568,65 @@ pub fn EmailItem(cx: Scope, plants: Vec<Plant>) -> impl IntoView {
 }
 
 #[component]
-pub fn UpdateView(cx: Scope, plant: Plant, user_id: i32) -> impl IntoView {
-    let format = time::format_description::parse("[year]-[month]-[day]").unwrap();
-    let feed_date = (plant.last_fed.format(&format)).unwrap();
-    let pot_date = (plant.last_potted.format(&format)).unwrap();
-    let prune_date = (plant.last_pruned.format(&format)).unwrap();
+pub fn VerifyEmailMessage(cx: Scope, link: String) -> impl IntoView {
+    view! { cx,
+        <h1>"Welcome to Plant Track!"</h1>
+        <p>"Follow this link to verify your email address, then we can send you care reminders:"</p>
+        <p><a href=link.clone()>{link.clone()}</a></p>
+        <p>"It works for a week. If you didn't sign up, ignore this email."</p>
+    }
+}
+
+#[component]
+pub fn ResetPasswordMessage(cx: Scope, link: String) -> impl IntoView {
+    view! { cx,
+        <h1>"Reset your password"</h1>
+        <p>"Follow this link to choose a new password for Plant Track:"</p>
+        <p><a href=link.clone()>{link.clone()}</a></p>
+        <p>"It works once, for an hour. If you didn't ask for it, ignore this email and your password stays as it is."</p>
+    }
+}
+
+#[component]
+pub fn InvitationMessage(
+    cx: Scope,
+    inviter: String,
+    household: String,
+    role: HouseholdRole,
+    link: String,
+) -> impl IntoView {
+    view! { cx,
+        <h1>{format!("{} has invited you to {}", inviter, household)}</h1>
+        <p>{format!("You'll join as a {}, so you can see its plants on Plant Track{}. Follow this link to join:",
+            role.as_str(),
+            if role == HouseholdRole::Viewer { "" } else { " and log the care you give them" },
+        )}</p>
+        <p><a href=link.clone()>{link.clone()}</a></p>
+        <p>"Sign up or log in with this email address first. The link works once, for a week. If you weren't expecting it, ignore this email."</p>
+    }
+}
+
+/// Form for changing a plant, it can be moved to any of `locations` in its household.
+#[component]
+pub fn UpdateView(
+    cx: Scope,
+    plant: Plant,
+    user_id: i32,
+    locations: Vec<Location>,
+) -> impl IntoView {
+    let plant_id = plant.plant_id;
+    let comments_url = format!("/plants/{}/comments", plant_id);
+    let unplaced = plant.location_id.is_none();
+    let location_options = locations
+        .into_iter()
+        .filter(|location| location.household_id == plant.household_id)
+        .map(|location| {
+            let selected = plant.location_id == Some(location.location_id);
+            view! { cx,
+                <option value=location.location_id selected=selected>{location.name}</option>
+            }
+        })
+        .collect::<Vec<_>>();
 
     view! { cx,
         <h2>"Details to Update"</h2>
@@ -291,6 +640,7 @@ pub fn UpdateView(cx: Scope, plant: Plant, user_id: i32) -> impl IntoView {
                 <div>
                 <label>
                     <input type="number" name="user_id" hidden="true" value=user_id/>
+                    <input type="number" name="household_id" hidden="true" value=plant.household_id/>
                     "Plant id: "
                     <input type="number" name="plant_id" readonly value=plant.plant_id />
                 </label>
@@ -309,46 +659,700 @@ pub fn UpdateView(cx: Scope, plant: Plant, user_id: i32) -> impl IntoView {
                 </div>
                 <div>
                 <label>
-                    "Last fed: "
-                    <input type="date" name="last_fed" value=feed_date/>
-                </label>
-                </div>
-                <div>
-                <label>
-                    "Feed interval: "
-                    <input type="number" name="feed_interval" value=plant.feed_interval />
+                    "Tags: "
+                    <input type="text" name="tags" value=plant.tags.join(", ") placeholder="e.g. pet-toxic, succulent" />
                 </label>
                 </div>
                 <div>
                 <label>
-                    "Last potted: "
-                    <input type="date" name="last_potted" value=pot_date />
+                    "Location: "
+                    <select name="location_id">
+                        <option value="" selected=unplaced>"Not placed"</option>
+                        {location_options}
+                    </select>
                 </label>
                 </div>
-                <div>
-                <label>
-                    "Time to next potting cycle: "
-                    <input type="number" name="potting_interval" value=plant.potting_interval/>
-                </label>
-                </div>
-                <div>
-                <label>
-                    "Last pruned: "
-                    <input type="date" name="last_pruned"  value=prune_date/>
-                </label>
-                </div>
-                <div>
-                <label>
-                    "Time to next pruning cycle: "
-                    <input type="number" name="pruning_interval" value=plant.pruning_interval />
-                </label>
+                <div id="task-rows">
+                    {plant
+                        .tasks
+                        .into_iter()
+                        .map(|task| view! { cx,
+                            <TaskRow
+                                task_id=task.task_id
+                                name=task.name
+                                interval_days=task.interval_days
+                                last_done=task.last_done
+                                optional=task.optional
+                                plant_id=plant_id
+                            />
+                        })
+                        .collect::<Vec<_>>()}
                 </div>
+                <AddTaskButton/>
                 <input type="submit" value="Update plant"/>
             </form>
+            <div
+                hx-get=comments_url
+                hx-trigger="load"
+                hx-swap="innerHTML"
+            ></div>
         </div>
     }
 }
 
+/// Thumbnails of a plant's photos, each linking to the full size photo, plus an upload form.
+#[component]
+pub fn PhotoGallery(
+    cx: Scope,
+    plant_id: i32,
+    photos: Vec<PlantPhoto>,
+    error: Option<String>,
+) -> impl IntoView {
+    let upload_url = format!("/plants/{}/photos", plant_id);
+
+    view! { cx,
+        <div class="photo-gallery">
+            {error.map(|error| view! { cx, <p>"Photo not uploaded: " {error}</p> })}
+            <ul class="photos">
+                {photos
+                    .into_iter()
+                    .map(|photo| {
+                        let delete_url = format!(
+                            "/plants/{}/photos/{}/delete",
+                            photo.plant_id, photo.photo_id
+                        );
+                        let thumbnail = photo.thumbnail_uri.unwrap_or(photo.photo_uri.clone());
+                        view! { cx,
+                            <li class="photo">
+                                <a href=photo.photo_uri target="_blank">
+                                    <img src=thumbnail loading="lazy"/>
+                                </a>
+                                <button
+                                    hx-post=delete_url
+                                    hx-trigger="click"
+                                    hx-target="closest .photo-gallery"
+                                    hx-swap="outerHTML"
+                                    hx-confirm="Delete this photo?"
+                                >"Delete"</button>
+                            </li>
+                        }
+                    })
+                    .collect::<Vec<_>>()}
+            </ul>
+            <form
+                hx-post=upload_url
+                hx-encoding="multipart/form-data"
+                hx-trigger="submit"
+                hx-target="closest .photo-gallery"
+                hx-swap="outerHTML"
+            >
+                <input type="file" name="photo" accept="image/*" required/>
+                <input type="submit" value="Upload photo"/>
+            </form>
+        </div>
+    }
+}
+
+/// Journal notes for a plant with a form to add another. Every action re-renders the whole
+/// panel in place.
+#[component]
+pub fn CommentsPanel(cx: Scope, plant_id: i32, comments: Vec<Comments>) -> impl IntoView {
+    let add_url = format!("/plants/{}/comments", plant_id);
+
+    view! { cx,
+        <div class="comments-panel">
+            <h3>"Journal"</h3>
+            <form
+                hx-post=add_url
+                hx-trigger="submit"
+                hx-target="closest .comments-panel"
+                hx-swap="outerHTML"
+            >
+                <textarea name="comment" placeholder="Yellow leaves, new growth..." required></textarea>
+                <input type="submit" value="Add note"/>
+            </form>
+            <ul class="comments">
+                {comments
+                    .into_iter()
+                    .map(|comment| view! { cx, <CommentItem comment=comment/> })
+                    .collect::<Vec<_>>()}
+            </ul>
+        </div>
+    }
+}
+
+#[component]
+pub fn CommentItem(cx: Scope, comment: Comments) -> impl IntoView {
+    let format = time::format_description::parse("[year]-[month]-[day] [hour]:[minute]").unwrap();
+    let time_made = comment.time_made.format(&format).unwrap();
+    let edited = comment.time_edited.map(|_| " (edited)");
+    let edit_url = format!(
+        "/plants/{}/comments/{}/edit",
+        comment.plant_id, comment.comment_id
+    );
+    let delete_url = format!(
+        "/plants/{}/comments/{}/delete",
+        comment.plant_id, comment.comment_id
+    );
+
+    view! { cx,
+        <li class="comment">
+            <div class="comment-time">{time_made} {edited}</div>
+            <p>{comment.comment}</p>
+            <button
+                hx-get=edit_url
+                hx-trigger="click"
+                hx-target="closest .comment"
+                hx-swap="outerHTML"
+            >"Edit"</button>
+            <button
+                hx-post=delete_url
+                hx-trigger="click"
+                hx-target="closest .comments-panel"
+                hx-swap="outerHTML"
+                hx-confirm="Delete this note?"
+            >"Delete"</button>
+        </li>
+    }
+}
+
+#[component]
+pub fn CommentEditForm(cx: Scope, comment: Comments) -> impl IntoView {
+    let edit_url = format!(
+        "/plants/{}/comments/{}/edit",
+        comment.plant_id, comment.comment_id
+    );
+
+    view! { cx,
+        <li class="comment">
+            <form
+                hx-post=edit_url
+                hx-trigger="submit"
+                hx-target="closest .comments-panel"
+                hx-swap="outerHTML"
+            >
+                <textarea name="comment" required>{comment.comment}</textarea>
+                <input type="submit" value="Save"/>
+            </form>
+        </li>
+    }
+}
+
+#[component]
+pub fn SortSelect(cx: Scope, task_names: Vec<String>) -> impl IntoView {
+    view! { cx,
+        <select
+            name="task_name"
+            hx-get="/sort-by-task"
+            hx-trigger="change"
+            hx-target="#plants"
+            hx-swap="outerHTML"
+        >
+            <option value="" disabled selected>"Sort by next due task"</option>
+            {task_names
+                .into_iter()
+                .map(|name| view! { cx, <option value=name.clone()>{name}</option> })
+                .collect::<Vec<_>>()}
+        </select>
+    }
+}
+
+const WEEKDAYS: [(i16, &str); 7] = [
+    (1, "Monday"),
+    (2, "Tuesday"),
+    (3, "Wednesday"),
+    (4, "Thursday"),
+    (5, "Friday"),
+    (6, "Saturday"),
+    (7, "Sunday"),
+];
+
+/// Notification preferences form. `task_names` are the user's tasks to choose from, leaving
+/// them all unticked includes every task.
+#[component]
+pub fn NotificationSettings(
+    cx: Scope,
+    preferences: NotificationPreferences,
+    task_names: Vec<String>,
+    timezones: Vec<String>,
+    message: Option<String>,
+    email_verified: bool,
+) -> impl IntoView {
+    let weekly = preferences.frequency == DigestFrequency::Weekly;
+    let gotify = preferences.push_service == PushService::Gotify;
+    let included = preferences.task_names.unwrap_or_default();
+
+    view! { cx,
+        <div id="settings">
+            <h2>"Notifications"</h2>
+            {message.map(|message| view! { cx, <p class="settings-message">{message}</p> })}
+            <form
+                hx-post="/settings"
+                hx-trigger="submit"
+                hx-target="#main-view"
+                hx-swap="innerHTML"
+            >
+                <fieldset>
+                    <legend>"Send reminders by"</legend>
+                    {NOTIFICATION_CHANNELS
+                        .iter()
+                        .map(|channel| {
+                            let checked = preferences.channels.iter().any(|c| c == channel);
+                            view! { cx,
+                                <label>
+                                    <input type="checkbox" name="channels" value=*channel checked=checked/>
+                                    {*channel}
+                                </label>
+                            }
+                        })
+                        .collect::<Vec<_>>()}
+                    <label>
+                        " Webhook URL "
+                        <input type="url" name="webhook_url" value=preferences.webhook_url.unwrap_or_default()/>
+                    </label>
+                    <label>
+                        " Push through "
+                        <select name="push_service">
+                            <option value="ntfy" selected=!gotify>"ntfy"</option>
+                            <option value="gotify" selected=gotify>"Gotify"</option>
+                        </select>
+                    </label>
+                    <label>
+                        " at "
+                        <input type="url" name="push_url" value=preferences.push_url.unwrap_or_default()/>
+                    </label>
+                    <label>
+                        " token "
+                        <input type="password" name="push_token" value=preferences.push_token.unwrap_or_default()/>
+                    </label>
+                    <p class="settings-hint">
+                        "For ntfy use the full topic URL, the token is optional. For Gotify use the server URL and an application token."
+                    </p>
+                </fieldset>
+                <fieldset>
+                    <legend>"How often"</legend>
+                    <select name="frequency">
+                        <option value="daily" selected=!weekly>"Daily"</option>
+                        <option value="weekly" selected=weekly>"Weekly"</option>
+                    </select>
+                    <label>
+                        " on "
+                        <select name="weekly_day">
+                            {WEEKDAYS
+                                .iter()
+                                .map(|(day, name)| {
+                                    let selected = *day == preferences.weekly_day;
+                                    view! { cx, <option value=*day selected=selected>{*name}</option> }
+                                })
+                                .collect::<Vec<_>>()}
+                        </select>
+                        " when weekly"
+                    </label>
+                    <label>
+                        " at "
+                        <select name="send_hour">
+                            {(0..24i16)
+                                .map(|hour| {
+                                    let selected = hour == preferences.send_hour;
+                                    view! { cx,
+                                        <option value=hour selected=selected>{format!("{:02}:00", hour)}</option>
+                                    }
+                                })
+                                .collect::<Vec<_>>()}
+                        </select>
+                    </label>
+                    <label>
+                        " in "
+                        <select name="timezone">
+                            {timezones
+                                .into_iter()
+                                .map(|timezone| {
+                                    let selected = timezone == preferences.timezone;
+                                    view! { cx,
+                                        <option value=timezone.clone() selected=selected>{timezone}</option>
+                                    }
+                                })
+                                .collect::<Vec<_>>()}
+                        </select>
+                    </label>
+                </fieldset>
+                <fieldset>
+                    <legend>"Only remind me about"</legend>
+                    {task_names
+                        .into_iter()
+                        .map(|name| {
+                            let checked = included.contains(&name);
+                            view! { cx,
+                                <label>
+                                    <input type="checkbox" name="task_names" value=name.clone() checked=checked/>
+                                    {name}
+                                </label>
+                            }
+                        })
+                        .collect::<Vec<_>>()}
+                    <p class="settings-hint">"Leave these all unticked to hear about every task."</p>
+                </fieldset>
+                <fieldset>
+                    <legend>"Never send on"</legend>
+                    {WEEKDAYS
+                        .iter()
+                        .map(|(day, name)| {
+                            let checked = preferences.quiet_days.contains(day);
+                            view! { cx,
+                                <label>
+                                    <input type="checkbox" name="quiet_days" value=*day checked=checked/>
+                                    {*name}
+                                </label>
+                            }
+                        })
+                        .collect::<Vec<_>>()}
+                </fieldset>
+                <button type="submit">"Save"</button>
+            </form>
+            {(!email_verified).then(|| view! { cx,
+                <h2>"Email"</h2>
+                <form
+                    hx-post="/verify-email/resend"
+                    hx-trigger="submit"
+                    hx-target="#main-view"
+                    hx-swap="innerHTML"
+                >
+                    <p class="settings-hint">
+                        "Your email address isn't verified yet, so no reminders are emailed to it. Follow the link in the email we sent when you signed up."
+                    </p>
+                    <button type="submit">"Send the link again"</button>
+                </form>
+            })}
+            <h2>"Sessions"</h2>
+            <form method="post" action="/logout-everywhere">
+                <p class="settings-hint">
+                    "Signs you out on every device, this one included. Use it if you've lost one or signed in somewhere shared."
+                </p>
+                <button type="submit">"Sign out everywhere"</button>
+            </form>
+        </div>
+    }
+}
+
+/// The user's households, who is in each and where its plants live. Owners also get forms to
+/// change what members may do, to invite someone else in, to add and remove locations and to
+/// make plant-sitter links, each paired with its address.
+#[component]
+pub fn HouseholdsView(
+    cx: Scope,
+    households: Vec<Household>,
+    members: Vec<HouseholdMember>,
+    locations: Vec<Location>,
+    sitter_links: Vec<(SitterLink, String)>,
+    message: Option<String>,
+) -> impl IntoView {
+    view! { cx,
+        <div id="households">
+            <h2>"Households"</h2>
+            {message.map(|message| view! { cx, <p class="settings-message">{message}</p> })}
+            <p class="settings-hint">
+                "Viewers can see a household's plants, caretakers can also log care, write notes and add photos, and owners can also change plants and who is in the household. Give a plant-sitter a role until the day you're back, or if they don't have an account, a link that shows what needs doing while you're away."
+            </p>
+            {households
+                .into_iter()
+                .map(|household| {
+                    let household_id = household.household_id;
+                    let is_owner = household.role == HouseholdRole::Owner;
+                    let in_household = members
+                        .iter()
+                        .filter(|member| member.household_id == household_id)
+                        .map(|member| {
+                            let member_url =
+                                format!("/households/{}/members/{}", household_id, member.user_id);
+                            let remove_url = format!("{}/remove", member_url);
+                            let until = households::grant_until(member.expires_at);
+                            view! { cx,
+                                <li>
+                                    {format!("{} {}", member.first_name, member.last_name)}
+                                    " (" {member.email.clone()} "), " {member.role.as_str()}
+                                    {until.map(|until| format!(" until {}", until))}
+                                    {is_owner.then(|| view! { cx,
+                                        <form
+                                            class="member-form"
+                                            hx-post=member_url
+                                            hx-trigger="submit"
+                                            hx-target="#main-view"
+                                            hx-swap="innerHTML"
+                                        >
+                                            <RoleFields role=member.role until=until/>
+                                            <button type="submit">"Change"</button>
+                                            <button
+                                                type="button"
+                                                hx-post=remove_url
+                                                hx-target="#main-view"
+                                                hx-swap="innerHTML"
+                                                hx-confirm="Take them out of the household?"
+                                            >"Remove"</button>
+                                        </form>
+                                    })}
+                                </li>
+                            }
+                        })
+                        .collect::<Vec<_>>();
+                    let invite_url = format!("/households/{}/invite", household_id);
+                    let locations_url = format!("/households/{}/locations", household_id);
+                    let in_locations = locations
+                        .iter()
+                        .filter(|location| location.household_id == household_id)
+                        .map(|location| {
+                            let delete_url =
+                                format!("{}/{}/delete", locations_url, location.location_id);
+                            view! { cx,
+                                <li>
+                                    {location.describe()}
+                                    {is_owner.then(|| view! { cx,
+                                        <button
+                                            hx-post=delete_url
+                                            hx-target="#main-view"
+                                            hx-swap="innerHTML"
+                                            hx-confirm="Remove this location? Its plants will be left unplaced."
+                                        >"Remove"</button>
+                                    })}
+                                </li>
+                            }
+                        })
+                        .collect::<Vec<_>>();
+                    let sitter_links_url = format!("/households/{}/sitter-links", household_id);
+                    let links = sitter_links
+                        .iter()
+                        .filter(|(link, _)| link.household_id == household_id)
+                        .map(|(link, url)| {
+                            let revoke_url =
+                                format!("{}/{}/revoke", sitter_links_url, link.link_id);
+                            view! { cx,
+                                <li>
+                                    {format!(
+                                        "{}, {} to {}: ",
+                                        link.sitter_name,
+                                        link.first_day(),
+                                        link.last_day()
+                                    )}
+                                    <input type="text" readonly=true value=url.clone()/>
+                                    <button
+                                        hx-post=revoke_url
+                                        hx-target="#main-view"
+                                        hx-swap="innerHTML"
+                                        hx-confirm="Stop this link working?"
+                                    >"Revoke"</button>
+                                </li>
+                            }
+                        })
+                        .collect::<Vec<_>>();
+                    view! { cx,
+                        <section class="household">
+                            <h3>{household.name}</h3>
+                            <ul>{in_household}</ul>
+                            <h4>"Locations"</h4>
+                            <ul class="locations">{in_locations}</ul>
+                            {is_owner.then(|| view! { cx,
+                                <form
+                                    hx-post=locations_url
+                                    hx-trigger="submit"
+                                    hx-target="#main-view"
+                                    hx-swap="innerHTML"
+                                >
+                                    <LocationFields/>
+                                    <button type="submit">"Add location"</button>
+                                </form>
+                                <form
+                                    hx-post=invite_url
+                                    hx-trigger="submit"
+                                    hx-target="#main-view"
+                                    hx-swap="innerHTML"
+                                >
+                                    <label>
+                                        "Invite by email: "
+                                        <input type="email" name="email" required=true/>
+                                    </label>
+                                    <RoleFields role=HouseholdRole::Caretaker until=None/>
+                                    <button type="submit">"Send invitation"</button>
+                                </form>
+                                <h4>"Plant-sitter links"</h4>
+                                <ul class="sitter-links">{links}</ul>
+                                <form
+                                    hx-post=sitter_links_url
+                                    hx-trigger="submit"
+                                    hx-target="#main-view"
+                                    hx-swap="innerHTML"
+                                >
+                                    <label>
+                                        "Sitter's name: "
+                                        <input type="text" name="sitter_name" required=true/>
+                                    </label>
+                                    <label>
+                                        " from "
+                                        <input type="date" name="from" required=true/>
+                                    </label>
+                                    <label>
+                                        " until "
+                                        <input type="date" name="until" required=true/>
+                                    </label>
+                                    <button type="submit">"Make link"</button>
+                                </form>
+                            })}
+                        </section>
+                    }
+                })
+                .collect::<Vec<_>>()}
+        </div>
+    }
+}
+
+/// The inputs for a new location, light level and orientation can be left blank.
+#[component]
+pub fn LocationFields(cx: Scope) -> impl IntoView {
+    view! { cx,
+        <label>
+            "Location: "
+            <input type="text" name="name" required=true placeholder="e.g. Kitchen windowsill"/>
+        </label>
+        <select name="kind">
+            {LocationKind::ALL
+                .into_iter()
+                .map(|kind| view! { cx, <option value=kind.as_str()>{kind.as_str()}</option> })
+                .collect::<Vec<_>>()}
+        </select>
+        <select name="light_level">
+            <option value="">"Light?"</option>
+            {LightLevel::ALL
+                .into_iter()
+                .map(|light| view! { cx, <option value=light.as_str()>{light.as_str()} " light"</option> })
+                .collect::<Vec<_>>()}
+        </select>
+        <select name="orientation">
+            <option value="">"Facing?"</option>
+            {Orientation::ALL
+                .into_iter()
+                .map(|orientation| view! { cx,
+                    <option value=orientation.as_str()>{orientation.as_str()} " facing"</option>
+                })
+                .collect::<Vec<_>>()}
+        </select>
+    }
+}
+
+/// A role and the last day it lasts, leaving the date blank for a member who stays.
+#[component]
+pub fn RoleFields(cx: Scope, role: HouseholdRole, until: Option<time::Date>) -> impl IntoView {
+    view! { cx,
+        <label>
+            " as "
+            <select name="role">
+                {HouseholdRole::ALL
+                    .into_iter()
+                    .map(|option| {
+                        let selected = option == role;
+                        view! { cx, <option value=option.as_str() selected=selected>{option.as_str()}</option> }
+                    })
+                    .collect::<Vec<_>>()}
+            </select>
+        </label>
+        <label>
+            " until "
+            <input type="date" name="until" value=until.map(|until| until.to_string()).unwrap_or_default()/>
+        </label>
+    }
+}
+
+/// What a plant-sitter sees through their link, the care due before the owners are back. Ticking
+/// a task off swaps in the list again with it gone, or moved on if it falls due again in time.
+#[component]
+pub fn SitterChecklist(
+    cx: Scope,
+    token: String,
+    link: SitterLink,
+    plants: Vec<Plant>,
+    message: Option<String>,
+) -> impl IntoView {
+    let format = time::format_description::parse("[year]-[month]-[day]").unwrap();
+    let today = schedule::today();
+
+    view! { cx,
+        <div id="sitter-checklist">
+            <p>
+                {format!(
+                    "Hi {}, thanks for looking after these until {}.",
+                    link.sitter_name,
+                    link.last_day()
+                )}
+            </p>
+            {message.map(|message| view! { cx, <p class="settings-message">{message}</p> })}
+            {plants.is_empty().then(|| view! { cx, <p>"Nothing needs doing, enjoy the quiet."</p> })}
+            {plants
+                .into_iter()
+                .map(|plant| {
+                    let plant_id = plant.plant_id;
+                    view! { cx,
+                        <div class="plant-container">
+                            <div>{plant.common_name} " (" {plant.botanical_name} ")"</div>
+                            <ul class="plant-tasks">
+                                {plant
+                                    .tasks
+                                    .into_iter()
+                                    .map(|task| {
+                                        let due = schedule::task_next_due(&task, today)
+                                            .map(|date| {
+                                                let status = schedule::task_due_status(&task, today)
+                                                    .map(|status| format!(" ({})", status))
+                                                    .unwrap_or_default();
+                                                format!("{}{}", date.format(&format).unwrap(), status)
+                                            })
+                                            .unwrap_or_default();
+                                        view! { cx,
+                                            <li>
+                                                <form
+                                                    hx-post="/sit/done"
+                                                    hx-trigger="submit"
+                                                    hx-target="#sitter-checklist"
+                                                    hx-swap="outerHTML"
+                                                >
+                                                    {task.name} ", due " {due} " "
+                                                    <input type="hidden" name="token" value=token.clone()/>
+                                                    <input type="hidden" name="plant_id" value=plant_id/>
+                                                    <input type="hidden" name="task_id" value=task.task_id/>
+                                                    <button type="submit">"Done today"</button>
+                                                </form>
+                                            </li>
+                                        }
+                                    })
+                                    .collect::<Vec<_>>()}
+                            </ul>
+                        </div>
+                    }
+                })
+                .collect::<Vec<_>>()}
+        </div>
+    }
+}
+
+#[component]
+pub fn CareHistory(cx: Scope, events: Vec<CareEvent>) -> impl IntoView {
+    let format = time::format_description::parse("[year]-[month]-[day]").unwrap();
+
+    view! { cx,
+        <h3>"Care history"</h3>
+        <ul id="care-history">
+            {events
+                .into_iter()
+                .map(|event| {
+                    let date = event.time_made.format(&format).unwrap();
+                    view! { cx,
+                        <li>
+                            {date} ": " {event.task_name} " by " {event.done_by}
+                            {event.note.map(|note| format!(" - {}", note))}
+                        </li>
+                    }
+                })
+                .collect::<Vec<_>>()}
+        </ul>
+    }
+}
+
 #[component]
 pub fn LoginView(cx: Scope) -> impl IntoView {
     view! {cx,
@@ -368,6 +1372,7 @@ pub fn LoginView(cx: Scope) -> impl IntoView {
                 <input type="password" name="password"/>
             </label>
             <input type="submit" value="Submit"/>
+            <a href="/forgot-password">"Forgot your password?"</a>
         </form>
     }
 }
@@ -381,7 +1386,8 @@ pub fn NotLoggedInMain(cx: Scope) -> impl IntoView {
             <link href="https://fonts.googleapis.com/css?family=Roboto:100,300,400,500,700,900" rel="stylesheet"/>
             <link rel="stylesheet" href="./css/styles.css"/>
         </head>
-        <body>
+        <body hx-on=SWAP_ERRORS>
+            <ErrorBanner message=None/>
             <div id="not-logged-in-main">
                 <h1>"Welcome to Plant Track!"</h1>
                 <h2>"Please login or sign up to continue"</h2>
@@ -392,6 +1398,76 @@ pub fn NotLoggedInMain(cx: Scope) -> impl IntoView {
     }
 }
 
+/// A page for someone who isn't logged in, such as where an emailed link lands. Its content is
+/// inside `#not-logged-in-main` so the `LoginView` form works on it.
+#[component]
+pub fn AccountPage(cx: Scope, title: &'static str, children: Children) -> impl IntoView {
+    view! { cx,
+        <head>
+            <script src="https://unpkg.com/htmx.org@1.9.2" integrity="sha384-L6OqL9pRWyyFU3+/bjdSri+iIphTN/bvYyM37tICVyOJkWZLpP2vGn6VUEXgzg6h" crossorigin="anonymous"></script>
+            <link href="https://fonts.googleapis.com/css?family=Roboto:100,300,400,500,700,900" rel="stylesheet"/>
+            <link rel="stylesheet" href="/css/styles.css"/>
+        </head>
+        <body hx-on=SWAP_ERRORS>
+            <ErrorBanner message=None/>
+            <div id="not-logged-in-main">
+                <h1>{title}</h1>
+                {children(cx)}
+            </div>
+        </body>
+    }
+}
+
+/// What went wrong, as a whole page for a request that didn't come from htmx.
+#[component]
+pub fn ErrorPage(cx: Scope, message: String) -> impl IntoView {
+    view! { cx,
+        <AccountPage title="Something went wrong">
+            <p>{message}</p>
+            <a href="/">"Back to your plants"</a>
+        </AccountPage>
+    }
+}
+
+#[component]
+pub fn ForgotPasswordForm(cx: Scope) -> impl IntoView {
+    view! { cx,
+        <form
+            id="forgot-password-form"
+            hx-post="/forgot-password"
+            hx-trigger="submit"
+            hx-swap="outerHTML"
+        >
+            <p>"We'll email you a link to choose a new one."</p>
+            <label>
+                "Email Address: "
+                <input type="email" name="email" required=true/>
+            </label>
+            <input type="submit" value="Send link"/>
+        </form>
+    }
+}
+
+#[component]
+pub fn ResetPasswordForm(cx: Scope, token: String) -> impl IntoView {
+    view! { cx,
+        <form
+            id="reset-password-form"
+            hx-post="/reset-password"
+            hx-trigger="submit"
+            hx-target="#not-logged-in-main"
+            hx-swap="innerHTML"
+        >
+            <input type="hidden" name="token" value=token/>
+            <label>
+                "New password: "
+                <input type="password" name="password" required=true/>
+            </label>
+            <input type="submit" value="Change password"/>
+        </form>
+    }
+}
+
 #[component]
 pub fn SignUpForm(cx: Scope) -> impl IntoView {
     view! { cx,
@@ -0,0 +1,360 @@
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+/// Read when `PLANT_TRACK_CONFIG` isn't set. It's fine for this file not to exist.
+const DEFAULT_CONFIG_FILE: &str = "plant-track.toml";
+
+/// Everything that changes between deployments. Loaded once at startup from an optional TOML
+/// file, then environment variables (and `.env`) override individual values.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub auth: AuthConfig,
+    pub smtp: SmtpConfig,
+    pub reminders: ReminderConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_address: SocketAddr,
+    pub static_dir: PathBuf,
+    pub photo_dir: PathBuf,
+    /// Where users reach the site, for links in emails.
+    pub public_url: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+}
+
+/// Signing in starts a session. Its access tokens expire after `access_token_minutes` and are
+/// replaced using its refresh token, which lasts `token_lifetime_days` after it was last used.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub jwt_secret: String,
+    pub token_lifetime_days: i64,
+    pub access_token_minutes: i64,
+}
+
+/// Mail server used for reminders. Email is disabled while `host` is empty, and no login is
+/// attempted while `username` is empty.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub implicit_tls: bool,
+    pub username: String,
+    pub password: String,
+    pub sender_name: String,
+    pub sender_email: String,
+}
+
+/// Care reminder digests. When and how each user gets theirs is up to their notification
+/// preferences, the email channel is skipped unless `smtp` is configured too.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReminderConfig {
+    pub enabled: bool,
+    /// No longer used, each user picks when theirs is sent. Still accepted so config files from
+    /// before that load, with a warning.
+    pub send_time: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            static_dir: PathBuf::from("resources/css"),
+            photo_dir: PathBuf::from("photos"),
+            public_url: "http://localhost:3000".to_owned(),
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            max_connections: 5,
+        }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            jwt_secret: String::new(),
+            token_lifetime_days: 30,
+            access_token_minutes: 15,
+        }
+    }
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: 587,
+            implicit_tls: false,
+            username: String::new(),
+            password: String::new(),
+            sender_name: "Plant Track".to_owned(),
+            sender_email: String::new(),
+        }
+    }
+}
+
+impl Default for ReminderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            send_time: None,
+        }
+    }
+}
+
+impl SmtpConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.host.is_empty()
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        dotenvy::dotenv().ok();
+
+        let mut config = match std::env::var("PLANT_TRACK_CONFIG") {
+            Ok(path) => Self::from_file(Path::new(&path))?,
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            Err(_) => Self::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        config.warn_deprecated();
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read config file {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        env_override("BIND_ADDRESS", &mut self.server.bind_address)?;
+        env_override("STATIC_DIR", &mut self.server.static_dir)?;
+        env_override("PHOTO_DIR", &mut self.server.photo_dir)?;
+        env_override("PUBLIC_URL", &mut self.server.public_url)?;
+        env_override("DATABASE_URL", &mut self.database.url)?;
+        env_override(
+            "DATABASE_MAX_CONNECTIONS",
+            &mut self.database.max_connections,
+        )?;
+        env_override("JWT_SECRET", &mut self.auth.jwt_secret)?;
+        env_override("TOKEN_LIFETIME_DAYS", &mut self.auth.token_lifetime_days)?;
+        env_override("ACCESS_TOKEN_MINUTES", &mut self.auth.access_token_minutes)?;
+        env_override("SMTP_HOST", &mut self.smtp.host)?;
+        env_override("SMTP_PORT", &mut self.smtp.port)?;
+        env_override("SMTP_IMPLICIT_TLS", &mut self.smtp.implicit_tls)?;
+        env_override("SMTP_USERNAME", &mut self.smtp.username)?;
+        env_override("EMAIL_PASSWORD", &mut self.smtp.password)?;
+        env_override("SMTP_PASSWORD", &mut self.smtp.password)?;
+        env_override("SMTP_SENDER_NAME", &mut self.smtp.sender_name)?;
+        env_override("SMTP_SENDER_EMAIL", &mut self.smtp.sender_email)?;
+        env_override("REMINDERS_ENABLED", &mut self.reminders.enabled)?;
+        Ok(())
+    }
+
+    fn warn_deprecated(&self) {
+        if self.reminders.send_time.is_some() || std::env::var("REMINDER_SEND_TIME").is_ok() {
+            eprintln!(
+                "Warning: reminders.send_time (REMINDER_SEND_TIME) is ignored, each user now \
+                 picks when their reminders are sent in their notification preferences"
+            );
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.database.url.is_empty() {
+            return Err(anyhow!(
+                "DATABASE_URL must be set, or database.url in the config file"
+            ));
+        }
+        if !self.server.public_url.starts_with("http://")
+            && !self.server.public_url.starts_with("https://")
+        {
+            return Err(anyhow!(
+                "server.public_url must be an http or https URL, got {:?}",
+                self.server.public_url
+            ));
+        }
+        if self.database.max_connections == 0 {
+            return Err(anyhow!("database.max_connections must be at least 1"));
+        }
+        if self.auth.jwt_secret.is_empty() {
+            return Err(anyhow!(
+                "JWT_SECRET must be set, or auth.jwt_secret in the config file"
+            ));
+        }
+        if self.auth.token_lifetime_days <= 0 {
+            return Err(anyhow!("auth.token_lifetime_days must be at least 1"));
+        }
+        if self.auth.access_token_minutes <= 0 {
+            return Err(anyhow!("auth.access_token_minutes must be at least 1"));
+        }
+        if self.smtp.is_enabled() && !self.smtp.sender_email.contains('@') {
+            return Err(anyhow!(
+                "smtp.sender_email must be an email address when smtp.host is set, got {:?}",
+                self.smtp.sender_email
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Replaces `field` with the environment variable `name`, if it is set.
+fn env_override<T>(name: &str, field: &mut T) -> Result<()>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Ok(value) = std::env::var(name) {
+        *field = value
+            .parse()
+            .map_err(|e| anyhow!("Invalid value for {}: {:?} ({})", name, value, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_reminder_send_time_is_still_accepted() {
+        let config: Config = toml::from_str(
+            r#"
+            [reminders]
+            enabled = true
+            send_time = "08:00"
+            "#,
+        )
+        .unwrap();
+        assert!(config.reminders.enabled);
+        assert_eq!(config.reminders.send_time.as_deref(), Some("08:00"));
+    }
+
+    fn valid() -> Config {
+        let mut config = Config::default();
+        config.database.url = "postgres://localhost/plants".to_owned();
+        config.auth.jwt_secret = "secret".to_owned();
+        config
+    }
+
+    fn error(config: &Config) -> String {
+        config.validate().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn required_settings_are_named_when_missing() {
+        assert!(valid().validate().is_ok());
+
+        let mut config = valid();
+        config.database.url.clear();
+        assert!(error(&config).contains("DATABASE_URL"));
+
+        let mut config = valid();
+        config.auth.jwt_secret.clear();
+        assert!(error(&config).contains("JWT_SECRET"));
+    }
+
+    #[test]
+    fn malformed_settings_are_rejected() {
+        for url in ["", "localhost:3000", "ftp://plants.example.com"] {
+            let mut config = valid();
+            config.server.public_url = url.to_owned();
+            assert!(error(&config).contains("server.public_url"), "{:?}", url);
+        }
+
+        let mut config = valid();
+        config.database.max_connections = 0;
+        assert!(error(&config).contains("database.max_connections"));
+
+        let mut config = valid();
+        config.auth.access_token_minutes = 0;
+        assert!(error(&config).contains("auth.access_token_minutes"));
+
+        let mut config = valid();
+        config.smtp.host = "smtp.example.com".to_owned();
+        config.smtp.sender_email = "reminders".to_owned();
+        assert!(error(&config).contains("smtp.sender_email"));
+    }
+
+    // each test uses its own variables, the environment is shared by every test thread
+    #[test]
+    fn env_overrides_replace_values_that_parse() {
+        let mut port: u16 = 587;
+        env_override("PLANT_TRACK_TEST_UNSET", &mut port).unwrap();
+        assert_eq!(port, 587);
+
+        std::env::set_var("PLANT_TRACK_TEST_PORT", "2525");
+        env_override("PLANT_TRACK_TEST_PORT", &mut port).unwrap();
+        assert_eq!(port, 2525);
+
+        std::env::set_var("PLANT_TRACK_TEST_BAD_PORT", "lots");
+        let error = env_override("PLANT_TRACK_TEST_BAD_PORT", &mut port).unwrap_err();
+        assert!(error.to_string().contains("PLANT_TRACK_TEST_BAD_PORT"));
+        assert_eq!(port, 2525);
+
+        std::env::set_var("PLANT_TRACK_TEST_BAD_ADDRESS", "not an address");
+        let mut address = ServerConfig::default().bind_address;
+        assert!(env_override("PLANT_TRACK_TEST_BAD_ADDRESS", &mut address).is_err());
+    }
+
+    #[test]
+    fn config_files_are_read_and_checked() {
+        let path = std::env::temp_dir().join(format!("plant-track-{}.toml", std::process::id()));
+        let load = |contents: &str| {
+            std::fs::write(&path, contents).unwrap();
+            Config::from_file(&path)
+        };
+
+        let config = load(
+            r#"
+            [server]
+            public_url = "https://plants.example.com"
+
+            [database]
+            url = "postgres://localhost/plants"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.server.public_url, "https://plants.example.com");
+        assert_eq!(config.database.url, "postgres://localhost/plants");
+        // anything left out keeps its default
+        assert_eq!(config.database.max_connections, 5);
+
+        assert!(load("[server]\npublic_ur = \"https://plants.example.com\"\n").is_err());
+        assert!(load("[sever]\n").is_err());
+        assert!(load("[database]\nmax_connections = \"five\"\n").is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let error = Config::from_file(&path).unwrap_err();
+        assert!(error.to_string().contains("Couldn't read config file"));
+    }
+}
@@ -1,19 +1,111 @@
-use sqlx::postgres::PgPoolOptions;
-use sqlx::{Pool, Postgres, Row};
+use sqlx::postgres::{PgPoolOptions, PgQueryResult};
+use sqlx::{Executor, Pool, Postgres, Row, Transaction};
 
-use crate::User;
+use crate::{
+    CareEvent, CareTask, CommentMatch, Comments, DueDigest, NotificationPreferences, PlantPhoto,
+    User, HIGHLIGHT_END, HIGHLIGHT_START,
+};
 
 use super::Plant;
+use crate::accounts::TokenPurpose;
+use crate::filters::SavedFilter;
+use crate::households::{Household, HouseholdMember, HouseholdRole};
+use crate::locations::Location;
+use crate::sessions::Session;
+use crate::sitters::SitterLink;
 use anyhow::Result;
 
-pub async fn init_pool(db_url: &str) -> Result<Pool<Postgres>> {
+/// The households user `$1` is a member of, temporary memberships that have run out aside. A
+/// plant is shared by everyone in its household, so queries scope plants with
+/// `p.household_id IN ({MEMBER_OF})`, binding the user id first. Anyone in it can read.
+const MEMBER_OF: &str = r#"
+    SELECT m.household_id FROM household_members m
+    WHERE m.user_id = $1 AND (m.expires_at IS NULL OR m.expires_at > NOW())
+"#;
+
+/// Like `MEMBER_OF`, the households where user `$1` can log care, write notes and add photos.
+const CARER_OF: &str = r#"
+    SELECT m.household_id FROM household_members m
+    WHERE m.user_id = $1 AND (m.expires_at IS NULL OR m.expires_at > NOW())
+        AND m.role IN ('owner', 'caretaker')
+"#;
+
+/// Like `MEMBER_OF`, the households where user `$1` can add and change plants and manage who is
+/// in it.
+const OWNER_OF: &str = r#"
+    SELECT m.household_id FROM household_members m
+    WHERE m.user_id = $1 AND (m.expires_at IS NULL OR m.expires_at > NOW())
+        AND m.role = 'owner'
+"#;
+
+/// Selects every plant column. Callers append their own `WHERE` clause against the `p` alias and
+/// then fill in `Plant::tasks` and `Plant::tags` with `attach_tasks_and_tags`.
+const PLANT_SELECT: &str = "SELECT p.* FROM plants p";
+
+/// Selects every task column, with `last_done` filled in from the latest care event of the task.
+/// Callers append their own `WHERE` clause against the `t` alias.
+const TASK_SELECT: &str = r#"
+    SELECT
+      t.*,
+      (SELECT (MAX(e.time_made) AT TIME ZONE 'UTC')::DATE FROM care_events e
+        WHERE e.task_id = t.task_id) AS last_done
+    FROM care_tasks t
+"#;
+
+pub async fn init_pool(db_url: &str, max_connections: u32) -> Result<Pool<Postgres>> {
     let pool = PgPoolOptions::new()
-        .max_connections(5)
+        .max_connections(max_connections)
         .connect(db_url)
         .await?;
     Ok(pool)
 }
 
+/// Applies any migrations in `migrations/` that haven't been run against this database yet.
+pub async fn run_migrations(pool: &Pool<Postgres>) -> Result<()> {
+    sqlx::migrate!().run(pool).await?;
+    Ok(())
+}
+
+/// Dummy users and plants for local development. Kept out of the migrations so it can never
+/// end up in a real database by accident.
+pub async fn load_dev_fixtures(pool: &Pool<Postgres>) -> Result<()> {
+    pool.execute(include_str!("../fixtures/dev.sql")).await?;
+    Ok(())
+}
+
+/// Loads the care tasks and tags for every plant, a single query for each.
+async fn attach_tasks_and_tags(
+    pool: &Pool<Postgres>,
+    mut plants: Vec<Plant>,
+) -> Result<Vec<Plant>> {
+    let plant_ids: Vec<i32> = plants.iter().map(|plant| plant.plant_id).collect();
+    let query = format!("{TASK_SELECT} WHERE t.plant_id = ANY($1) ORDER BY t.task_id");
+    let tasks: Vec<CareTask> = sqlx::query_as(&query)
+        .bind(&plant_ids)
+        .fetch_all(pool)
+        .await?;
+    let tags: Vec<(i32, String)> = sqlx::query_as(
+        "SELECT plant_id, tag FROM plant_tags WHERE plant_id = ANY($1) ORDER BY tag",
+    )
+    .bind(&plant_ids)
+    .fetch_all(pool)
+    .await?;
+
+    for plant in plants.iter_mut() {
+        plant.tasks = tasks
+            .iter()
+            .filter(|task| task.plant_id == plant.plant_id)
+            .cloned()
+            .collect();
+        plant.tags = tags
+            .iter()
+            .filter(|(plant_id, _)| *plant_id == plant.plant_id)
+            .map(|(_, tag)| tag.clone())
+            .collect();
+    }
+    Ok(plants)
+}
+
 pub async fn get_all_plants(
     pool: &Pool<Postgres>,
     user_id: i32,
@@ -24,18 +116,19 @@ pub async fn get_all_plants(
         x => Some(x),
     };
 
-    let rows = sqlx::query_as(
+    let query = format!(
         r#"
-        SELECT * FROM plants
-        WHERE user_id = $1
+        {PLANT_SELECT}
+        WHERE p.household_id IN ({MEMBER_OF})
         LIMIT $2
-        "#,
-    )
-    .bind(user_id)
-    .bind(limit)
-    .fetch_all(pool)
-    .await?;
-    Ok(rows)
+        "#
+    );
+    let rows = sqlx::query_as(&query)
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+    attach_tasks_and_tags(pool, rows).await
 }
 
 pub async fn get_user_from_email(pool: &Pool<Postgres>, email: String) -> Result<User> {
@@ -46,54 +139,255 @@ pub async fn get_user_from_email(pool: &Pool<Postgres>, email: String) -> Result
     Ok(user)
 }
 
+/// Inserts the plant into `plant.household_id` along with its tasks, `plant.user_id` being who
+/// added it. Each task's `last_done`, if any, becomes its first care event. Fails if the user
+/// doesn't own the household or the location is in another one.
 pub async fn add_plant_to_db(pool: &Pool<Postgres>, plant: Plant) -> Result<i32> {
-    let result = sqlx::query(
-        "INSERT INTO plants (user_id, botanical_name, common_name, last_fed, feed_interval, \
-            last_potted, potting_interval, last_pruned, pruning_interval)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+    let mut tx = pool.begin().await?;
+    let query = format!(
+        r#"
+        INSERT INTO plants (user_id, household_id, botanical_name, common_name, location_id)
+            SELECT $1, $2, $3, $4, $5
+            WHERE $2 IN ({OWNER_OF}) AND (
+              $5::INTEGER IS NULL
+              OR EXISTS (SELECT 1 FROM locations l WHERE l.location_id = $5 AND l.household_id = $2)
+            )
             RETURNING plant_id
+        "#
+    );
+    let result = sqlx::query(&query)
+        .bind(plant.user_id)
+        .bind(plant.household_id)
+        .bind(plant.botanical_name)
+        .bind(plant.common_name)
+        .bind(plant.location_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let plant_id = result.get::<i32, _>("plant_id");
+
+    for task in plant.tasks {
+        insert_care_task(&mut tx, plant_id, plant.user_id, task).await?;
+    }
+    set_tags(&mut tx, plant_id, &plant.tags).await?;
+    tx.commit().await?;
+    Ok(plant_id)
+}
+
+/// Replaces the plant's tags, which are expected to be normalised already.
+async fn set_tags(
+    tx: &mut Transaction<'_, Postgres>,
+    plant_id: i32,
+    tags: &[String],
+) -> Result<()> {
+    sqlx::query("DELETE FROM plant_tags WHERE plant_id = $1")
+        .bind(plant_id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO plant_tags (plant_id, tag)
+            SELECT $1, tag FROM UNNEST($2::TEXT[]) AS tag
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(plant_id)
+    .bind(tags)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+async fn insert_care_task(
+    tx: &mut Transaction<'_, Postgres>,
+    plant_id: i32,
+    user_id: i32,
+    task: CareTask,
+) -> Result<i32> {
+    let result = sqlx::query(
+        "INSERT INTO care_tasks (plant_id, name, interval_days, optional)
+            VALUES ($1, $2, $3, $4)
+            RETURNING task_id
         ",
     )
-    .bind(plant.user_id)
-    .bind(plant.botanical_name)
-    .bind(plant.common_name)
-    .bind(plant.last_fed)
-    .bind(plant.feed_interval)
-    .bind(plant.last_potted)
-    .bind(plant.potting_interval)
-    .bind(plant.last_pruned)
-    .bind(plant.pruning_interval)
-    .fetch_one(pool)
+    .bind(plant_id)
+    .bind(task.name)
+    .bind(task.interval_days)
+    .bind(task.optional)
+    .fetch_one(&mut **tx)
     .await?;
-    Ok(result.get::<i32, _>("plant_id"))
+    let task_id = result.get::<i32, _>("task_id");
+
+    if let Some(last_done) = task.last_done {
+        insert_care_event(tx, task_id, user_id, last_done, None).await?;
+    }
+    Ok(task_id)
+}
+
+async fn insert_care_event(
+    tx: &mut Transaction<'_, Postgres>,
+    task_id: i32,
+    user_id: i32,
+    date: time::Date,
+    note: Option<String>,
+) -> Result<i32> {
+    let result = sqlx::query(
+        "INSERT INTO care_events (task_id, user_id, time_made, note)
+            VALUES ($1, $2, $3, $4)
+            RETURNING event_id
+        ",
+    )
+    .bind(task_id)
+    .bind(user_id)
+    .bind(date.midnight().assume_utc())
+    .bind(note)
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(result.get::<i32, _>("event_id"))
+}
+
+/// Records that `user_id` did the task just now. Fails unless they're a caretaker or owner of the
+/// plant's household.
+pub async fn add_care_event(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+    task_id: i32,
+    note: Option<String>,
+) -> Result<i32> {
+    let query = format!(
+        r#"
+        INSERT INTO care_events (task_id, user_id, time_made, note)
+            SELECT t.task_id, $1, NOW(), $4 FROM care_tasks t
+            JOIN plants p ON p.plant_id = t.plant_id
+            WHERE p.household_id IN ({CARER_OF}) AND p.plant_id = $2 AND t.task_id = $3
+            RETURNING event_id
+        "#
+    );
+    let result = sqlx::query(&query)
+        .bind(user_id)
+        .bind(plant_id)
+        .bind(task_id)
+        .bind(note)
+        .fetch_one(pool)
+        .await?;
+    Ok(result.get::<i32, _>("event_id"))
+}
+
+/// Full care history of a plant, newest first, with who did each, a sitter going by the name on
+/// their link.
+pub async fn get_care_events(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+) -> Result<Vec<CareEvent>> {
+    let query = format!(
+        r#"
+        SELECT e.*, t.name AS task_name, COALESCE(s.sitter_name, u.first_name) AS done_by
+        FROM care_events e
+        JOIN care_tasks t ON t.task_id = e.task_id
+        JOIN plants p ON p.plant_id = t.plant_id
+        JOIN users u ON u.user_id = e.user_id
+        LEFT JOIN sitter_links s ON s.link_id = e.sitter_link_id
+        WHERE p.household_id IN ({MEMBER_OF}) AND p.plant_id = $2
+        ORDER BY e.time_made DESC
+        "#
+    );
+    let rows = sqlx::query_as(&query)
+        .bind(user_id)
+        .bind(plant_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Removes a task and its history from a plant in a household `user_id` owns.
+pub async fn delete_care_task(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+    task_id: i32,
+) -> Result<()> {
+    let query = format!(
+        r#"
+        DELETE FROM care_tasks t
+        USING plants p
+        WHERE p.plant_id = t.plant_id AND p.household_id IN ({OWNER_OF})
+            AND p.plant_id = $2 AND t.task_id = $3
+        "#
+    );
+    let result = sqlx::query(&query)
+        .bind(user_id)
+        .bind(plant_id)
+        .bind(task_id)
+        .execute(pool)
+        .await?;
+    expect_rows(result)
+}
+
+/// For updates and deletes scoped to a user. Matching nothing means the row doesn't exist or
+/// isn't theirs, which callers treat the same as a missing row.
+fn expect_rows(result: PgQueryResult) -> Result<()> {
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound.into());
+    }
+    Ok(())
+}
+
+/// Every distinct task name across the user's plants, for the sort selector.
+pub async fn get_task_names(pool: &Pool<Postgres>, user_id: i32) -> Result<Vec<String>> {
+    let query = format!(
+        r#"
+        SELECT DISTINCT t.name FROM care_tasks t
+        JOIN plants p ON p.plant_id = t.plant_id
+        WHERE p.household_id IN ({MEMBER_OF})
+        ORDER BY t.name
+        "#
+    );
+    let names = sqlx::query_scalar(&query)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(names)
 }
 
+/// Plants with a required task that is due by `today`, which is the user's own date for
+/// reminders. `task_names` narrows which tasks count, `None` counts them all.
 pub async fn get_plants_that_need_attention(
     pool: &Pool<Postgres>,
     user_id: i32,
+    task_names: Option<&[String]>,
+    today: time::Date,
 ) -> Result<Vec<Plant>> {
-    let rows = sqlx::query_as(
+    let query = format!(
         r#"
 
-        SELECT 
-          * 
-        FROM 
-          plants 
-        WHERE 
-          user_id = $1
-          AND (
-            last_fed < CURRENT_DATE - feed_interval * INTERVAL '1 day'
-            OR 
-            last_potted < CURRENT_DATE - potting_interval * INTERVAL '1 day'
-            OR 
-            last_pruned < CURRENT_DATE - pruning_interval * INTERVAL '1 day'
+        SELECT
+          p.*
+        FROM
+          plants p
+        WHERE
+          p.household_id IN ({MEMBER_OF})
+          AND EXISTS (
+            SELECT 1 FROM ({TASK_SELECT}) AS t
+            WHERE t.plant_id = p.plant_id
+              AND NOT t.optional
+              AND t.interval_days IS NOT NULL
+              AND ($2::TEXT[] IS NULL OR t.name = ANY($2))
+              AND (
+                t.last_done IS NULL
+                OR
+                t.last_done <= $3 - t.interval_days * INTERVAL '1 day'
+              )
           );
-    "#,
-    )
-    .bind(user_id)
-    .fetch_all(pool)
-    .await?;
-    Ok(rows)
+    "#
+    );
+    let rows = sqlx::query_as(&query)
+        .bind(user_id)
+        .bind(task_names)
+        .bind(today)
+        .fetch_all(pool)
+        .await?;
+    attach_tasks_and_tags(pool, rows).await
 }
 
 pub async fn get_plant_from_id(
@@ -101,72 +395,549 @@ pub async fn get_plant_from_id(
     user_id: i32,
     plant_id: i32,
 ) -> Result<Plant> {
-    let row = sqlx::query_as(
+    let query = format!(
         r#"
-        SELECT * FROM plants WHERE user_id = $1 AND plant_id = $2;
-        "#,
+        {PLANT_SELECT} WHERE p.household_id IN ({MEMBER_OF}) AND p.plant_id = $2;
+        "#
+    );
+    let row = sqlx::query_as(&query)
+        .bind(user_id)
+        .bind(plant_id)
+        .fetch_one(pool)
+        .await?;
+    let mut plants = attach_tasks_and_tags(pool, vec![row]).await?;
+    Ok(plants.remove(0))
+}
+
+/// Updates the plant details and its tasks. Tasks with a `task_id` of -1 are added, any other
+/// `task_id` has to be one of the plant's tasks. Any `last_done` that differs from the current
+/// one is recorded as a new care event by `user_id` rather than overwriting history. Clearing
+/// `last_done` leaves the history alone. Fails without changing anything if the plant isn't in a
+/// household `user_id` owns, the location is in another one, or a task isn't the plant's. The
+/// household it's in stays as it is, its tags are replaced.
+pub async fn update_plant(pool: &Pool<Postgres>, plant: Plant, user_id: i32) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    let query = format!(
+        r#"
+        UPDATE plants p
+        SET botanical_name = $2, common_name = $3, location_id = $5
+        WHERE p.household_id IN ({OWNER_OF}) AND p.plant_id = $4 AND (
+          $5::INTEGER IS NULL
+          OR EXISTS (
+            SELECT 1 FROM locations l
+            WHERE l.location_id = $5 AND l.household_id = p.household_id
+          )
+        )
+        "#
+    );
+    let result = sqlx::query(&query)
+        .bind(user_id)
+        .bind(plant.botanical_name)
+        .bind(plant.common_name)
+        .bind(plant.plant_id)
+        .bind(plant.location_id)
+        .execute(&mut *tx)
+        .await?;
+    expect_rows(result)?;
+
+    let query = format!("{TASK_SELECT} WHERE t.plant_id = $1");
+    let current: Vec<CareTask> = sqlx::query_as(&query)
+        .bind(plant.plant_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    for task in plant.tasks {
+        if task.task_id == -1 {
+            insert_care_task(&mut tx, plant.plant_id, user_id, task).await?;
+            continue;
+        }
+        // another plant's task, or one deleted since the form was loaded
+        let Some(old) = current.iter().find(|old| old.task_id == task.task_id) else {
+            return Err(sqlx::Error::RowNotFound.into());
+        };
+
+        let result = sqlx::query(
+            r#"
+            UPDATE care_tasks
+            SET name = $1, interval_days = $2, optional = $3
+            WHERE task_id = $4 AND plant_id = $5
+            "#,
+        )
+        .bind(&task.name)
+        .bind(task.interval_days)
+        .bind(task.optional)
+        .bind(task.task_id)
+        .bind(plant.plant_id)
+        .execute(&mut *tx)
+        .await?;
+        expect_rows(result)?;
+
+        match task.last_done {
+            Some(last_done) if old.last_done != task.last_done => {
+                insert_care_event(&mut tx, task.task_id, user_id, last_done, None).await?;
+            }
+            _ => {}
+        }
+    }
+    set_tags(&mut tx, plant.plant_id, &plant.tags).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Plants whose name contains `search`, followed by plants with a journal note matching it,
+/// best matching notes first. Only plants with every one of `tags` are kept.
+pub async fn search_plants(
+    pool: &Pool<Postgres>,
+    search: String,
+    tags: &[String],
+    user_id: i32,
+) -> Result<Vec<Plant>> {
+    let pattern = format!("%{}%", search);
+    let query = format!(
+        r#"
+        {PLANT_SELECT}
+        WHERE p.household_id IN ({MEMBER_OF}) AND (
+          p.botanical_name ILIKE $2 OR p.common_name ILIKE $2
+          OR EXISTS (
+            SELECT 1 FROM comments c
+            WHERE c.plant_id = p.plant_id
+              AND c.search_vector @@ websearch_to_tsquery('english', $3)
+          )
+        ) AND NOT EXISTS (
+          SELECT 1 FROM UNNEST($4::TEXT[]) AS wanted(tag)
+          WHERE NOT EXISTS (
+            SELECT 1 FROM plant_tags pt WHERE pt.plant_id = p.plant_id AND pt.tag = wanted.tag
+          )
+        )
+        ORDER BY
+          (p.botanical_name ILIKE $2 OR p.common_name ILIKE $2) DESC,
+          (
+            SELECT MAX(ts_rank(c.search_vector, websearch_to_tsquery('english', $3)))
+            FROM comments c WHERE c.plant_id = p.plant_id
+          ) DESC NULLS LAST
+        "#
+    );
+    let rows = sqlx::query_as(&query)
+        .bind(user_id)
+        .bind(pattern)
+        .bind(search)
+        .bind(tags)
+        .fetch_all(pool)
+        .await?;
+    attach_tasks_and_tags(pool, rows).await
+}
+
+/// Journal notes matching `search`, best first, with the matched words highlighted in a short
+/// snippet.
+pub async fn search_comments(
+    pool: &Pool<Postgres>,
+    search: String,
+    user_id: i32,
+) -> Result<Vec<CommentMatch>> {
+    let headline_options =
+        format!("StartSel={HIGHLIGHT_START}, StopSel={HIGHLIGHT_END}, MaxWords=20, MinWords=5");
+    let query = format!(
+        r#"
+        SELECT
+          c.comment_id,
+          c.plant_id,
+          ts_headline('english', c.comment, q.query, $3) AS snippet,
+          ts_rank(c.search_vector, q.query) AS rank
+        FROM comments c
+        JOIN plants p ON p.plant_id = c.plant_id
+        CROSS JOIN websearch_to_tsquery('english', $2) AS q(query)
+        WHERE p.household_id IN ({MEMBER_OF}) AND c.search_vector @@ q.query
+        ORDER BY rank DESC
+        "#
+    );
+    let rows = sqlx::query_as(&query)
+        .bind(user_id)
+        .bind(search)
+        .bind(headline_options)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Journal notes on a plant in one of `user_id`'s households, newest first.
+pub async fn get_comments(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+) -> Result<Vec<Comments>> {
+    let query = format!(
+        r#"
+        SELECT c.* FROM comments c
+        JOIN plants p ON p.plant_id = c.plant_id
+        WHERE p.household_id IN ({MEMBER_OF}) AND p.plant_id = $2
+        ORDER BY c.time_made DESC
+        "#
+    );
+    let rows = sqlx::query_as(&query)
+        .bind(user_id)
+        .bind(plant_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn get_comment(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+    comment_id: i32,
+) -> Result<Comments> {
+    let query = format!(
+        r#"
+        SELECT c.* FROM comments c
+        JOIN plants p ON p.plant_id = c.plant_id
+        WHERE p.household_id IN ({MEMBER_OF}) AND p.plant_id = $2 AND c.comment_id = $3
+        "#
+    );
+    let row = sqlx::query_as(&query)
+        .bind(user_id)
+        .bind(plant_id)
+        .bind(comment_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(row)
+}
+
+/// Adds a note by `user_id` to a plant. Fails unless they're a caretaker or owner of the plant's
+/// household.
+pub async fn add_comment(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+    comment: String,
+) -> Result<i32> {
+    let query = format!(
+        r#"
+        INSERT INTO comments (plant_id, user_id, time_made, comment)
+            SELECT p.plant_id, $1, NOW(), $3 FROM plants p
+            WHERE p.household_id IN ({CARER_OF}) AND p.plant_id = $2
+            RETURNING comment_id
+        "#
+    );
+    let result = sqlx::query(&query)
+        .bind(user_id)
+        .bind(plant_id)
+        .bind(comment)
+        .fetch_one(pool)
+        .await?;
+    Ok(result.get::<i32, _>("comment_id"))
+}
+
+/// Only the member who wrote a note can change it.
+pub async fn update_comment(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+    comment_id: i32,
+    comment: String,
+) -> Result<()> {
+    let query = format!(
+        r#"
+        UPDATE comments c
+        SET comment = $4, time_edited = NOW()
+        FROM plants p
+        WHERE p.plant_id = c.plant_id AND p.household_id IN ({CARER_OF})
+            AND p.plant_id = $2 AND c.comment_id = $3 AND c.user_id = $1
+        "#
+    );
+    let result = sqlx::query(&query)
+        .bind(user_id)
+        .bind(plant_id)
+        .bind(comment_id)
+        .bind(comment)
+        .execute(pool)
+        .await?;
+    expect_rows(result)
+}
+
+/// Only the member who wrote a note can delete it.
+pub async fn delete_comment(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+    comment_id: i32,
+) -> Result<()> {
+    let query = format!(
+        r#"
+        DELETE FROM comments c
+        USING plants p
+        WHERE p.plant_id = c.plant_id AND p.household_id IN ({CARER_OF})
+            AND p.plant_id = $2 AND c.comment_id = $3 AND c.user_id = $1
+        "#
+    );
+    let result = sqlx::query(&query)
+        .bind(user_id)
+        .bind(plant_id)
+        .bind(comment_id)
+        .execute(pool)
+        .await?;
+    expect_rows(result)
+}
+
+/// Photos of a plant in one of `user_id`'s households, newest first.
+pub async fn get_photos(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+) -> Result<Vec<PlantPhoto>> {
+    let query = format!(
+        r#"
+        SELECT ph.* FROM photos ph
+        JOIN plants p ON p.plant_id = ph.plant_id
+        WHERE p.household_id IN ({MEMBER_OF}) AND p.plant_id = $2
+        ORDER BY ph.time_made DESC
+        "#
+    );
+    let rows = sqlx::query_as(&query)
+        .bind(user_id)
+        .bind(plant_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Fails unless `user_id` is a caretaker or owner of the plant's household.
+pub async fn add_photo(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+    photo_uri: String,
+    thumbnail_uri: Option<String>,
+) -> Result<i32> {
+    let query = format!(
+        r#"
+        INSERT INTO photos (plant_id, user_id, photo_uri, thumbnail_uri, time_made)
+            SELECT p.plant_id, $1, $3, $4, NOW() FROM plants p
+            WHERE p.household_id IN ({CARER_OF}) AND p.plant_id = $2
+            RETURNING photo_id
+        "#
+    );
+    let result = sqlx::query(&query)
+        .bind(user_id)
+        .bind(plant_id)
+        .bind(photo_uri)
+        .bind(thumbnail_uri)
+        .fetch_one(pool)
+        .await?;
+    Ok(result.get::<i32, _>("photo_id"))
+}
+
+/// Returns the deleted photo so its files can be removed from storage.
+pub async fn delete_photo(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+    photo_id: i32,
+) -> Result<Option<PlantPhoto>> {
+    let query = format!(
+        r#"
+        DELETE FROM photos ph
+        USING plants p
+        WHERE p.plant_id = ph.plant_id AND p.household_id IN ({CARER_OF})
+            AND p.plant_id = $2 AND ph.photo_id = $3
+        RETURNING ph.*
+        "#
+    );
+    let row = sqlx::query_as(&query)
+        .bind(user_id)
+        .bind(plant_id)
+        .bind(photo_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
+
+/// Whether `uri` is a photo or thumbnail of a plant in one of the user's households.
+pub async fn user_has_photo(pool: &Pool<Postgres>, user_id: i32, uri: &str) -> Result<bool> {
+    let query = format!(
+        r#"
+        SELECT EXISTS (
+          SELECT 1 FROM photos ph
+          JOIN plants p ON p.plant_id = ph.plant_id
+          WHERE p.household_id IN ({MEMBER_OF}) AND (ph.photo_uri = $2 OR ph.thumbnail_uri = $2)
+        )
+        "#
+    );
+    let exists = sqlx::query_scalar(&query)
+        .bind(user_id)
+        .bind(uri)
+        .fetch_one(pool)
+        .await?;
+    Ok(exists)
+}
+
+pub async fn get_user(pool: &Pool<Postgres>, user_id: i32) -> Result<User> {
+    let user = sqlx::query_as("SELECT * FROM users WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(user)
+}
+
+/// The user's email address, or `None` until they've verified it.
+pub async fn get_verified_email(pool: &Pool<Postgres>, user_id: i32) -> Result<Option<String>> {
+    let email = sqlx::query_scalar(
+        "SELECT email FROM users WHERE user_id = $1 AND email_verified_at IS NOT NULL",
     )
     .bind(user_id)
-    .bind(plant_id)
-    .fetch_one(pool)
+    .fetch_optional(pool)
     .await?;
-    Ok(row)
+    Ok(email)
 }
 
-pub async fn update_plant(pool: &Pool<Postgres>, plant: Plant) -> Result<()> {
-    let _ = sqlx::query(
-        r#"
-        UPDATE plants
-        SET botanical_name = $1, common_name = $2, last_fed = $3, feed_interval = $4, 
-        last_potted = $5, potting_interval = $6, last_pruned = $7, pruning_interval = $8
-        WHERE plant_id = $9
-        "#,
+pub async fn mark_email_verified(pool: &Pool<Postgres>, user_id: i32) -> Result<()> {
+    sqlx::query(
+        "UPDATE users SET email_verified_at = NOW()
+            WHERE user_id = $1 AND email_verified_at IS NULL",
     )
-    .bind(plant.botanical_name)
-    .bind(plant.common_name)
-    .bind(plant.last_fed)
-    .bind(plant.feed_interval)
-    .bind(plant.last_potted)
-    .bind(plant.potting_interval)
-    .bind(plant.last_pruned)
-    .bind(plant.pruning_interval)
-    .bind(plant.plant_id)
+    .bind(user_id)
     .execute(pool)
     .await?;
     Ok(())
 }
 
-pub async fn search_plants(
+/// Sets a new password hash. Any other password reset links still out there stop working.
+pub async fn update_password(
     pool: &Pool<Postgres>,
-    search: String,
     user_id: i32,
-) -> Result<Vec<Plant>> {
-    let search = format!("%{}%", search);
-    let rows = sqlx::query_as(
+    password_hash: String,
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE users SET password_hash = $1 WHERE user_id = $2")
+        .bind(password_hash)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "UPDATE email_tokens SET used_at = NOW()
+            WHERE user_id = $1 AND purpose = 'reset_password' AND used_at IS NULL",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Users whose digest should be sent now: their send hour has passed in their own timezone,
+/// today isn't a quiet day or the wrong day for a weekly digest, and today's hasn't been sent.
+pub async fn get_due_digests(pool: &Pool<Postgres>) -> Result<Vec<DueDigest>> {
+    let digests = sqlx::query_as(
         r#"
-        SELECT * FROM plants
-        WHERE user_id = $1 AND (botanical_name ILIKE $2 OR common_name ILIKE $3) 
+        SELECT n.*, l.local_now::DATE AS local_date
+        FROM notification_preferences n
+        CROSS JOIN LATERAL (SELECT NOW() AT TIME ZONE n.timezone AS local_now) l
+        WHERE cardinality(n.channels) > 0
+          AND EXTRACT(HOUR FROM l.local_now) >= n.send_hour
+          AND NOT EXTRACT(ISODOW FROM l.local_now)::SMALLINT = ANY(n.quiet_days)
+          AND (n.frequency = 'daily' OR EXTRACT(ISODOW FROM l.local_now) = n.weekly_day)
+          AND NOT EXISTS (
+            SELECT 1 FROM reminder_digests d
+            WHERE d.user_id = n.user_id AND d.digest_date = l.local_now::DATE
+          )
+        ORDER BY n.user_id
         "#,
     )
-    .bind(user_id)
-    .bind(search.clone())
-    .bind(search)
     .fetch_all(pool)
     .await?;
-    Ok(rows)
+    Ok(digests)
 }
 
-pub async fn get_user_email(pool: &Pool<Postgres>, user_id: i32) -> Result<String> {
-    let email = sqlx::query("SELECT email FROM users WHERE user_id = $1")
+pub async fn get_notification_preferences(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+) -> Result<NotificationPreferences> {
+    let preferences = sqlx::query_as("SELECT * FROM notification_preferences WHERE user_id = $1")
         .bind(user_id)
         .fetch_one(pool)
-        .await
-        .unwrap()
-        .get::<String, _>("email");
-    Ok(email)
+        .await?;
+    Ok(preferences)
+}
+
+pub async fn update_notification_preferences(
+    pool: &Pool<Postgres>,
+    preferences: NotificationPreferences,
+) -> Result<()> {
+    sqlx::query(
+        "UPDATE notification_preferences
+            SET channels = $2, frequency = $3, weekly_day = $4, send_hour = $5, timezone = $6,
+              task_names = $7, quiet_days = $8, webhook_url = $9, push_service = $10,
+              push_url = $11, push_token = $12
+            WHERE user_id = $1",
+    )
+    .bind(preferences.user_id)
+    .bind(preferences.channels)
+    .bind(preferences.frequency)
+    .bind(preferences.weekly_day)
+    .bind(preferences.send_hour)
+    .bind(preferences.timezone)
+    .bind(preferences.task_names)
+    .bind(preferences.quiet_days)
+    .bind(preferences.webhook_url)
+    .bind(preferences.push_service)
+    .bind(preferences.push_url)
+    .bind(preferences.push_token)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Every timezone name Postgres understands, which is what `notification_preferences` accepts.
+pub async fn get_timezone_names(pool: &Pool<Postgres>) -> Result<Vec<String>> {
+    let names = sqlx::query_scalar("SELECT name FROM pg_timezone_names ORDER BY name")
+        .fetch_all(pool)
+        .await?;
+    Ok(names)
+}
+
+pub async fn is_known_timezone(pool: &Pool<Postgres>, name: &str) -> Result<bool> {
+    let known =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1)")
+            .bind(name)
+            .fetch_one(pool)
+            .await?;
+    Ok(known)
+}
+
+/// Records that the user's digest for `date`, their local day, is being sent. `false` if it
+/// already was.
+pub async fn claim_reminder_digest(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    date: time::Date,
+) -> Result<bool> {
+    let result = sqlx::query(
+        "INSERT INTO reminder_digests (user_id, digest_date) VALUES ($1, $2)
+            ON CONFLICT DO NOTHING",
+    )
+    .bind(user_id)
+    .bind(date)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Undoes `claim_reminder_digest` after a failed send so the next run tries again.
+pub async fn release_reminder_digest(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    date: time::Date,
+) -> Result<()> {
+    sqlx::query("DELETE FROM reminder_digests WHERE user_id = $1 AND digest_date = $2")
+        .bind(user_id)
+        .bind(date)
+        .execute(pool)
+        .await?;
+    Ok(())
 }
 
 pub async fn add_user_to_db(pool: &Pool<Postgres>, user: super::User) -> Result<i32> {
+    let mut tx = pool.begin().await?;
     let result = sqlx::query(
         "INSERT INTO users (email, password_hash, first_name, last_name)
             VALUES ($1, $2, $3, $4)
@@ -177,7 +948,639 @@ pub async fn add_user_to_db(pool: &Pool<Postgres>, user: super::User) -> Result<
     .bind(user.password_hash)
     .bind(user.first_name)
     .bind(user.last_name)
+    .fetch_one(&mut *tx)
+    .await?;
+    let user_id = result.get::<i32, _>("user_id");
+
+    sqlx::query("INSERT INTO notification_preferences (user_id) VALUES ($1)")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // everyone starts with a household of their own, which others can be invited into
+    let household_id: i32 =
+        sqlx::query_scalar("INSERT INTO households (name) VALUES ($1) RETURNING household_id")
+            .bind(format!("{}'s plants", user.first_name))
+            .fetch_one(&mut *tx)
+            .await?;
+    sqlx::query(
+        "INSERT INTO household_members (household_id, user_id, role) VALUES ($1, $2, 'owner')",
+    )
+    .bind(household_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(user_id)
+}
+
+/// Starts a session for `user_id`, returning its id.
+pub async fn insert_session(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    refresh_token_hash: &str,
+    expires_at: time::OffsetDateTime,
+) -> Result<i32> {
+    let session_id = sqlx::query_scalar(
+        "INSERT INTO sessions (user_id, refresh_token_hash, expires_at)
+            VALUES ($1, $2, $3)
+            RETURNING session_id
+        ",
+    )
+    .bind(user_id)
+    .bind(refresh_token_hash)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await?;
+    Ok(session_id)
+}
+
+pub async fn get_session(pool: &Pool<Postgres>, session_id: i32) -> Result<Option<Session>> {
+    let session = sqlx::query_as("SELECT * FROM sessions WHERE session_id = $1")
+        .bind(session_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(session)
+}
+
+/// Swaps the session's refresh token for a new one, as long as `refresh_token_hash` is the
+/// current one and the session is still live. Returns the session's user if it was swapped.
+pub async fn rotate_session(
+    pool: &Pool<Postgres>,
+    session_id: i32,
+    refresh_token_hash: &str,
+    new_token_hash: &str,
+    expires_at: time::OffsetDateTime,
+) -> Result<Option<i32>> {
+    let user_id = sqlx::query_scalar(
+        r#"
+        UPDATE sessions
+        SET previous_token_hash = refresh_token_hash, refresh_token_hash = $3,
+            time_refreshed = NOW(), expires_at = $4
+        WHERE session_id = $1 AND refresh_token_hash = $2
+            AND revoked_at IS NULL AND expires_at > NOW()
+        RETURNING user_id
+        "#,
+    )
+    .bind(session_id)
+    .bind(refresh_token_hash)
+    .bind(new_token_hash)
+    .bind(expires_at)
+    .fetch_optional(pool)
+    .await?;
+    Ok(user_id)
+}
+
+pub async fn revoke_session(pool: &Pool<Postgres>, user_id: i32, session_id: i32) -> Result<()> {
+    sqlx::query(
+        "UPDATE sessions SET revoked_at = NOW()
+            WHERE session_id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Signs the user out everywhere.
+pub async fn revoke_all_sessions(pool: &Pool<Postgres>, user_id: i32) -> Result<()> {
+    sqlx::query("UPDATE sessions SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Records a link about to be emailed, returning its id.
+pub async fn insert_email_token(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    purpose: TokenPurpose,
+    expires_at: time::OffsetDateTime,
+) -> Result<i32> {
+    let token_id = sqlx::query_scalar(
+        "INSERT INTO email_tokens (user_id, purpose, expires_at)
+            VALUES ($1, $2, $3)
+            RETURNING token_id
+        ",
+    )
+    .bind(user_id)
+    .bind(purpose)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await?;
+    Ok(token_id)
+}
+
+/// Marks the link used. False if it already was, has expired or isn't what it claims to be.
+pub async fn use_email_token(
+    pool: &Pool<Postgres>,
+    token_id: i32,
+    user_id: i32,
+    purpose: TokenPurpose,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE email_tokens SET used_at = NOW()
+        WHERE token_id = $1 AND user_id = $2 AND purpose = $3
+            AND used_at IS NULL AND expires_at > NOW()
+        "#,
+    )
+    .bind(token_id)
+    .bind(user_id)
+    .bind(purpose)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// The user's households with their role in each, the one they've been in longest first.
+pub async fn get_households(pool: &Pool<Postgres>, user_id: i32) -> Result<Vec<Household>> {
+    let rows = sqlx::query_as(
+        r#"
+        SELECT h.*, m.role, m.expires_at FROM households h
+        JOIN household_members m ON m.household_id = h.household_id
+        WHERE m.user_id = $1 AND (m.expires_at IS NULL OR m.expires_at > NOW())
+        ORDER BY m.joined_at, h.household_id
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// Where plants the user adds go unless they say otherwise, the household they've owned longest.
+pub async fn get_default_household(pool: &Pool<Postgres>, user_id: i32) -> Result<i32> {
+    let query = format!(
+        r#"
+        SELECT m.household_id FROM household_members m
+        WHERE m.household_id IN ({OWNER_OF}) AND m.user_id = $1
+        ORDER BY m.joined_at, m.household_id
+        LIMIT 1
+        "#
+    );
+    let household_id = sqlx::query_scalar(&query)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(household_id)
+}
+
+/// The user's role in the household, `None` if they aren't in it or their time is up.
+pub async fn get_role(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    household_id: i32,
+) -> Result<Option<HouseholdRole>> {
+    let role = sqlx::query_scalar(
+        r#"
+        SELECT role FROM household_members
+        WHERE user_id = $1 AND household_id = $2 AND (expires_at IS NULL OR expires_at > NOW())
+        "#,
+    )
+    .bind(user_id)
+    .bind(household_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(role)
+}
+
+/// The user's role in the household the plant is in, `None` if they can't see it.
+pub async fn get_plant_role(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+) -> Result<Option<HouseholdRole>> {
+    let role = sqlx::query_scalar(
+        r#"
+        SELECT m.role FROM household_members m
+        JOIN plants p ON p.household_id = m.household_id
+        WHERE m.user_id = $1 AND p.plant_id = $2 AND (m.expires_at IS NULL OR m.expires_at > NOW())
+        "#,
+    )
+    .bind(user_id)
+    .bind(plant_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(role)
+}
+
+/// Everyone in each of the user's households, the user included.
+pub async fn get_household_members(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+) -> Result<Vec<HouseholdMember>> {
+    let query = format!(
+        r#"
+        SELECT
+          m.household_id, m.user_id, m.joined_at, m.role, m.expires_at,
+          u.first_name, u.last_name, u.email
+        FROM household_members m
+        JOIN users u ON u.user_id = m.user_id
+        WHERE m.household_id IN ({MEMBER_OF})
+        ORDER BY m.household_id, m.joined_at
+        "#
+    );
+    let rows = sqlx::query_as(&query).bind(user_id).fetch_all(pool).await?;
+    Ok(rows)
+}
+
+/// Changes what `member_id` may do in the household, or until when. Fails if `user_id` doesn't
+/// own it. False, changing nothing, if it would leave the household without an owner who stays.
+pub async fn update_member(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    household_id: i32,
+    member_id: i32,
+    role: HouseholdRole,
+    expires_at: Option<time::OffsetDateTime>,
+) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+    let query = format!(
+        r#"
+        UPDATE household_members SET role = $4, expires_at = $5
+        WHERE household_id = $2 AND user_id = $3 AND $2 IN ({OWNER_OF})
+        "#
+    );
+    let result = sqlx::query(&query)
+        .bind(user_id)
+        .bind(household_id)
+        .bind(member_id)
+        .bind(role)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+    expect_rows(result)?;
+    if !has_lasting_owner(&mut tx, household_id).await? {
+        return Ok(false);
+    }
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Takes `member_id` out of the household. Fails if `user_id` doesn't own it. False, changing
+/// nothing, if it would leave the household without an owner who stays.
+pub async fn remove_member(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    household_id: i32,
+    member_id: i32,
+) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+    let query = format!(
+        r#"
+        DELETE FROM household_members
+        WHERE household_id = $2 AND user_id = $3 AND $2 IN ({OWNER_OF})
+        "#
+    );
+    let result = sqlx::query(&query)
+        .bind(user_id)
+        .bind(household_id)
+        .bind(member_id)
+        .execute(&mut *tx)
+        .await?;
+    expect_rows(result)?;
+    if !has_lasting_owner(&mut tx, household_id).await? {
+        return Ok(false);
+    }
+    tx.commit().await?;
+    Ok(true)
+}
+
+async fn has_lasting_owner(tx: &mut Transaction<'_, Postgres>, household_id: i32) -> Result<bool> {
+    let exists = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+          SELECT 1 FROM household_members
+          WHERE household_id = $1 AND role = 'owner' AND expires_at IS NULL
+        )
+        "#,
+    )
+    .bind(household_id)
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(exists)
+}
+
+/// Records an invitation about to be emailed, returning its id. Whoever accepts it joins as
+/// `role`, until `grant_expires_at` if that's set. Fails if `invited_by` doesn't own the
+/// household.
+pub async fn insert_invitation(
+    pool: &Pool<Postgres>,
+    invited_by: i32,
+    household_id: i32,
+    email: &str,
+    role: HouseholdRole,
+    grant_expires_at: Option<time::OffsetDateTime>,
+    expires_at: time::OffsetDateTime,
+) -> Result<i32> {
+    let query = format!(
+        r#"
+        INSERT INTO household_invitations
+            (invited_by, household_id, email, role, grant_expires_at, expires_at)
+            SELECT $1, $2, $3, $4, $5, $6
+            WHERE $2 IN ({OWNER_OF})
+            RETURNING invitation_id
+        "#
+    );
+    let invitation_id = sqlx::query_scalar(&query)
+        .bind(invited_by)
+        .bind(household_id)
+        .bind(email)
+        .bind(role)
+        .bind(grant_expires_at)
+        .bind(expires_at)
+        .fetch_one(pool)
+        .await?;
+    Ok(invitation_id)
+}
+
+/// Uses up the invitation and adds `user_id` to its household, returning the household. `None`
+/// if it was already used, has expired or was sent to an address other than the user's. A
+/// temporary member gets the invitation's role and time instead, anyone else keeps theirs.
+pub async fn accept_invitation(
+    pool: &Pool<Postgres>,
+    invitation_id: i32,
+    user_id: i32,
+) -> Result<Option<i32>> {
+    let mut tx = pool.begin().await?;
+    let invitation: Option<(i32, HouseholdRole, Option<time::OffsetDateTime>)> = sqlx::query_as(
+        r#"
+        UPDATE household_invitations i SET accepted_at = NOW()
+        FROM users u
+        WHERE i.invitation_id = $1 AND u.user_id = $2 AND LOWER(i.email) = LOWER(u.email)
+            AND i.accepted_at IS NULL AND i.expires_at > NOW()
+        RETURNING i.household_id, i.role, i.grant_expires_at
+        "#,
+    )
+    .bind(invitation_id)
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some((household_id, role, expires_at)) = invitation else {
+        return Ok(None);
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO household_members (household_id, user_id, role, expires_at)
+            VALUES ($1, $2, $3, $4)
+        ON CONFLICT (household_id, user_id) DO UPDATE
+            SET role = EXCLUDED.role, expires_at = EXCLUDED.expires_at, joined_at = NOW()
+            WHERE household_members.expires_at IS NOT NULL
+        "#,
+    )
+    .bind(household_id)
+    .bind(user_id)
+    .bind(role)
+    .bind(expires_at)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Some(household_id))
+}
+
+/// Makes a plant-sitter link for the household, returning it. Fails if `user_id` doesn't own the
+/// household.
+pub async fn insert_sitter_link(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    household_id: i32,
+    sitter_name: &str,
+    starts_at: time::OffsetDateTime,
+    expires_at: time::OffsetDateTime,
+) -> Result<SitterLink> {
+    let query = format!(
+        r#"
+        INSERT INTO sitter_links (household_id, created_by, sitter_name, starts_at, expires_at)
+            SELECT $2, $1, $3, $4, $5
+            WHERE $2 IN ({OWNER_OF})
+            RETURNING *
+        "#
+    );
+    let link = sqlx::query_as(&query)
+        .bind(user_id)
+        .bind(household_id)
+        .bind(sitter_name)
+        .bind(starts_at)
+        .bind(expires_at)
+        .fetch_one(pool)
+        .await?;
+    Ok(link)
+}
+
+/// The links still to come or in use in households the user owns, soonest first.
+pub async fn get_sitter_links(pool: &Pool<Postgres>, user_id: i32) -> Result<Vec<SitterLink>> {
+    let query = format!(
+        r#"
+        SELECT s.* FROM sitter_links s
+        WHERE s.household_id IN ({OWNER_OF}) AND s.revoked_at IS NULL AND s.expires_at > NOW()
+        ORDER BY s.starts_at, s.link_id
+        "#
+    );
+    let rows = sqlx::query_as(&query).bind(user_id).fetch_all(pool).await?;
+    Ok(rows)
+}
+
+/// A link by id, whether or not it can still be used. Only for opening a link whose token has
+/// been checked, everything else goes through the household.
+pub async fn get_sitter_link(pool: &Pool<Postgres>, link_id: i32) -> Result<Option<SitterLink>> {
+    let link = sqlx::query_as("SELECT * FROM sitter_links WHERE link_id = $1")
+        .bind(link_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(link)
+}
+
+/// Stops a link working straight away. Fails if `user_id` doesn't own its household.
+pub async fn revoke_sitter_link(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    household_id: i32,
+    link_id: i32,
+) -> Result<()> {
+    let query = format!(
+        r#"
+        UPDATE sitter_links SET revoked_at = NOW()
+        WHERE link_id = $3 AND household_id = $2 AND $2 IN ({OWNER_OF}) AND revoked_at IS NULL
+        "#
+    );
+    let result = sqlx::query(&query)
+        .bind(user_id)
+        .bind(household_id)
+        .bind(link_id)
+        .execute(pool)
+        .await?;
+    expect_rows(result)
+}
+
+/// Every plant in the household, for a sitter link that has already been checked.
+pub async fn get_household_plants(pool: &Pool<Postgres>, household_id: i32) -> Result<Vec<Plant>> {
+    let query = format!("{PLANT_SELECT} WHERE p.household_id = $1 ORDER BY p.plant_id");
+    let rows = sqlx::query_as(&query)
+        .bind(household_id)
+        .fetch_all(pool)
+        .await?;
+    attach_tasks_and_tags(pool, rows).await
+}
+
+/// Records that the sitter did the task just now, on behalf of whoever made the link. Fails
+/// unless the link is in use and the plant is in its household.
+pub async fn add_sitter_care_event(
+    pool: &Pool<Postgres>,
+    link_id: i32,
+    plant_id: i32,
+    task_id: i32,
+) -> Result<i32> {
+    let event_id = sqlx::query_scalar(
+        r#"
+        INSERT INTO care_events (task_id, user_id, time_made, sitter_link_id)
+            SELECT t.task_id, s.created_by, NOW(), s.link_id FROM care_tasks t
+            JOIN plants p ON p.plant_id = t.plant_id
+            JOIN sitter_links s ON s.household_id = p.household_id
+            WHERE s.link_id = $1 AND p.plant_id = $2 AND t.task_id = $3
+                AND s.revoked_at IS NULL AND NOW() >= s.starts_at AND NOW() < s.expires_at
+            RETURNING event_id
+        "#,
+    )
+    .bind(link_id)
+    .bind(plant_id)
+    .bind(task_id)
     .fetch_one(pool)
     .await?;
-    Ok(result.get::<i32, _>("user_id"))
+    Ok(event_id)
+}
+
+/// Every location in the user's households, by household and then name.
+pub async fn get_locations(pool: &Pool<Postgres>, user_id: i32) -> Result<Vec<Location>> {
+    let query = format!(
+        r#"
+        SELECT l.* FROM locations l
+        WHERE l.household_id IN ({MEMBER_OF})
+        ORDER BY l.household_id, l.name
+        "#
+    );
+    let rows = sqlx::query_as(&query).bind(user_id).fetch_all(pool).await?;
+    Ok(rows)
+}
+
+/// Adds a location to `location.household_id`, returning its id. Fails if `user_id` doesn't own
+/// the household or it already has a location with that name.
+pub async fn add_location(pool: &Pool<Postgres>, user_id: i32, location: Location) -> Result<i32> {
+    let query = format!(
+        r#"
+        INSERT INTO locations (household_id, name, kind, light_level, orientation)
+            SELECT $2, $3, $4, $5, $6
+            WHERE $2 IN ({OWNER_OF})
+            RETURNING location_id
+        "#
+    );
+    let location_id = sqlx::query_scalar(&query)
+        .bind(user_id)
+        .bind(location.household_id)
+        .bind(location.name)
+        .bind(location.kind)
+        .bind(location.light_level)
+        .bind(location.orientation)
+        .fetch_one(pool)
+        .await?;
+    Ok(location_id)
+}
+
+/// Removes a location from a household `user_id` owns. Its plants are left unplaced.
+pub async fn delete_location(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    household_id: i32,
+    location_id: i32,
+) -> Result<()> {
+    let query = format!(
+        r#"
+        DELETE FROM locations
+        WHERE location_id = $3 AND household_id = $2 AND $2 IN ({OWNER_OF})
+        "#
+    );
+    let result = sqlx::query(&query)
+        .bind(user_id)
+        .bind(household_id)
+        .bind(location_id)
+        .execute(pool)
+        .await?;
+    expect_rows(result)
+}
+
+/// Every tag on a plant in the user's households, for suggesting on the forms.
+pub async fn get_tags(pool: &Pool<Postgres>, user_id: i32) -> Result<Vec<String>> {
+    let query = format!(
+        r#"
+        SELECT DISTINCT pt.tag FROM plant_tags pt
+        JOIN plants p ON p.plant_id = pt.plant_id
+        WHERE p.household_id IN ({MEMBER_OF})
+        ORDER BY pt.tag
+        "#
+    );
+    let tags = sqlx::query_scalar(&query)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(tags)
+}
+
+/// The user's saved filters, in the order they were saved.
+pub async fn get_saved_filters(pool: &Pool<Postgres>, user_id: i32) -> Result<Vec<SavedFilter>> {
+    let rows = sqlx::query_as(
+        "SELECT * FROM saved_filters WHERE user_id = $1 ORDER BY time_made, filter_id",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn get_saved_filter(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    filter_id: i32,
+) -> Result<SavedFilter> {
+    let row = sqlx::query_as("SELECT * FROM saved_filters WHERE user_id = $1 AND filter_id = $2")
+        .bind(user_id)
+        .bind(filter_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(row)
+}
+
+/// Saves the filter for `filter.user_id`, replacing one of theirs with the same name.
+pub async fn save_filter(pool: &Pool<Postgres>, filter: SavedFilter) -> Result<i32> {
+    let filter_id = sqlx::query_scalar(
+        r#"
+        INSERT INTO saved_filters (user_id, name, search_string, tags, due)
+            VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (user_id, name) DO UPDATE
+            SET search_string = EXCLUDED.search_string, tags = EXCLUDED.tags, due = EXCLUDED.due
+        RETURNING filter_id
+        "#,
+    )
+    .bind(filter.user_id)
+    .bind(filter.name)
+    .bind(filter.search_string)
+    .bind(filter.tags)
+    .bind(filter.due)
+    .fetch_one(pool)
+    .await?;
+    Ok(filter_id)
+}
+
+pub async fn delete_saved_filter(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    filter_id: i32,
+) -> Result<()> {
+    let result = sqlx::query("DELETE FROM saved_filters WHERE user_id = $1 AND filter_id = $2")
+        .bind(user_id)
+        .bind(filter_id)
+        .execute(pool)
+        .await?;
+    expect_rows(result)
 }
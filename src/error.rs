@@ -0,0 +1,275 @@
+use axum::{
+    body::Body,
+    extract::{rejection::JsonRejection, Request},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use leptos::view;
+use leptos::*;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::auth_memes::AuthError;
+use crate::components::*;
+
+/// Anything a handler can fail with. HTML routes return it as is, which renders an error
+/// banner for htmx to swap in, `/api` routes wrap it in `ApiError` to get JSON instead.
+#[derive(Debug)]
+pub enum AppError {
+    /// The request is malformed or fails validation, the message says why.
+    BadRequest(String),
+    /// Missing or wrong credentials.
+    Unauthorized(String),
+    /// Signed in, but not allowed to touch this.
+    Forbidden,
+    /// Doesn't exist, or belongs to another user and shouldn't be revealed.
+    NotFound,
+    /// Clashes with something that already exists, such as an email that is already signed up.
+    Conflict(String),
+    /// The request body is over a size limit, the message says which.
+    TooLarge(String),
+    /// Our fault. Logged, but never shown to the client.
+    Internal(anyhow::Error),
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        AppError::BadRequest(message.into())
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// What the client is told.
+    pub fn message(&self) -> String {
+        match self {
+            AppError::BadRequest(message)
+            | AppError::Unauthorized(message)
+            | AppError::Conflict(message)
+            | AppError::TooLarge(message) => message.clone(),
+            AppError::Forbidden => "You don't have access to that".to_owned(),
+            AppError::NotFound => "Not found".to_owned(),
+            AppError::Internal(_) => "Something went wrong, please try again".to_owned(),
+        }
+    }
+
+    fn log(&self) {
+        if let AppError::Internal(error) = self {
+            eprintln!("Error handling request: {:#}", error);
+        }
+    }
+}
+
+/// `db_api` fails with `RowNotFound` when a row doesn't exist or isn't the user's, and with a
+/// unique violation when it clashes with an existing one. Anything else is a bug or an outage.
+impl From<anyhow::Error> for AppError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => AppError::NotFound,
+            Some(sqlx::Error::Database(db_error)) if db_error.is_unique_violation() => {
+                AppError::Conflict("That already exists".to_owned())
+            }
+            _ => AppError::Internal(error),
+        }
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(error: sqlx::Error) -> Self {
+        anyhow::Error::from(error).into()
+    }
+}
+
+impl From<AuthError> for AppError {
+    fn from(error: AuthError) -> Self {
+        match error {
+            AuthError::WrongCredentials => {
+                AppError::Unauthorized("Wrong email or password".to_owned())
+            }
+            AuthError::MissingCredentials => AppError::BadRequest("Missing credentials".to_owned()),
+            AuthError::InvalidToken => AppError::Unauthorized("Invalid token".to_owned()),
+            AuthError::TokenCreation => {
+                AppError::Internal(anyhow::anyhow!("Couldn't create a token"))
+            }
+        }
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        AppError::BadRequest(rejection.body_text())
+    }
+}
+
+/// Renders as `ErrorBanner`, retargeted so htmx swaps it into the page's banner whatever the
+/// request was aimed at. The pages let htmx swap error responses, see `Index`. Requests that
+/// didn't come from htmx get an `ErrorPage` instead, see `full_page_errors`.
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        self.log();
+        let message = self.message();
+        let banner_message = message.clone();
+        let html = leptos::ssr::render_to_string(move |cx| {
+            view! { cx,
+                <ErrorBanner
+                    message=Some(banner_message)
+                />
+            }
+        });
+        let mut response = (
+            self.status(),
+            [("HX-Retarget", "#error-banner"), ("HX-Reswap", "outerHTML")],
+            axum::response::Html(html),
+        )
+            .into_response();
+        response.extensions_mut().insert(ErrorMessage(message));
+        response
+    }
+}
+
+/// What an `AppError` response told the client, kept so `full_page_errors` can render it again.
+#[derive(Debug, Clone)]
+struct ErrorMessage(String);
+
+/// Swaps the banner fragment of an `AppError` response for a whole `ErrorPage` when the request
+/// wasn't made by htmx, such as opening a link from an email, which would otherwise show a bare
+/// banner. Goes outside everything else on the HTML routes, the status and cookies are kept.
+pub async fn full_page_errors(request: Request, next: Next) -> Response {
+    let from_htmx = request.headers().contains_key("HX-Request");
+    let response = next.run(request).await;
+    let message = match response.extensions().get::<ErrorMessage>() {
+        Some(ErrorMessage(message)) if !from_htmx => message.clone(),
+        _ => return response,
+    };
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <ErrorPage
+                message=message
+            />
+        }
+    });
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove("HX-Retarget");
+    parts.headers.remove("HX-Reswap");
+    Response::from_parts(parts, Body::from(html))
+}
+
+/// An `AppError` from a `/api` route, rendered as an `ErrorBody` instead of HTML.
+#[derive(Debug)]
+pub struct ApiError(pub AppError);
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+impl<E: Into<AppError>> From<E> for ApiError {
+    fn from(error: E) -> Self {
+        ApiError(error.into())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        self.0.log();
+        let body = Json(ErrorBody {
+            error: self.0.message(),
+        });
+        (self.0.status(), body).into_response()
+    }
+}
+
+/// The body of every JSON error response, from `ApiError` and `AuthError` alike.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row_not_found_is_404() {
+        let error: AppError = anyhow::Error::from(sqlx::Error::RowNotFound).into();
+        assert_eq!(error.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn internal_errors_hide_details() {
+        let error: AppError = anyhow::anyhow!("connection refused to 10.0.0.1").into();
+        assert_eq!(error.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!error.message().contains("10.0.0.1"));
+    }
+
+    #[test]
+    fn wrong_credentials_are_401() {
+        let error: AppError = AuthError::WrongCredentials.into();
+        assert_eq!(error.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn html_errors_retarget_the_banner() {
+        let response = AppError::Conflict("Taken".to_owned()).into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(response.headers()["HX-Retarget"], "#error-banner");
+    }
+
+    #[tokio::test]
+    async fn only_htmx_requests_get_the_banner() {
+        use axum::{middleware, routing::get, Router};
+        use tower::ServiceExt;
+
+        let router = Router::new()
+            .route(
+                "/verify-email",
+                get(|| async { AppError::bad_request("That link has expired") }),
+            )
+            .route("/", get(|| async { "ok" }))
+            .layer(middleware::from_fn(full_page_errors));
+        let get = |uri: &str, htmx: bool| {
+            let mut request = Request::builder().uri(uri);
+            if htmx {
+                request = request.header("HX-Request", "true");
+            }
+            let request = request.body(Body::empty()).unwrap();
+            let router = router.clone();
+            async move {
+                let response = router.oneshot(request).await.unwrap();
+                let status = response.status();
+                let retargeted = response.headers().contains_key("HX-Retarget");
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (
+                    status,
+                    retargeted,
+                    String::from_utf8(body.to_vec()).unwrap(),
+                )
+            }
+        };
+
+        let (status, retargeted, body) = get("/verify-email", true).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(retargeted);
+        assert!(!body.contains("<body"));
+
+        let (status, retargeted, body) = get("/verify-email", false).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!retargeted);
+        assert!(body.contains("<body"));
+        assert!(body.contains("That link has expired"));
+
+        assert_eq!(get("/", false).await.2, "ok");
+    }
+}
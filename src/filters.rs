@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+use time::{Date, Duration};
+
+use crate::{schedule, Plant};
+
+/// Which plants to keep by their required tasks, optional tasks never count.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "due_filter", rename_all = "snake_case")]
+pub enum DueFilter {
+    #[default]
+    Any,
+    /// Due within the next week, or already.
+    ThisWeek,
+    /// Due today or overdue, the same plants as "need attention".
+    Due,
+    Overdue,
+}
+
+impl DueFilter {
+    pub const ALL: [DueFilter; 4] = [
+        DueFilter::Any,
+        DueFilter::ThisWeek,
+        DueFilter::Due,
+        DueFilter::Overdue,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DueFilter::Any => "any",
+            DueFilter::ThisWeek => "this_week",
+            DueFilter::Due => "due",
+            DueFilter::Overdue => "overdue",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DueFilter::Any => "Due any time",
+            DueFilter::ThisWeek => "Due this week",
+            DueFilter::Due => "Due now",
+            DueFilter::Overdue => "Overdue",
+        }
+    }
+
+    pub fn matches(self, plant: &Plant, today: Date) -> bool {
+        let latest = match self {
+            DueFilter::Any => return true,
+            DueFilter::ThisWeek => today + Duration::days(7),
+            DueFilter::Due => today,
+            DueFilter::Overdue => today - Duration::days(1),
+        };
+        plant
+            .tasks
+            .iter()
+            .filter(|task| !task.optional)
+            .filter_map(|task| schedule::task_next_due(task, today))
+            .any(|due| due <= latest)
+    }
+}
+
+/// A search the user named so it shows up as a button, see `MainView`.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SavedFilter {
+    pub filter_id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub search_string: String,
+    pub tags: Vec<String>,
+    pub due: DueFilter,
+}
+
+/// How a tag is stored, trimmed and lower case with hyphens for spaces, so "Pet toxic" and
+/// "pet-toxic" are the same tag.
+pub fn normalise_tag(tag: &str) -> String {
+    tag.split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
+
+/// The tags normalised and sorted, without blanks or repeats.
+pub fn tidy_tags<'a>(tags: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .into_iter()
+        .map(normalise_tag)
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// The tags in a comma separated list, as typed into a form.
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    tidy_tags(tags.split(','))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db_api, CareTask};
+    use sqlx::{Pool, Postgres};
+
+    #[test]
+    fn tags_are_spelt_one_way() {
+        assert_eq!(
+            parse_tags(" Pet toxic, succulent,,pet-toxic , PROPAGATION "),
+            ["pet-toxic", "propagation", "succulent"]
+        );
+        assert!(parse_tags(" , ").is_empty());
+    }
+
+    #[test]
+    fn due_filters_only_count_required_tasks() {
+        let today = schedule::today();
+        let task = |interval_days, days_ago, optional| CareTask {
+            task_id: 1,
+            plant_id: 1,
+            name: "Water".to_owned(),
+            interval_days: Some(interval_days),
+            last_done: Some(today - Duration::days(days_ago)),
+            optional,
+        };
+        let plant = |tasks| Plant {
+            plant_id: 1,
+            user_id: 1,
+            household_id: 1,
+            location_id: None,
+            botanical_name: String::new(),
+            common_name: String::new(),
+            tags: Vec::new(),
+            tasks,
+        };
+
+        let overdue = plant(vec![task(7, 10, false)]);
+        let due_today = plant(vec![task(7, 7, false)]);
+        let in_five_days = plant(vec![task(7, 2, false)]);
+        let optional_overdue = plant(vec![task(7, 10, true)]);
+        let untracked = plant(vec![CareTask {
+            interval_days: None,
+            last_done: None,
+            ..task(7, 0, false)
+        }]);
+        let no_tasks = plant(Vec::new());
+
+        let kept = |filter: DueFilter| {
+            [
+                &overdue,
+                &due_today,
+                &in_five_days,
+                &optional_overdue,
+                &untracked,
+                &no_tasks,
+            ]
+            .iter()
+            .map(|plant| filter.matches(plant, today))
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(kept(DueFilter::Any), [true, true, true, true, true, true]);
+        assert_eq!(
+            kept(DueFilter::ThisWeek),
+            [true, true, true, false, false, false]
+        );
+        assert_eq!(
+            kept(DueFilter::Due),
+            [true, true, false, false, false, false]
+        );
+        assert_eq!(
+            kept(DueFilter::Overdue),
+            [true, false, false, false, false, false]
+        );
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn plants_are_filtered_by_every_tag(pool: Pool<Postgres>) {
+        let f = crate::tests::fixture(pool).await;
+        let pool = &f.app.db_pool;
+        let tagged = Plant {
+            tags: parse_tags("Pet toxic, succulent"),
+            ..f.plant.clone()
+        };
+        db_api::update_plant(pool, tagged, f.owner).await.unwrap();
+        let plant = db_api::get_plant_from_id(pool, f.owner, f.plant.plant_id)
+            .await
+            .unwrap();
+        assert_eq!(plant.tags, ["pet-toxic", "succulent"]);
+
+        let search = |tags: &str| {
+            let tags = parse_tags(tags);
+            async move {
+                db_api::search_plants(pool, String::new(), &tags, f.owner)
+                    .await
+                    .unwrap()
+                    .len()
+            }
+        };
+        assert_eq!(search("").await, 1);
+        assert_eq!(search("pet-toxic").await, 1);
+        assert_eq!(search("pet-toxic, succulent").await, 1);
+        assert_eq!(search("pet-toxic, propagation").await, 0);
+
+        // saving under a name that's in use replaces that filter
+        let filter = |due| SavedFilter {
+            filter_id: -1,
+            user_id: f.owner,
+            name: "Cat".to_owned(),
+            search_string: String::new(),
+            tags: vec!["pet-toxic".to_owned()],
+            due,
+        };
+        let first = db_api::save_filter(pool, filter(DueFilter::Any))
+            .await
+            .unwrap();
+        let second = db_api::save_filter(pool, filter(DueFilter::Due))
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+        let saved = db_api::get_saved_filters(pool, f.owner).await.unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].due, DueFilter::Due);
+        assert!(db_api::get_saved_filter(pool, f.other, first)
+            .await
+            .is_err());
+        assert!(db_api::delete_saved_filter(pool, f.other, first)
+            .await
+            .is_err());
+    }
+}
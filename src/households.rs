@@ -0,0 +1,339 @@
+use anyhow::Result;
+use jsonwebtoken::{decode, encode, Header, Validation};
+use leptos::view;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use time::{Duration, OffsetDateTime};
+use utoipa::ToSchema;
+
+use crate::components::*;
+use crate::error::{AppError, AppResult};
+use crate::{config, db_api, notify, KEYS};
+
+/// How long an emailed invitation works for.
+const INVITATION_LIFETIME: Duration = Duration::days(7);
+
+/// What a member may do in a household, each role can do everything the ones before it can.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, sqlx::Type, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "household_role", rename_all = "snake_case")]
+pub enum HouseholdRole {
+    /// Can see the plants, their history, notes and photos.
+    Viewer,
+    /// Can also log care, write notes and add photos.
+    Caretaker,
+    /// Can also add and change plants, invite people and manage members.
+    Owner,
+}
+
+impl HouseholdRole {
+    pub const ALL: [HouseholdRole; 3] = [
+        HouseholdRole::Owner,
+        HouseholdRole::Caretaker,
+        HouseholdRole::Viewer,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HouseholdRole::Viewer => "viewer",
+            HouseholdRole::Caretaker => "caretaker",
+            HouseholdRole::Owner => "owner",
+        }
+    }
+}
+
+/// A shared collection of plants. Everyone starts with one of their own, and can invite others
+/// in. `role` and `expires_at` are the signed in user's membership.
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::FromRow, ToSchema)]
+pub struct Household {
+    pub household_id: i32,
+    pub name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub time_made: OffsetDateTime,
+    pub role: HouseholdRole,
+    /// When a temporary membership stops working, `None` for one that stays.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct HouseholdMember {
+    pub household_id: i32,
+    pub user_id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: String,
+    pub joined_at: OffsetDateTime,
+    pub role: HouseholdRole,
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+/// The last day a temporary membership works, as picked on a form, to when it stops.
+pub fn grant_expiry(until: Option<time::Date>) -> Option<OffsetDateTime> {
+    until.map(|day| day.next_day().unwrap_or(day).midnight().assume_utc())
+}
+
+/// The other way around, the last day a temporary membership works.
+pub fn grant_until(expires_at: Option<OffsetDateTime>) -> Option<time::Date> {
+    expires_at.map(|expires_at| (expires_at - Duration::nanoseconds(1)).date())
+}
+
+/// The token in an invitation link, signed with the JWT secret so it can't be made up. The
+/// `household_invitations` row it names is what makes it single use, and holds the address it
+/// was sent to.
+#[derive(Serialize, Deserialize, Debug)]
+struct InvitationClaims {
+    invitation_id: i32,
+    household_id: i32,
+    exp: usize,
+}
+
+/// Emails `to` a link that adds whoever is signed up with that address to the household, as
+/// `role` until `grant_expires_at` if that is set.
+pub async fn invite(
+    pool: &Pool<Postgres>,
+    invited_by: i32,
+    household_id: i32,
+    to: &str,
+    role: HouseholdRole,
+    grant_expires_at: Option<OffsetDateTime>,
+) -> AppResult<()> {
+    if !config().smtp.is_enabled() {
+        return Err(AppError::bad_request("Email isn't set up on this server"));
+    }
+    let inviter = db_api::get_user(pool, invited_by).await?;
+    let household = db_api::get_households(pool, invited_by)
+        .await?
+        .into_iter()
+        .find(|household| household.household_id == household_id)
+        .ok_or(AppError::NotFound)?;
+
+    let expires_at = OffsetDateTime::now_utc() + INVITATION_LIFETIME;
+    let invitation_id = db_api::insert_invitation(
+        pool,
+        invited_by,
+        household_id,
+        to,
+        role,
+        grant_expires_at,
+        expires_at,
+    )
+    .await?;
+    let token = sign(&InvitationClaims {
+        invitation_id,
+        household_id,
+        exp: expires_at.unix_timestamp() as usize,
+    })?;
+    let link = format!(
+        "{}/households/join?token={}",
+        config().server.public_url.trim_end_matches('/'),
+        token
+    );
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <InvitationMessage
+                inviter=inviter.first_name
+                household=household.name
+                role=role
+                link=link
+            />
+        }
+    });
+    notify::send_email(
+        &config().smtp,
+        to,
+        "You're invited to share some plants",
+        html,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Uses up the invitation, adding `user_id` to its household. Only works for the user the
+/// invitation was emailed to, a forwarded link is no use to anyone else.
+pub async fn join(pool: &Pool<Postgres>, token: &str, user_id: i32) -> AppResult<i32> {
+    let unusable = || {
+        AppError::bad_request(
+            "That invitation has expired, was already used or was sent to a different email address",
+        )
+    };
+    let claims = verify(token).ok_or_else(unusable)?;
+    db_api::accept_invitation(pool, claims.invitation_id, user_id)
+        .await?
+        .filter(|household_id| *household_id == claims.household_id)
+        .ok_or_else(unusable)
+}
+
+fn sign(claims: &InvitationClaims) -> Result<String> {
+    Ok(encode(&Header::default(), claims, &KEYS.encoding)?)
+}
+
+fn verify(token: &str) -> Option<InvitationClaims> {
+    decode::<InvitationClaims>(token, &KEYS.decoding, &Validation::default())
+        .ok()
+        .map(|data| data.claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Plant;
+
+    #[test]
+    fn invitation_tokens_cant_be_forged() {
+        crate::tests::init_config();
+        let token = sign(&InvitationClaims {
+            invitation_id: 3,
+            household_id: 5,
+            exp: (OffsetDateTime::now_utc() + INVITATION_LIFETIME).unix_timestamp() as usize,
+        })
+        .unwrap();
+        assert_eq!(verify(&token).unwrap().household_id, 5);
+        assert!(verify(&token.replace('.', ".x")).is_none());
+        // neither a session nor any other emailed link is an invitation
+        assert!(verify(&crate::auth_memes::create_token(5, 3).unwrap()).is_none());
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn members_share_plants(pool: Pool<Postgres>) {
+        let f = crate::tests::fixture(pool).await;
+        let pool = &f.app.db_pool;
+        let household_id = f.plant.household_id;
+        let expires_at = OffsetDateTime::now_utc() + INVITATION_LIFETIME;
+
+        // only members can invite, and only the invited address can join
+        assert!(db_api::insert_invitation(
+            pool,
+            f.other,
+            household_id,
+            "other@example.com",
+            HouseholdRole::Owner,
+            None,
+            expires_at
+        )
+        .await
+        .is_err());
+        let wrong_address = db_api::insert_invitation(
+            pool,
+            f.owner,
+            household_id,
+            "someone@example.com",
+            HouseholdRole::Caretaker,
+            None,
+            expires_at,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            db_api::accept_invitation(pool, wrong_address, f.other)
+                .await
+                .unwrap(),
+            None
+        );
+        assert!(db_api::get_plant_from_id(pool, f.other, f.plant.plant_id)
+            .await
+            .is_err());
+
+        let invitation_id = db_api::insert_invitation(
+            pool,
+            f.owner,
+            household_id,
+            "Other@example.com",
+            HouseholdRole::Caretaker,
+            None,
+            expires_at,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            db_api::accept_invitation(pool, invitation_id, f.other)
+                .await
+                .unwrap(),
+            Some(household_id)
+        );
+        assert_eq!(
+            db_api::accept_invitation(pool, invitation_id, f.other)
+                .await
+                .unwrap(),
+            None
+        );
+
+        // the caretaker can now look after the plant, and the history says who did
+        let task_id = f.plant.tasks[0].task_id;
+        db_api::add_care_event(pool, f.other, f.plant.plant_id, task_id, None)
+            .await
+            .unwrap();
+        let events = db_api::get_care_events(pool, f.owner, f.plant.plant_id)
+            .await
+            .unwrap();
+        assert_eq!(events[0].user_id, f.other);
+        assert_eq!(events[0].done_by, "other");
+        assert_eq!(
+            db_api::get_households(pool, f.other).await.unwrap().len(),
+            2
+        );
+
+        // but only owners add and change plants
+        let plant = Plant {
+            household_id,
+            user_id: f.other,
+            ..f.plant.clone()
+        };
+        assert!(db_api::add_plant_to_db(pool, plant.clone()).await.is_err());
+        assert!(db_api::update_plant(pool, plant, f.other).await.is_err());
+        db_api::update_member(
+            pool,
+            f.owner,
+            household_id,
+            f.other,
+            HouseholdRole::Owner,
+            None,
+        )
+        .await
+        .unwrap();
+        let plant = Plant {
+            household_id,
+            user_id: f.other,
+            ..f.plant.clone()
+        };
+        db_api::add_plant_to_db(pool, plant).await.unwrap();
+        let plants = db_api::get_all_plants(pool, f.owner, "-1".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(plants.len(), 2);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn households_keep_an_owner(pool: Pool<Postgres>) {
+        let f = crate::tests::fixture(pool).await;
+        let pool = &f.app.db_pool;
+        let household_id = f.plant.household_id;
+        let viewer = HouseholdRole::Viewer;
+        assert!(
+            !db_api::update_member(pool, f.owner, household_id, f.owner, viewer, None)
+                .await
+                .unwrap()
+        );
+        assert!(!db_api::remove_member(pool, f.owner, household_id, f.owner)
+            .await
+            .unwrap());
+        assert_eq!(
+            db_api::get_role(pool, f.owner, household_id).await.unwrap(),
+            Some(HouseholdRole::Owner)
+        );
+    }
+
+    #[test]
+    fn grants_last_through_the_day_picked() {
+        let until = time::macros::date!(2023 - 11 - 20);
+        let expires_at = grant_expiry(Some(until)).unwrap();
+        assert_eq!(expires_at, time::macros::datetime!(2023 - 11 - 21 0:00 UTC));
+        assert_eq!(grant_until(Some(expires_at)), Some(until));
+        assert_eq!(grant_expiry(None), None);
+    }
+}
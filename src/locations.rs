@@ -0,0 +1,282 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::Plant;
+
+/// What sort of place a location is.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, sqlx::Type, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "location_kind", rename_all = "snake_case")]
+pub enum LocationKind {
+    Room,
+    Windowsill,
+    Balcony,
+    Greenhouse,
+}
+
+impl LocationKind {
+    pub const ALL: [LocationKind; 4] = [
+        LocationKind::Room,
+        LocationKind::Windowsill,
+        LocationKind::Balcony,
+        LocationKind::Greenhouse,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LocationKind::Room => "room",
+            LocationKind::Windowsill => "windowsill",
+            LocationKind::Balcony => "balcony",
+            LocationKind::Greenhouse => "greenhouse",
+        }
+    }
+}
+
+/// How much light a spot gets, from a dim corner to full sun.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, sqlx::Type, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "light_level", rename_all = "snake_case")]
+pub enum LightLevel {
+    Low,
+    Medium,
+    Bright,
+    Direct,
+}
+
+impl LightLevel {
+    pub const ALL: [LightLevel; 4] = [
+        LightLevel::Low,
+        LightLevel::Medium,
+        LightLevel::Bright,
+        LightLevel::Direct,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LightLevel::Low => "low",
+            LightLevel::Medium => "medium",
+            LightLevel::Bright => "bright",
+            LightLevel::Direct => "direct",
+        }
+    }
+}
+
+/// The way a window or balcony faces.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, sqlx::Type, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "orientation", rename_all = "snake_case")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Orientation {
+    pub const ALL: [Orientation; 4] = [
+        Orientation::North,
+        Orientation::East,
+        Orientation::South,
+        Orientation::West,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Orientation::North => "north",
+            Orientation::East => "east",
+            Orientation::South => "south",
+            Orientation::West => "west",
+        }
+    }
+}
+
+/// Somewhere in a household plants can live, such as a room or a windowsill. Names are unique
+/// within a household.
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::FromRow, ToSchema)]
+pub struct Location {
+    pub location_id: i32,
+    pub household_id: i32,
+    pub name: String,
+    pub kind: LocationKind,
+    pub light_level: Option<LightLevel>,
+    pub orientation: Option<Orientation>,
+}
+
+impl Location {
+    /// The name along with what is known about the spot, e.g. "Kitchen (windowsill, bright
+    /// light, south facing)".
+    pub fn describe(&self) -> String {
+        let mut details = vec![self.kind.as_str().to_owned()];
+        details.extend(
+            self.light_level
+                .map(|light| format!("{} light", light.as_str())),
+        );
+        details.extend(
+            self.orientation
+                .map(|orientation| format!("{} facing", orientation.as_str())),
+        );
+        format!("{} ({})", self.name, details.join(", "))
+    }
+}
+
+/// Puts each plant under its location, in the order `locations` are in, leaving out locations
+/// with no plants. Plants that haven't been placed come last, under `None`.
+pub fn group_by_location(
+    plants: Vec<Plant>,
+    locations: &[Location],
+) -> Vec<(Option<Location>, Vec<Plant>)> {
+    let mut groups: Vec<(Option<Location>, Vec<Plant>)> = locations
+        .iter()
+        .map(|location| (Some(location.clone()), Vec::new()))
+        .collect();
+    let mut unplaced = Vec::new();
+    for plant in plants {
+        let group = groups.iter_mut().find(|(location, _)| {
+            location.as_ref().map(|location| location.location_id) == plant.location_id
+        });
+        match group {
+            Some((_, plants)) => plants.push(plant),
+            None => unplaced.push(plant),
+        }
+    }
+    groups.push((None, unplaced));
+    groups.retain(|(_, plants)| !plants.is_empty());
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_api;
+    use sqlx::{Pool, Postgres};
+
+    fn location(location_id: i32, name: &str) -> Location {
+        Location {
+            location_id,
+            household_id: 1,
+            name: name.to_owned(),
+            kind: LocationKind::Room,
+            light_level: None,
+            orientation: None,
+        }
+    }
+
+    fn plant(plant_id: i32, location_id: Option<i32>) -> Plant {
+        Plant {
+            plant_id,
+            user_id: 1,
+            household_id: 1,
+            location_id,
+            botanical_name: String::new(),
+            common_name: String::new(),
+            tags: Vec::new(),
+            tasks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn plants_are_grouped_room_by_room() {
+        let locations = [
+            location(1, "Kitchen"),
+            location(2, "Hall"),
+            location(3, "Bathroom"),
+        ];
+        let plants = vec![
+            plant(1, Some(3)),
+            plant(2, None),
+            plant(3, Some(1)),
+            plant(4, Some(3)),
+            // somewhere that's since been removed
+            plant(5, Some(9)),
+        ];
+        let groups = group_by_location(plants, &locations);
+        let groups: Vec<(Option<&str>, Vec<i32>)> = groups
+            .iter()
+            .map(|(location, plants)| {
+                (
+                    location.as_ref().map(|location| location.name.as_str()),
+                    plants.iter().map(|plant| plant.plant_id).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                (Some("Kitchen"), vec![3]),
+                (Some("Bathroom"), vec![1, 4]),
+                (None, vec![2, 5]),
+            ]
+        );
+    }
+
+    #[test]
+    fn locations_describe_the_spot() {
+        let mut sill = location(1, "Kitchen");
+        assert_eq!(sill.describe(), "Kitchen (room)");
+        sill.kind = LocationKind::Windowsill;
+        sill.light_level = Some(LightLevel::Bright);
+        sill.orientation = Some(Orientation::South);
+        assert_eq!(
+            sill.describe(),
+            "Kitchen (windowsill, bright light, south facing)"
+        );
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn plants_stay_in_their_own_households_locations(pool: Pool<Postgres>) {
+        let f = crate::tests::fixture(pool).await;
+        let pool = &f.app.db_pool;
+        let kitchen = Location {
+            household_id: f.plant.household_id,
+            ..location(-1, "Kitchen")
+        };
+        let kitchen = db_api::add_location(pool, f.owner, kitchen).await.unwrap();
+        let other_household = db_api::get_default_household(pool, f.other).await.unwrap();
+        let elsewhere = Location {
+            household_id: other_household,
+            ..location(-1, "Hall")
+        };
+        // only owners add locations, and names don't repeat in a household
+        assert!(db_api::add_location(pool, f.owner, elsewhere.clone())
+            .await
+            .is_err());
+        let elsewhere = db_api::add_location(pool, f.other, elsewhere)
+            .await
+            .unwrap();
+        let again = Location {
+            household_id: f.plant.household_id,
+            ..location(-1, "Kitchen")
+        };
+        assert!(db_api::add_location(pool, f.owner, again).await.is_err());
+
+        let moved = |location_id| Plant {
+            location_id: Some(location_id),
+            ..f.plant.clone()
+        };
+        assert!(db_api::update_plant(pool, moved(elsewhere), f.owner)
+            .await
+            .is_err());
+        db_api::update_plant(pool, moved(kitchen), f.owner)
+            .await
+            .unwrap();
+        let plant = db_api::get_plant_from_id(pool, f.owner, f.plant.plant_id)
+            .await
+            .unwrap();
+        assert_eq!(plant.location_id, Some(kitchen));
+
+        db_api::delete_location(pool, f.owner, f.plant.household_id, kitchen)
+            .await
+            .unwrap();
+        let plant = db_api::get_plant_from_id(pool, f.owner, f.plant.plant_id)
+            .await
+            .unwrap();
+        assert_eq!(plant.location_id, None);
+    }
+}
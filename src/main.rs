@@ -1,23 +1,24 @@
-use auth_memes::{check_password, get_jwt_cookie_for_new_user, hash_password};
+use auth_memes::{check_password, hash_password};
 use axum::{
     debug_handler,
-    extract::{Form, State},
-    http::{
-        header::{self},
-        StatusCode,
-    },
+    extract::{multipart::MultipartError, DefaultBodyLimit, Form, Multipart, Path, Query, State},
+    http::header::{self},
+    http::{HeaderMap, StatusCode},
     middleware::{self},
-    response::{Html, IntoResponse, Response},
+    response::{Html, IntoResponse, Redirect, Response},
     routing::{get, get_service, post},
     Extension, Router,
 };
 
 use anyhow::Result;
+use headers::{Cookie, HeaderMapExt};
+// supports repeated keys, which the task rows on the plant forms rely on
+use axum_extra::extract::Form as RepeatedForm;
 
 use leptos::view;
 use leptos::*;
-use mail_send::mail_builder::MessageBuilder;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 use sqlx::postgres::PgRow;
 use sqlx::{FromRow, Row};
@@ -25,70 +26,230 @@ use sqlx::{Pool, Postgres};
 use time::Instant;
 
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
 use tower_http::services::ServeDir;
 
 use jsonwebtoken::{DecodingKey, EncodingKey};
-use once_cell::sync::Lazy;
-use tokio::io::{AsyncRead, AsyncWrite};
+use once_cell::sync::{Lazy, OnceCell};
 mod components;
 use components::*;
 
+mod config;
+use config::Config;
+
 mod db_api;
 use db_api::*;
 
+mod schedule;
+
+mod reminders;
+
+mod notify;
+
+mod api;
+
+mod photos;
+use photos::{LocalStorage, PhotoStorage};
+
 mod auth_memes;
-use crate::auth_memes::{authorize, check_client, AuthPayload};
+use crate::auth_memes::{check_client, check_role, require_role, AuthError};
+
+mod error;
+use error::{full_page_errors, AppError, AppResult};
+
+mod sessions;
 
-type AppState = Arc<Mutex<App>>;
+mod accounts;
+use accounts::TokenPurpose;
+
+mod households;
+use households::{Household, HouseholdRole};
+
+mod sitters;
+
+mod locations;
+
+mod filters;
+use filters::DueFilter;
+
+/// Cloned into every request. `App` is only handles to shared, thread safe resources, so
+/// there is nothing to lock and requests run concurrently.
+type AppState = App;
 
 static N_PLANTS: i32 = 9;
 
-static KEYS: Lazy<Keys> = Lazy::new(|| {
-    let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-    Keys::new(secret.as_bytes())
-});
+static MAX_PHOTO_BYTES: usize = 10 * 1024 * 1024;
+
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+static KEYS: Lazy<Keys> = Lazy::new(|| Keys::new(config().auth.jwt_secret.as_bytes()));
+
+/// The config loaded at startup. Only valid once `main` has set it.
+pub fn config() -> &'static Config {
+    CONFIG.get().expect("config is loaded at the start of main")
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let pool = init_pool(&std::env::var("DATABASE_URL").unwrap()).await?;
+    let config = CONFIG.get_or_try_init(Config::load)?;
+
+    let pool = init_pool(&config.database.url, config.database.max_connections).await?;
+    run_migrations(&pool).await?;
+
+    // `migrate` only applies the migrations above, `seed` also loads the dev fixtures
+    match std::env::args().nth(1).as_deref() {
+        None | Some("serve") => {}
+        Some("migrate") => return Ok(()),
+        Some("seed") => return load_dev_fixtures(&pool).await,
+        Some(command) => {
+            return Err(anyhow::anyhow!(
+                "Unknown command: {}, expected serve, migrate or seed",
+                command
+            ))
+        }
+    }
+
+    if config.reminders.enabled {
+        tokio::spawn(reminders::run(pool.clone()));
+    }
+
+    let storage = LocalStorage::new(&config.server.photo_dir)?;
 
-    let app_state = Arc::new(Mutex::new(App {
+    let app_state = App {
         db_pool: pool,
+        storage: Arc::new(storage),
         //state: Vec::new(),
-    }));
+    };
 
-    let css_server = ServeDir::new("resources/css");
+    let css_server = ServeDir::new(&config.server.static_dir);
+
+    // these name a plant or household in the path, `require_role` checks what the user may do
+    // in it before the handler runs
+    let caretaker_routes = Router::new()
+        .route(
+            "/plants/:plant_id/tasks/:task_id/done",
+            post(post_task_done),
+        )
+        .route(
+            "/plants/:plant_id/photos",
+            get(get_photo_gallery)
+                .post(post_upload_photo)
+                .layer(DefaultBodyLimit::max(MAX_PHOTO_BYTES)),
+        )
+        .route(
+            "/plants/:plant_id/photos/:photo_id/delete",
+            post(post_delete_photo),
+        )
+        .route(
+            "/plants/:plant_id/comments",
+            get(get_comments_panel).post(post_add_comment),
+        )
+        .route(
+            "/plants/:plant_id/comments/:comment_id/edit",
+            get(get_edit_comment).post(post_edit_comment),
+        )
+        .route(
+            "/plants/:plant_id/comments/:comment_id/delete",
+            post(post_delete_comment),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            (app_state.clone(), HouseholdRole::Caretaker),
+            require_role,
+        ));
+
+    let owner_routes = Router::new()
+        .route(
+            "/plants/:plant_id/tasks/:task_id/delete",
+            post(post_delete_task),
+        )
+        .route("/households/:household_id/invite", post(post_invite))
+        .route(
+            "/households/:household_id/members/:member_id",
+            post(post_update_member),
+        )
+        .route(
+            "/households/:household_id/members/:member_id/remove",
+            post(post_remove_member),
+        )
+        .route(
+            "/households/:household_id/locations",
+            post(post_add_location),
+        )
+        .route(
+            "/households/:household_id/locations/:location_id/delete",
+            post(post_delete_location),
+        )
+        .route(
+            "/households/:household_id/sitter-links",
+            post(post_add_sitter_link),
+        )
+        .route(
+            "/households/:household_id/sitter-links/:link_id/revoke",
+            post(post_revoke_sitter_link),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            (app_state.clone(), HouseholdRole::Owner),
+            require_role,
+        ));
 
     let protected_routes = Router::new()
         .route("/", get(index))
         .route("/add-plant", post(post_add_plant))
         .route("/plant-view", get(get_plant_view))
+        .route("/plant-view/by-location", get(get_plants_by_location))
         .route("/add-view", get(get_add_view))
-        .route("/sort-by-feed", get(get_sorted_feed_plant_view))
-        .route("/sort-by-pot", get(get_sorted_pot_plant_view))
-        .route("/sort-by-prune", get(get_sorted_prune_plant_view))
+        .route("/sort-options", get(get_sort_options))
+        .route("/sort-by-task", get(get_sorted_task_plant_view))
         .route("/update-view", get(get_update_view))
         .route("/update-plant", post(post_update_plant))
         .route("/search-plants", post(search_plants))
+        .route("/filters", get(get_saved_filters).post(post_save_filter))
+        .route("/filters/:filter_id", get(get_run_filter))
+        .route("/filters/:filter_id/delete", post(post_delete_filter))
         .route("/get-plants-that-need-attention", get(get_plants_attn))
-        .layer(middleware::from_fn(check_client))
+        .route("/settings", get(get_settings).post(post_settings))
+        .route("/logout-everywhere", post(post_logout_everywhere))
+        .route("/verify-email/resend", post(post_resend_verification))
+        .route("/households", get(get_households_view))
+        .route("/households/join", get(get_join_household))
+        .route("/photos/:key", get(get_photo))
+        .merge(caretaker_routes)
+        .merge(owner_routes)
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            check_client,
+        ))
+        .layer(middleware::from_fn(full_page_errors))
         .with_state(app_state.clone());
 
     let unprotected_routes = Router::new()
         .route("/login", get(get_login_page))
-        .route("/auth", post(authorize))
         .route("/signup", post(signup_user))
         .route("/login-email", post(login_user))
-        .with_state(app_state);
+        .route("/logout", post(post_logout))
+        .route("/verify-email", get(get_verify_email))
+        .route(
+            "/forgot-password",
+            get(get_forgot_password).post(post_forgot_password),
+        )
+        .route(
+            "/reset-password",
+            get(get_reset_password).post(post_reset_password),
+        )
+        .route("/sit", get(get_sitter_checklist))
+        .route("/sit/done", post(post_sitter_task_done))
+        .layer(middleware::from_fn(full_page_errors))
+        .with_state(app_state.clone());
 
     let app = Router::new()
         .merge(protected_routes)
         .merge(unprotected_routes)
+        .nest("/api/v1", api::router().with_state(app_state))
+        .route("/api/openapi.json", get(api::get_openapi_json))
+        .route("/api/docs", get(api::get_api_docs))
         .nest_service("/css", get_service(css_server));
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+    let listener = tokio::net::TcpListener::bind(config.server.bind_address).await?;
     axum::serve(listener, app).await?;
 
     Ok(())
@@ -111,6 +272,7 @@ impl Keys {
 #[derive(Clone)]
 pub struct App {
     pub db_pool: Pool<Postgres>,
+    pub storage: Arc<dyn PhotoStorage>,
     //pub state: Vec<i32>, //todo - some proper state for the app as a whole
 }
 
@@ -120,28 +282,129 @@ pub struct App {
 //      plants: Rc<Vec<Plant>>, //to cache the current list of plants, rather than always hitting db
 //  }
 
-#[derive(Clone, Deserialize, Debug, sqlx::FromRow)]
+#[derive(Clone, Deserialize, Serialize, Debug, sqlx::FromRow, ToSchema)]
 pub struct User {
     pub user_id: i32,
     pub first_name: String,
     pub last_name: String,
     pub email: String,
+    /// The plain password on signup, only ever the hash afterwards. Never sent back out.
+    #[serde(skip_serializing)]
+    #[schema(write_only)]
     pub password_hash: String,
     pub phone: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// A plant in a household, everyone in it can see and look after the plant. `user_id` is who
+/// added it, `location_id` where in the household it lives if that's been set.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
 pub struct Plant {
     pub plant_id: i32,
     pub user_id: i32,
+    pub household_id: i32,
+    #[serde(default)]
+    pub location_id: Option<i32>,
+    pub botanical_name: String,
+    pub common_name: String,
+    /// Free-form labels such as "pet-toxic", see `filters::normalise_tag`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub tasks: Vec<CareTask>,
+}
+
+/// A recurring job for a plant such as watering, misting or repotting. `last_done` is derived
+/// from the latest `CareEvent` of the task and is `None` if it has never been done. A task with
+/// no `interval_days` is not tracked, it never becomes due.
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::FromRow, ToSchema)]
+pub struct CareTask {
+    pub task_id: i32,
+    pub plant_id: i32,
+    pub name: String,
+    pub interval_days: Option<i32>,
+    pub last_done: Option<time::Date>,
+    pub optional: bool, // optional tasks never flag a plant as needing attention
+}
+
+impl CareTask {
+    /// The rules for saving a task, the same for the web forms and the API.
+    pub fn validate(&self) -> AppResult<()> {
+        if self.name.trim().is_empty() {
+            return Err(AppError::bad_request("Every task needs a name"));
+        }
+        if self.interval_days.is_some_and(|days| days <= 0) {
+            return Err(AppError::bad_request(
+                "Task intervals must be at least 1 day",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Add/update form for a plant. Every task is a row of inputs sharing the same names, so the
+/// `task_*` fields line up by index. Blank interval/date inputs come through as `None`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PlantForm {
+    pub plant_id: i32,
+    pub user_id: i32,
+    pub household_id: i32,
+    pub location_id: Option<i32>,
     pub botanical_name: String,
     pub common_name: String,
-    pub last_fed: time::Date,
-    pub feed_interval: i32, // days
-    pub last_potted: time::Date,
-    pub potting_interval: i32,
-    pub last_pruned: time::Date,
-    pub pruning_interval: i32,
+    /// Comma separated.
+    #[serde(default)]
+    pub tags: String,
+    #[serde(default)]
+    pub task_id: Vec<i32>,
+    #[serde(default)]
+    pub task_name: Vec<String>,
+    #[serde(default)]
+    pub task_interval: Vec<Option<i32>>,
+    #[serde(default)]
+    pub task_last_done: Vec<Option<time::Date>>,
+    #[serde(default)]
+    pub task_optional: Vec<bool>,
+}
+
+impl TryFrom<PlantForm> for Plant {
+    type Error = AppError;
+
+    fn try_from(form: PlantForm) -> AppResult<Self> {
+        let plant_id = form.plant_id;
+        let tasks: Vec<CareTask> = form
+            .task_id
+            .into_iter()
+            .zip(form.task_name)
+            .zip(form.task_interval)
+            .zip(form.task_last_done)
+            .zip(form.task_optional)
+            .filter(|((((_, name), _), _), _)| !name.trim().is_empty())
+            .map(
+                |((((task_id, name), interval_days), last_done), optional)| CareTask {
+                    task_id,
+                    plant_id,
+                    name: name.trim().to_owned(),
+                    interval_days,
+                    last_done,
+                    optional,
+                },
+            )
+            .collect();
+        for task in &tasks {
+            task.validate()?;
+        }
+
+        Ok(Self {
+            plant_id,
+            user_id: form.user_id,
+            household_id: form.household_id,
+            location_id: form.location_id,
+            botanical_name: form.botanical_name,
+            common_name: form.common_name,
+            tags: filters::parse_tags(&form.tags),
+            tasks,
+        })
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -149,9 +412,56 @@ pub struct PlantID {
     pub plant_id: i32,
 }
 
+/// The search bar, text narrowed down by tags and by when tasks are due. Also what a saved
+/// filter is saved from, under `filter_name`.
 #[derive(Deserialize, Debug, Clone)]
 pub struct Search {
+    #[serde(default)]
     pub search_string: String,
+    /// Comma separated, a plant has to have them all.
+    #[serde(default)]
+    pub tags: String,
+    #[serde(default)]
+    pub due: DueFilter,
+    #[serde(default)]
+    pub filter_name: String,
+}
+
+/// Wraps the matched words in `CommentMatch::snippet`. Picked to be unlikely in a note, the
+/// snippet is split on them and rendered as escaped text rather than trusted as HTML.
+pub const HIGHLIGHT_START: &str = "\u{27E6}";
+pub const HIGHLIGHT_END: &str = "\u{27E7}";
+
+/// A journal note that matched a full-text search.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct CommentMatch {
+    pub comment_id: i32,
+    pub plant_id: i32,
+    pub snippet: String,
+    pub rank: f32,
+}
+
+impl CommentMatch {
+    /// The snippet as `(text, is_highlighted)` pieces.
+    pub fn snippet_parts(&self) -> Vec<(String, bool)> {
+        let mut parts = Vec::new();
+        for (i, piece) in self.snippet.split(HIGHLIGHT_START).enumerate() {
+            match piece.split_once(HIGHLIGHT_END) {
+                Some((highlighted, rest)) if i > 0 => {
+                    parts.push((highlighted.to_owned(), true));
+                    parts.push((rest.to_owned(), false));
+                }
+                _ => parts.push((piece.to_owned(), false)),
+            }
+        }
+        parts.retain(|(text, _)| !text.is_empty());
+        parts
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SortByTask {
+    pub task_name: String,
 }
 
 impl FromRow<'_, PgRow> for Plant {
@@ -159,44 +469,210 @@ impl FromRow<'_, PgRow> for Plant {
         Ok(Self {
             plant_id: row.try_get("plant_id")?,
             user_id: row.try_get("user_id")?,
+            household_id: row.try_get("household_id")?,
+            location_id: row.try_get("location_id")?,
             botanical_name: row.try_get("botanical_name")?,
             common_name: row.try_get("common_name")?,
-            last_fed: row.try_get("last_fed")?,
-            feed_interval: row.try_get("feed_interval")?,
-            last_potted: row.try_get("last_potted")?,
-            potting_interval: row.try_get("potting_interval")?,
-            last_pruned: row.try_get("last_pruned")?,
-            pruning_interval: row.try_get("pruning_interval")?,
+            tags: Vec::new(),  // filled in by db_api
+            tasks: Vec::new(), // filled in by db_api
         })
     }
 }
 
-#[derive(Deserialize, Debug, sqlx::FromRow)]
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::FromRow, ToSchema)]
 pub struct PlantPhoto {
+    pub photo_id: i32,
     pub plant_id: i32,
     pub user_id: i32,
     pub photo_uri: String,
+    pub thumbnail_uri: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub time_made: time::OffsetDateTime,
 }
 
-#[derive(Deserialize, Debug, sqlx::FromRow)]
+/// A timestamped journal note on a plant.
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::FromRow, ToSchema)]
 pub struct Comments {
+    pub comment_id: i32,
     pub plant_id: i32,
     pub user_id: i32,
+    #[serde(with = "time::serde::rfc3339")]
     pub time_made: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub time_edited: Option<time::OffsetDateTime>,
+    pub comment: String,
+}
+
+#[derive(Deserialize, Debug, Clone, ToSchema)]
+pub struct CommentForm {
     pub comment: String,
 }
 
-#[derive(Debug, Deserialize)]
+/// A single time a care task was done. Rows in this table are never updated, only appended,
+/// so the full history of a plant is kept. `user_id` is the household member who did it.
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::FromRow, ToSchema)]
+pub struct CareEvent {
+    pub event_id: i32,
+    pub task_id: i32,
+    pub task_name: String,
+    pub user_id: i32,
+    /// First name of the member who did it, or the name on the sitter link it was done through.
+    pub done_by: String,
+    pub sitter_link_id: Option<i32>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub time_made: time::OffsetDateTime,
+    pub note: Option<String>,
+}
+
+/// Channels a reminder digest can be sent through.
+pub const NOTIFICATION_CHANNELS: &[&str] = &["email", "webhook", "push"];
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "digest_frequency", rename_all = "lowercase")]
+pub enum DigestFrequency {
+    Daily,
+    Weekly,
+}
+
+/// Push notification servers the `push` channel can talk to.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "push_service", rename_all = "lowercase")]
+pub enum PushService {
+    Ntfy,
+    Gotify,
+}
+
+/// How and when a user gets reminder digests. Weekdays are ISO numbered, 1 is Monday.
+#[derive(Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct NotificationPreferences {
+    pub user_id: i32,
+    pub channels: Vec<String>,
+    pub frequency: DigestFrequency,
+    pub weekly_day: i16,
+    pub send_hour: i16,
+    pub timezone: String,
+    /// `None` includes every task.
+    pub task_names: Option<Vec<String>>,
+    pub quiet_days: Vec<i16>,
+    pub webhook_url: Option<String>,
+    pub push_service: PushService,
+    /// The topic URL for ntfy, the server URL for Gotify.
+    pub push_url: Option<String>,
+    pub push_token: Option<String>,
+}
+
+impl NotificationPreferences {
+    pub fn has_channel(&self, channel: &str) -> bool {
+        self.channels.iter().any(|c| c == channel)
+    }
+
+    /// Checks everything but the timezone, which needs the database.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(channel) = self
+            .channels
+            .iter()
+            .find(|channel| !NOTIFICATION_CHANNELS.contains(&channel.as_str()))
+        {
+            return Err(format!("Unknown channel: {}", channel));
+        }
+        if !(0..=23).contains(&self.send_hour) {
+            return Err("Send hour must be between 0 and 23".to_owned());
+        }
+        if !(1..=7).contains(&self.weekly_day)
+            || self.quiet_days.iter().any(|d| !(1..=7).contains(d))
+        {
+            return Err("Unknown day of the week".to_owned());
+        }
+        if self.frequency == DigestFrequency::Weekly && self.quiet_days.contains(&self.weekly_day) {
+            return Err("The weekly digest can't be sent on a quiet day".to_owned());
+        }
+        if self.has_channel("webhook") && !is_http_url(self.webhook_url.as_deref()) {
+            return Err("The webhook needs an http:// or https:// URL".to_owned());
+        }
+        if self.has_channel("push") && !is_http_url(self.push_url.as_deref()) {
+            return Err("Push notifications need an http:// or https:// URL".to_owned());
+        }
+        if self.has_channel("push")
+            && self.push_service == PushService::Gotify
+            && self.push_token.is_none()
+        {
+            return Err("Gotify needs an application token".to_owned());
+        }
+        Ok(())
+    }
+}
+
+fn is_http_url(url: Option<&str>) -> bool {
+    url.is_some_and(|url| url.starts_with("http://") || url.starts_with("https://"))
+}
+
+/// A user whose digest should go out now, and the day it is for in their timezone.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DueDigest {
+    #[sqlx(flatten)]
+    pub preferences: NotificationPreferences,
+    pub local_date: time::Date,
+}
+
+/// The settings page form. Unticked checkboxes aren't sent at all, hence the defaults.
+#[derive(Deserialize, Debug)]
+pub struct NotificationPreferencesForm {
+    #[serde(default)]
+    pub channels: Vec<String>,
+    pub frequency: DigestFrequency,
+    pub weekly_day: i16,
+    pub send_hour: i16,
+    pub timezone: String,
+    #[serde(default)]
+    pub task_names: Vec<String>,
+    #[serde(default)]
+    pub quiet_days: Vec<i16>,
+    pub webhook_url: Option<String>,
+    pub push_service: PushService,
+    pub push_url: Option<String>,
+    pub push_token: Option<String>,
+}
+
+impl NotificationPreferencesForm {
+    pub fn into_preferences(self, user_id: i32) -> NotificationPreferences {
+        NotificationPreferences {
+            user_id,
+            channels: self.channels,
+            frequency: self.frequency,
+            weekly_day: self.weekly_day,
+            send_hour: self.send_hour,
+            timezone: self.timezone,
+            task_names: Some(self.task_names).filter(|names| !names.is_empty()),
+            quiet_days: self.quiet_days,
+            webhook_url: non_blank(self.webhook_url),
+            push_service: self.push_service,
+            push_url: non_blank(self.push_url),
+            push_token: non_blank(self.push_token),
+        }
+    }
+}
+
+/// Empty text inputs are still sent, treat them as not filled in.
+fn non_blank(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_owned())
+        .filter(|value| !value.is_empty())
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct UserLogin {
     pub email: String,
     pub password: String,
 }
 
-async fn index(State(app): State<AppState>, Extension(user_id): Extension<i32>) -> Html<String> {
-    let pool = &app.lock().await.db_pool;
-    let plants = get_all_plants(pool, user_id, N_PLANTS.to_string())
-        .await
-        .unwrap();
+async fn index(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let plants = get_all_plants(pool, user_id, N_PLANTS.to_string()).await?;
 
     let html = leptos::ssr::render_to_string(move |cx| {
         view! { cx,
@@ -206,7 +682,7 @@ async fn index(State(app): State<AppState>, Extension(user_id): Extension<i32>)
         }
     });
 
-    Html(html)
+    Ok(Html(html))
 }
 
 async fn get_login_page(State(_app): State<AppState>) -> Html<String> {
@@ -222,31 +698,22 @@ async fn get_login_page(State(_app): State<AppState>) -> Html<String> {
 async fn login_user(
     State(app): State<AppState>,
     Form(user_login): Form<UserLogin>,
-) -> impl IntoResponse {
-    let pool = &app.lock().await.db_pool;
+) -> AppResult<Response> {
+    let pool = &app.db_pool;
 
-    //TODO - if this fails then we should return a 403 as the user hasn't signed up yet or signed
-    //up with incorrect email
+    // an unknown email gets the same answer as a wrong password, so neither gives away who
+    // has signed up
     let user = db_api::get_user_from_email(pool, user_login.email)
         .await
-        .unwrap();
+        .map_err(|_| AuthError::WrongCredentials)?;
 
     let is_correct_pw = check_password(&user_login.password, &user.password_hash);
     if !is_correct_pw {
-        return Response::builder()
-            .status(StatusCode::UNAUTHORIZED)
-            .body("Incorrect Password".to_owned())
-            .unwrap();
+        return Err(AuthError::WrongCredentials.into());
     }
 
-    let auth_payload = AuthPayload {
-        client_id: user.user_id.to_string(),
-        client_secret: user.password_hash,
-    };
-    let cookie = get_jwt_cookie_for_new_user(auth_payload);
-    let plants = db_api::get_all_plants(pool, user.user_id, 9.to_string())
-        .await
-        .unwrap_or(Vec::new());
+    let tokens = sessions::start(pool, user.user_id).await?;
+    let plants = db_api::get_all_plants(pool, user.user_id, 9.to_string()).await?;
 
     let html = leptos::ssr::render_to_string(move |cx| {
         view! { cx,
@@ -255,31 +722,31 @@ async fn login_user(
             />
         }
     });
-    let response = Response::builder()
-        .status(StatusCode::OK)
-        .header(header::SET_COOKIE, cookie)
-        .header(header::CONTENT_TYPE, "text/html")
-        .body(html)
-        .unwrap();
-    response
+    Ok((sessions::set_cookies(&tokens), Html(html)).into_response())
 }
 
-async fn signup_user(State(app): State<AppState>, Form(user): Form<User>) -> impl IntoResponse {
-    println!("{:?}", user);
+async fn signup_user(State(app): State<AppState>, Form(user): Form<User>) -> AppResult<Response> {
     let mut user = user;
-    let pool = &app.lock().await.db_pool;
-    let pw_hash = auth_memes::hash_password(user.password_hash);
-
-    user.password_hash = pw_hash.clone();
-
-    let user_id = db_api::add_user_to_db(pool, user.clone()).await.unwrap();
+    let pool = &app.db_pool;
+    let pw_hash = auth_memes::hash_password(user.password_hash)?;
+
+    user.password_hash = pw_hash;
+
+    let user_id = match db_api::add_user_to_db(pool, user.clone()).await {
+        Err(e) => match AppError::from(e) {
+            AppError::Conflict(_) => {
+                return Err(AppError::Conflict(
+                    "That email is already signed up".to_owned(),
+                ))
+            }
+            error => return Err(error),
+        },
+        Ok(user_id) => user_id,
+    };
     user.user_id = user_id;
+    send_verification_email(pool.clone(), user_id, user.email.clone());
 
-    let auth_payload = AuthPayload {
-        client_id: user_id.to_string(),
-        client_secret: pw_hash.clone(),
-    };
-    let cookie = get_jwt_cookie_for_new_user(auth_payload);
+    let tokens = sessions::start(pool, user_id).await?;
     let plants = Vec::new();
     let html = leptos::ssr::render_to_string(move |cx| {
         view! { cx,
@@ -289,39 +756,65 @@ async fn signup_user(State(app): State<AppState>, Form(user): Form<User>) -> imp
         }
     });
 
-    let response = Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "text/html")
-        .header(header::SET_COOKIE, cookie)
-        .body(html)
-        .unwrap();
-    response
+    Ok((sessions::set_cookies(&tokens), Html(html)).into_response())
+}
+
+/// Ends this device's session. The refresh cookie is what names the session, so this works
+/// with an expired access cookie too.
+async fn post_logout(State(app): State<AppState>, headers: HeaderMap) -> AppResult<Response> {
+    let cookies = headers.typed_get::<Cookie>();
+    if let Some(token) = cookies
+        .as_ref()
+        .and_then(|c| c.get(sessions::REFRESH_COOKIE))
+    {
+        sessions::end(&app.db_pool, token).await?;
+    }
+    Ok((sessions::clear_cookies(), Redirect::to("/login")).into_response())
+}
+
+/// Ends every session of the user, on every device.
+async fn post_logout_everywhere(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+) -> AppResult<Response> {
+    db_api::revoke_all_sessions(&app.db_pool, user_id).await?;
+    Ok((sessions::clear_cookies(), Redirect::to("/login")).into_response())
 }
 
 pub async fn get_add_view(
-    State(_app): State<AppState>,
+    State(app): State<AppState>,
     Extension(user_id): Extension<i32>,
-) -> Html<String> {
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let households = owned_households(pool, user_id).await?;
+    let locations = db_api::get_locations(pool, user_id).await?;
     let html = leptos::ssr::render_to_string(move |cx| {
         view! {cx,
             <AddPlantView
                 user_id=user_id
                 plant_id=None
                 text="Add Plant".into()
+                households=households
+                locations=locations
             />
         }
     });
-    Html(html)
+    Ok(Html(html))
+}
+
+/// The households the user can add plants to.
+async fn owned_households(pool: &Pool<Postgres>, user_id: i32) -> AppResult<Vec<Household>> {
+    let mut households = db_api::get_households(pool, user_id).await?;
+    households.retain(|household| household.role == HouseholdRole::Owner);
+    Ok(households)
 }
 
 pub async fn get_plant_view(
     State(app): State<AppState>,
     Extension(user_id): Extension<i32>,
-) -> Html<String> {
-    let pool = &app.lock().await.db_pool;
-    let plants = get_all_plants(pool, user_id, N_PLANTS.to_string())
-        .await
-        .unwrap_or(Vec::new());
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let plants = get_all_plants(pool, user_id, N_PLANTS.to_string()).await?;
 
     let html = leptos::ssr::render_to_string(move |cx| {
         view! {cx,
@@ -330,67 +823,62 @@ pub async fn get_plant_view(
             />
         }
     });
-    Html(html)
+    Ok(Html(html))
 }
 
-pub async fn get_sorted_feed_plant_view(
+/// Every plant, not just the first `N_PLANTS`, grouped by where it lives.
+pub async fn get_plants_by_location(
     State(app): State<AppState>,
     Extension(user_id): Extension<i32>,
-) -> Html<String> {
-    let pool = &app.lock().await.db_pool;
-    let mut plants = get_all_plants(pool, user_id, N_PLANTS.to_string())
-        .await
-        .unwrap();
-    plants.sort_by(|a, b| {
-        let next_a = a.last_fed - time::Duration::days(a.feed_interval as i64);
-        let next_b = b.last_fed - time::Duration::days(b.feed_interval as i64);
-        next_a.cmp(&next_b)
-    });
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let plants = get_all_plants(pool, user_id, "-1".to_owned()).await?;
+    let locations = db_api::get_locations(pool, user_id).await?;
+    let groups = locations::group_by_location(plants, &locations);
+
     let html = leptos::ssr::render_to_string(move |cx| {
         view! {cx,
-            <PlantView
-                plants=plants
+            <PlantsByLocation
+                groups=groups
             />
         }
     });
-    Html(html)
+    Ok(Html(html))
 }
 
-pub async fn get_sorted_pot_plant_view(
+pub async fn get_sort_options(
     State(app): State<AppState>,
     Extension(user_id): Extension<i32>,
-) -> Html<String> {
-    let pool = &app.lock().await.db_pool;
-    let mut plants = get_all_plants(pool, user_id, N_PLANTS.to_string())
-        .await
-        .unwrap();
-    plants.sort_by(|a, b| {
-        let next_a = a.last_potted - time::Duration::days(a.potting_interval as i64);
-        let next_b = b.last_potted - time::Duration::days(b.potting_interval as i64);
-        next_a.cmp(&next_b)
-    });
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let task_names = db_api::get_task_names(pool, user_id).await?;
+
     let html = leptos::ssr::render_to_string(move |cx| {
         view! {cx,
-            <PlantView
-                plants=plants
+            <SortSelect
+                task_names=task_names
             />
         }
     });
-    Html(html)
+    Ok(Html(html))
 }
 
-pub async fn get_sorted_prune_plant_view(
+pub async fn get_sorted_task_plant_view(
     State(app): State<AppState>,
     Extension(user_id): Extension<i32>,
-) -> Html<String> {
-    let pool = &app.lock().await.db_pool;
-    let mut plants = get_all_plants(pool, user_id, N_PLANTS.to_string())
-        .await
-        .unwrap();
-    plants.sort_by(|a, b| {
-        let next_a = a.last_pruned - time::Duration::days(a.pruning_interval as i64);
-        let next_b = b.last_pruned - time::Duration::days(b.pruning_interval as i64);
-        next_a.cmp(&next_b)
+    Query(sort): Query<SortByTask>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let mut plants = get_all_plants(pool, user_id, N_PLANTS.to_string()).await?;
+    // plants without the task go last
+    let today = schedule::today();
+    plants.sort_by_key(|plant| {
+        let next = plant
+            .tasks
+            .iter()
+            .find(|task| task.name == sort.task_name)
+            .and_then(|task| schedule::task_next_due(task, today));
+        (next.is_none(), next)
     });
     let html = leptos::ssr::render_to_string(move |cx| {
         view! {cx,
@@ -399,15 +887,23 @@ pub async fn get_sorted_prune_plant_view(
             />
         }
     });
-    Html(html)
+    Ok(Html(html))
 }
 
 #[debug_handler]
 pub async fn post_add_plant(
     State(app): State<AppState>,
-    Form(mut plant): Form<Plant>,
-) -> Html<String> {
-    let pool = &app.lock().await.db_pool;
+    Extension(user_id): Extension<i32>,
+    RepeatedForm(form): RepeatedForm<PlantForm>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    // the form carries the user id, it has to be the signed in user's own
+    if form.user_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+    let role = db_api::get_role(pool, user_id, form.household_id).await?;
+    check_role(role, HouseholdRole::Owner)?;
+    let mut plant = Plant::try_from(form)?;
 
     let html;
 
@@ -425,6 +921,8 @@ pub async fn post_add_plant(
             });
         }
         Err(e) => {
+            let households = owned_households(pool, user_id).await?;
+            let locations = db_api::get_locations(pool, user_id).await?;
             html = leptos::ssr::render_to_string(move |cx| {
                 view! {cx,
                     <PlantAddFailure
@@ -434,49 +932,58 @@ pub async fn post_add_plant(
                        user_id = plant.user_id
                        plant_id=None
                        text="Add Plant".into()
+                       households=households
+                       locations=locations
                     />
                 }
             });
         }
     }
 
-    Html(html)
+    Ok(Html(html))
 }
 
 pub async fn get_update_view(
     State(app): State<AppState>,
     Extension(user_id): Extension<i32>,
     Form(plant_id): Form<PlantID>,
-) -> Html<String> {
-    let pool = &app.lock().await.db_pool;
-    //let user_id = user_id.unwrap_or(Extension(1)).0;
-    let plant = db_api::get_plant_from_id(pool, user_id, plant_id.plant_id)
-        .await
-        .unwrap();
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let plant = db_api::get_plant_from_id(pool, user_id, plant_id.plant_id).await?;
+    let events = db_api::get_care_events(pool, user_id, plant_id.plant_id).await?;
+    let locations = db_api::get_locations(pool, user_id).await?;
 
     let html = leptos::ssr::render_to_string(move |cx| {
         view! { cx,
             <UpdateView
                 plant=plant
                 user_id=user_id
+                locations=locations
+            />
+            <CareHistory
+                events=events
             />
 
         }
     });
 
-    Html(html)
+    Ok(Html(html))
 }
 
 pub async fn post_update_plant(
     State(app): State<AppState>,
     Extension(user_id): Extension<i32>,
-    Form(plant): Form<Plant>,
-) -> Html<String> {
-    let pool = &app.lock().await.db_pool;
-    db_api::update_plant(pool, plant).await.unwrap();
-    let plants = get_all_plants(pool, user_id, N_PLANTS.to_string())
-        .await
-        .unwrap();
+    RepeatedForm(form): RepeatedForm<PlantForm>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    if form.user_id != user_id {
+        return Err(AppError::Forbidden);
+    }
+    // the plant is named in the form rather than the path, so `require_role` can't check it
+    let role = db_api::get_plant_role(pool, user_id, form.plant_id).await?;
+    check_role(role, HouseholdRole::Owner)?;
+    db_api::update_plant(pool, Plant::try_from(form)?, user_id).await?;
+    let plants = get_all_plants(pool, user_id, N_PLANTS.to_string()).await?;
 
     let html = leptos::ssr::render_to_string(move |cx| {
         view! { cx,
@@ -485,125 +992,1625 @@ pub async fn post_update_plant(
             />
         }
     });
-    Html(html)
+    Ok(Html(html))
 }
 
-pub async fn search_plants(
+/// Stamps today as the latest run of the task and re-renders the single plant card so htmx can
+/// swap it in place.
+pub async fn post_task_done(
     State(app): State<AppState>,
     Extension(user_id): Extension<i32>,
-    Form(search): Form<Search>,
-) -> Html<String> {
-    let pool = &app.lock().await.db_pool;
+    Path((plant_id, task_id)): Path<(i32, i32)>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    db_api::add_care_event(pool, user_id, plant_id, task_id, None).await?;
+    let plant = db_api::get_plant_from_id(pool, user_id, plant_id).await?;
 
-    let plants = db_api::search_plants(pool, search.search_string, user_id)
-        .await
-        .unwrap();
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <PlantItem
+                plant=plant
+            />
+        }
+    });
+    Ok(Html(html))
+}
+
+/// Deletes the task, returning nothing so htmx removes its row from the update form.
+pub async fn post_delete_task(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path((plant_id, task_id)): Path<(i32, i32)>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    db_api::delete_care_task(pool, user_id, plant_id, task_id).await?;
+    Ok(Html(String::new()))
+}
+
+pub async fn get_comments_panel(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path(plant_id): Path<i32>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    render_comments_panel(pool, user_id, plant_id).await
+}
+
+pub async fn post_add_comment(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path(plant_id): Path<i32>,
+    Form(form): Form<CommentForm>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    if !form.comment.trim().is_empty() {
+        db_api::add_comment(pool, user_id, plant_id, form.comment).await?;
+    }
+    render_comments_panel(pool, user_id, plant_id).await
+}
+
+/// Swaps a single comment for a form to edit it.
+pub async fn get_edit_comment(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path((plant_id, comment_id)): Path<(i32, i32)>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let comment = db_api::get_comment(pool, user_id, plant_id, comment_id).await?;
 
     let html = leptos::ssr::render_to_string(move |cx| {
         view! { cx,
-            <PlantView
-                plants=plants
+            <CommentEditForm
+                comment=comment
             />
         }
     });
-    Html(html)
+    Ok(Html(html))
 }
 
-pub async fn get_plants_attn(
+pub async fn post_edit_comment(
     State(app): State<AppState>,
-    user_id: Option<Extension<i32>>,
-) -> Html<String> {
-    let pool = &app.lock().await.db_pool;
-    let user_id = user_id.unwrap_or(Extension(1)).0;
-    let plants = db_api::get_plants_that_need_attention(pool, user_id)
-        .await
-        .expect("Error getting plants");
+    Extension(user_id): Extension<i32>,
+    Path((plant_id, comment_id)): Path<(i32, i32)>,
+    Form(form): Form<CommentForm>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    db_api::update_comment(pool, user_id, plant_id, comment_id, form.comment).await?;
+    render_comments_panel(pool, user_id, plant_id).await
+}
+
+pub async fn post_delete_comment(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path((plant_id, comment_id)): Path<(i32, i32)>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    db_api::delete_comment(pool, user_id, plant_id, comment_id).await?;
+    render_comments_panel(pool, user_id, plant_id).await
+}
+
+async fn render_comments_panel(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+) -> AppResult<Html<String>> {
+    let comments = db_api::get_comments(pool, user_id, plant_id).await?;
+
     let html = leptos::ssr::render_to_string(move |cx| {
         view! { cx,
-            <PlantView
-                plants=plants
+            <CommentsPanel
+                plant_id=plant_id
+                comments=comments
             />
         }
     });
-    Html(html)
+    Ok(Html(html))
 }
 
-pub async fn notify_users_of_required_actions(State(app): State<AppState>) {
-    let pool = &app.lock().await.db_pool;
-    //TODO - loop through users instead of hardcoding a single user
+pub async fn get_photo_gallery(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path(plant_id): Path<i32>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    render_photo_gallery(pool, user_id, plant_id, None).await
+}
 
-    let plants = db_api::get_plants_that_need_attention(pool, 1)
-        .await
-        .expect("Error getting plants");
-    if plants.is_empty() {
-        return; //TODO - change to continue in a loop
-    }
+pub async fn post_upload_photo(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path(plant_id): Path<i32>,
+    multipart: Multipart,
+) -> AppResult<Html<String>> {
+    // someone else's plant is a 404 rather than an upload error shown in their gallery
+    db_api::get_plant_from_id(&app.db_pool, user_id, plant_id).await?;
+
+    let result = match read_photo_upload(multipart).await {
+        Ok(bytes) => save_photo(&app.db_pool, app.storage.as_ref(), user_id, plant_id, bytes).await,
+        Err(e) => Err(e),
+    };
 
-    let email = db_api::get_user_email(pool, 1)
-        .await
-        .expect("Couldn't find user email!");
+    // a bad upload is shown in the gallery, anything else is ours and goes to the banner
+    let error = match result {
+        Ok(_) => None,
+        Err(AppError::BadRequest(message) | AppError::TooLarge(message)) => Some(message),
+        Err(e) => return Err(e),
+    };
+    render_photo_gallery(&app.db_pool, user_id, plant_id, error).await
+}
+
+/// The bytes of the `photo` field of an upload form. A body over `MAX_PHOTO_BYTES` is
+/// `TooLarge` and any other broken upload a `BadRequest`, rather than looking like no photo.
+async fn read_photo_upload(mut multipart: Multipart) -> AppResult<Vec<u8>> {
+    let mut upload = None;
+    while let Some(field) = multipart.next_field().await.map_err(upload_error)? {
+        if field.name() == Some("photo") {
+            upload = Some(field.bytes().await.map_err(upload_error)?);
+        }
+    }
+    upload
+        .map(|bytes| bytes.to_vec())
+        .ok_or_else(|| AppError::bad_request("No photo was uploaded"))
+}
 
-    let mut client = mail_send::SmtpClientBuilder::new("smtp-mail.outlook.com", 587)
-        .implicit_tls(false)
-        .credentials((
-            "Thane_Patrol@outlook.com",
-            std::env::var("EMAIL_PASSWORD").unwrap().as_str(),
+fn upload_error(error: MultipartError) -> AppError {
+    if error.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        AppError::TooLarge(format!(
+            "Photos can be up to {} MB",
+            MAX_PHOTO_BYTES / 1024 / 1024
         ))
-        .connect()
-        .await
-        .expect("Error connecting to client");
+    } else {
+        AppError::bad_request(error.body_text())
+    }
+}
 
-    send_email(email, plants, &mut client).await;
+/// Stores the original upload and its thumbnail, then records both against the plant. Only an
+/// upload that isn't a usable image is a `BadRequest`.
+async fn save_photo(
+    pool: &Pool<Postgres>,
+    storage: &dyn PhotoStorage,
+    user_id: i32,
+    plant_id: i32,
+    bytes: Vec<u8>,
+) -> AppResult<i32> {
+    // fails if the plant isn't theirs, before anything is written to storage
+    db_api::get_plant_from_id(pool, user_id, plant_id).await?;
+
+    let processed = tokio::task::spawn_blocking(move || photos::process_upload(bytes))
+        .await
+        .map_err(|e| AppError::Internal(e.into()))??;
+    let base = format!(
+        "{}-{}",
+        plant_id,
+        time::OffsetDateTime::now_utc().unix_timestamp_nanos()
+    );
+    let key = format!("{}.{}", base, processed.extension);
+    let thumbnail_key = format!("{}-thumb.jpg", base);
+    storage.save(&key, processed.original).await?;
+    storage.save(&thumbnail_key, processed.thumbnail).await?;
+
+    Ok(db_api::add_photo(
+        pool,
+        user_id,
+        plant_id,
+        format!("/photos/{}", key),
+        Some(format!("/photos/{}", thumbnail_key)),
+    )
+    .await?)
 }
 
-fn get_days_till_next_feed(plant: &Plant) -> (String, String, String) {
-    fn do_sub(days: i32) -> i64 {
-        let cur_date = time::OffsetDateTime::now_utc();
+pub async fn post_delete_photo(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path((plant_id, photo_id)): Path<(i32, i32)>,
+) -> AppResult<Html<String>> {
+    remove_photo(&app, user_id, plant_id, photo_id).await?;
+    render_photo_gallery(&app.db_pool, user_id, plant_id, None).await
+}
 
-        let next_date = cur_date + time::Duration::days(days as i64);
+/// Deletes the photo's files from storage and then the photo. If storage fails the photo is
+/// kept, so deleting it again can finish the job rather than its files being left behind.
+async fn remove_photo(app: &AppState, user_id: i32, plant_id: i32, photo_id: i32) -> AppResult<()> {
+    let pool = &app.db_pool;
+    // the same check `db_api::delete_photo` makes, before anything is gone from storage
+    check_role(
+        db_api::get_plant_role(pool, user_id, plant_id).await?,
+        HouseholdRole::Caretaker,
+    )?;
+    let photo = db_api::get_photos(pool, user_id, plant_id)
+        .await?
+        .into_iter()
+        .find(|photo| photo.photo_id == photo_id)
+        .ok_or(AppError::NotFound)?;
+
+    // external photos have nothing in storage
+    for uri in Some(photo.photo_uri).into_iter().chain(photo.thumbnail_uri) {
+        if let Some(key) = uri.strip_prefix("/photos/") {
+            match app.storage.delete(key).await {
+                Ok(()) => {}
+                // gone already, such as by an earlier attempt that failed part way
+                Err(e)
+                    if e.downcast_ref::<std::io::Error>()
+                        .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) => {}
+                Err(e) => {
+                    return Err(AppError::Internal(
+                        e.context(format!("Couldn't delete {} from photo storage", key)),
+                    ))
+                }
+            }
+        }
+    }
+    db_api::delete_photo(pool, user_id, plant_id, photo_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(())
+}
 
-        (next_date - cur_date).whole_days()
+/// Serves an uploaded photo or thumbnail out of storage, only to the plant's owner.
+pub async fn get_photo(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path(key): Path<String>,
+) -> AppResult<Response> {
+    let uri = format!("/photos/{}", key);
+    if !db_api::user_has_photo(&app.db_pool, user_id, &uri).await? {
+        return Err(AppError::NotFound);
     }
-    let feed_int = do_sub(plant.feed_interval);
-    let pot_int = do_sub(plant.potting_interval);
-    let prune_int = do_sub(plant.pruning_interval);
-    let sentinel = String::from("Overdue");
 
-    (
-        match feed_int.cmp(&0) {
-            std::cmp::Ordering::Greater => feed_int.to_string(),
-            _ => sentinel.clone(),
-        },
-        match pot_int.cmp(&0) {
-            std::cmp::Ordering::Greater => pot_int.to_string(),
-            _ => sentinel.clone(),
-        },
-        match prune_int.cmp(&0) {
-            std::cmp::Ordering::Greater => prune_int.to_string(),
-            _ => sentinel.clone(),
-        },
+    let bytes = app
+        .storage
+        .load(&key)
+        .await
+        .map_err(|_| AppError::NotFound)?;
+    Ok((
+        [(header::CONTENT_TYPE, photos::content_type_for(&key))],
+        bytes,
     )
+        .into_response())
 }
 
-async fn send_email<T: AsyncRead + AsyncWrite + Unpin>(
-    email: String,
-    plants: Vec<Plant>,
-    mail: &mut mail_send::SmtpClient<T>,
-) {
+async fn render_photo_gallery(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    plant_id: i32,
+    error: Option<String>,
+) -> AppResult<Html<String>> {
+    let photos = db_api::get_photos(pool, user_id, plant_id).await?;
+
     let html = leptos::ssr::render_to_string(move |cx| {
-        view! {cx,
-            <EmailItem
+        view! { cx,
+            <PhotoGallery
+                plant_id=plant_id
+                photos=photos
+                error=error
+            />
+        }
+    });
+    Ok(Html(html))
+}
+
+pub async fn search_plants(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Form(search): Form<Search>,
+) -> AppResult<Html<String>> {
+    let tags = filters::parse_tags(&search.tags);
+    render_search(
+        &app.db_pool,
+        user_id,
+        search.search_string,
+        &tags,
+        search.due,
+    )
+    .await
+}
+
+async fn render_search(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    search_string: String,
+    tags: &[String],
+    due: DueFilter,
+) -> AppResult<Html<String>> {
+    let mut plants = db_api::search_plants(pool, search_string.clone(), tags, user_id).await?;
+    let today = schedule::today();
+    plants.retain(|plant| due.matches(plant, today));
+    let matches = db_api::search_comments(pool, search_string, user_id).await?;
+
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <SearchResults
                 plants=plants
+                matches=matches
             />
         }
     });
-    let message = MessageBuilder::new()
-        .from(("Thane Patrol", "thane_patrol@outlook.com"))
-        .to(vec![("1", email.as_str())])
-        .subject("Plants in need of your care!")
-        .html_body(html);
+    Ok(Html(html))
+}
 
-    mail.send(message)
-        .await
-        .unwrap_or_else(|_| panic!("Error sending email to: {}", email));
+pub async fn get_saved_filters(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+) -> AppResult<Html<String>> {
+    render_saved_filters(&app.db_pool, user_id).await
+}
+
+/// Saves what is in the search bar under the name given, replacing a filter of the same name.
+pub async fn post_save_filter(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Form(search): Form<Search>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let name = search.filter_name.trim();
+    if name.is_empty() {
+        return Err(AppError::bad_request("Give the filter a name"));
+    }
+    let filter = filters::SavedFilter {
+        filter_id: -1,
+        user_id,
+        name: name.to_owned(),
+        search_string: search.search_string.trim().to_owned(),
+        tags: filters::parse_tags(&search.tags),
+        due: search.due,
+    };
+    db_api::save_filter(pool, filter).await?;
+    render_saved_filters(pool, user_id).await
+}
+
+/// Runs a saved filter, showing the plants like a search does.
+pub async fn get_run_filter(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path(filter_id): Path<i32>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let filter = db_api::get_saved_filter(pool, user_id, filter_id).await?;
+    render_search(
+        pool,
+        user_id,
+        filter.search_string,
+        &filter.tags,
+        filter.due,
+    )
+    .await
+}
+
+pub async fn post_delete_filter(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path(filter_id): Path<i32>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    db_api::delete_saved_filter(pool, user_id, filter_id).await?;
+    render_saved_filters(pool, user_id).await
+}
+
+async fn render_saved_filters(pool: &Pool<Postgres>, user_id: i32) -> AppResult<Html<String>> {
+    let filters = db_api::get_saved_filters(pool, user_id).await?;
+    let tags = db_api::get_tags(pool, user_id).await?;
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <SavedFilters
+                filters=filters
+                tags=tags
+            />
+        }
+    });
+    Ok(Html(html))
+}
+
+pub async fn get_plants_attn(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let plants =
+        db_api::get_plants_that_need_attention(pool, user_id, None, schedule::today()).await?;
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <PlantView
+                plants=plants
+            />
+        }
+    });
+    Ok(Html(html))
+}
+
+pub async fn get_settings(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    render_settings(pool, user_id, None).await
+}
+
+pub async fn post_settings(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    RepeatedForm(form): RepeatedForm<NotificationPreferencesForm>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let preferences = form.into_preferences(user_id);
+
+    let message = match preferences.validate() {
+        Err(error) => error,
+        Ok(()) if !db_api::is_known_timezone(pool, &preferences.timezone).await? => {
+            format!("Unknown timezone: {}", preferences.timezone)
+        }
+        Ok(()) => {
+            db_api::update_notification_preferences(pool, preferences).await?;
+            "Settings saved".to_owned()
+        }
+    };
+    render_settings(pool, user_id, Some(message)).await
+}
+
+async fn render_settings(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    message: Option<String>,
+) -> AppResult<Html<String>> {
+    let preferences = db_api::get_notification_preferences(pool, user_id).await?;
+    let task_names = db_api::get_task_names(pool, user_id).await?;
+    let timezones = db_api::get_timezone_names(pool).await?;
+    let email_verified = db_api::get_verified_email(pool, user_id).await?.is_some();
+
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <NotificationSettings
+                preferences=preferences
+                task_names=task_names
+                timezones=timezones
+                message=message
+                email_verified=email_verified
+            />
+        }
+    });
+    Ok(Html(html))
+}
+
+/// Sends in the background, the request doesn't wait on the mail server. Does nothing while
+/// email isn't set up, such users simply never get email reminders.
+fn send_verification_email(pool: Pool<Postgres>, user_id: i32, email: String) {
+    if !config().smtp.is_enabled() {
+        return;
+    }
+    tokio::spawn(async move {
+        if let Err(e) = accounts::send_verification(&pool, user_id, &email).await {
+            eprintln!(
+                "Couldn't send a verification email to user {}: {:#}",
+                user_id, e
+            );
+        }
+    });
+}
+
+pub async fn post_resend_verification(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let message = if !config().smtp.is_enabled() {
+        "Email isn't set up on this server"
+    } else if db_api::get_verified_email(pool, user_id).await?.is_some() {
+        "Your email address is already verified"
+    } else {
+        let user = db_api::get_user(pool, user_id).await?;
+        send_verification_email(pool.clone(), user_id, user.email);
+        "We've sent the link again, check your inbox"
+    };
+    render_settings(pool, user_id, Some(message.to_owned())).await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TokenQuery {
+    pub token: String,
+}
+
+/// Where the link in the verification email lands.
+async fn get_verify_email(
+    State(app): State<AppState>,
+    Query(query): Query<TokenQuery>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let message = match accounts::redeem(pool, &query.token, TokenPurpose::VerifyEmail).await {
+        Ok(user_id) => {
+            db_api::mark_email_verified(pool, user_id).await?;
+            "Thanks, your email address is verified. Care reminders can now be emailed to it."
+                .to_owned()
+        }
+        Err(AppError::BadRequest(message)) => message,
+        Err(error) => return Err(error),
+    };
+
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <AccountPage title="Verify your email">
+                <p>{message}</p>
+                <a href="/">"Back to your plants"</a>
+            </AccountPage>
+        }
+    });
+    Ok(Html(html))
+}
+
+async fn get_forgot_password() -> Html<String> {
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <AccountPage title="Forgot your password?">
+                <ForgotPasswordForm/>
+            </AccountPage>
+        }
+    });
+    Html(html)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EmailForm {
+    pub email: String,
+}
+
+/// Answers the same whether or not the email is signed up, and sends in the background so
+/// the response time doesn't tell either.
+async fn post_forgot_password(
+    State(app): State<AppState>,
+    Form(form): Form<EmailForm>,
+) -> AppResult<Html<String>> {
+    if !config().smtp.is_enabled() {
+        return Err(AppError::bad_request(
+            "Email isn't set up on this server, ask whoever runs it to reset your password",
+        ));
+    }
+    let pool = app.db_pool.clone();
+    let email = form.email.trim().to_owned();
+    let message = format!(
+        "If {} is signed up, a link to reset its password is on its way.",
+        email
+    );
+    tokio::spawn(async move {
+        let Ok(user) = db_api::get_user_from_email(&pool, email).await else {
+            return;
+        };
+        if let Err(e) = accounts::send_password_reset(&pool, user.user_id, &user.email).await {
+            eprintln!(
+                "Couldn't send a password reset email to user {}: {:#}",
+                user.user_id, e
+            );
+        }
+    });
+
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <p>{message}</p>
+        }
+    });
+    Ok(Html(html))
+}
+
+/// Where the link in the password reset email lands.
+async fn get_reset_password(Query(query): Query<TokenQuery>) -> Html<String> {
+    let token = query.token;
+    let usable = accounts::is_plausible(&token, TokenPurpose::ResetPassword);
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <AccountPage title="Choose a new password">
+                {if usable {
+                    view! { cx, <ResetPasswordForm token=token/> }.into_view(cx)
+                } else {
+                    view! { cx,
+                        <p>"That link has expired or was already used."</p>
+                        <a href="/forgot-password">"Send a new one"</a>
+                    }
+                    .into_view(cx)
+                }}
+            </AccountPage>
+        }
+    });
+    Html(html)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NewPasswordForm {
+    pub token: String,
+    pub password: String,
+}
+
+/// Changes the password and signs the user out everywhere, whoever knew the old one included.
+async fn post_reset_password(
+    State(app): State<AppState>,
+    Form(form): Form<NewPasswordForm>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    if form.password.is_empty() {
+        return Err(AppError::bad_request("The new password can't be empty"));
+    }
+    let user_id = accounts::redeem(pool, &form.token, TokenPurpose::ResetPassword).await?;
+    let password_hash = hash_password(form.password)?;
+    db_api::update_password(pool, user_id, password_hash).await?;
+    db_api::revoke_all_sessions(pool, user_id).await?;
+    // only the address's owner could have followed the link
+    db_api::mark_email_verified(pool, user_id).await?;
+
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <h1>"Password changed"</h1>
+            <p>"You've been signed out everywhere. Log in with your new password."</p>
+            <LoginView/>
+        }
+    });
+    Ok(Html(html))
+}
+
+pub async fn get_households_view(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+) -> AppResult<Html<String>> {
+    render_households(&app.db_pool, user_id, None).await
+}
+
+/// The role to give someone in a household, `until` the last day it works for a temporary one.
+/// A blank date comes through as `None`.
+#[derive(Deserialize, Debug)]
+pub struct MemberForm {
+    #[serde(default)]
+    pub email: String,
+    pub role: HouseholdRole,
+    pub until: Option<time::Date>,
+}
+
+impl MemberForm {
+    fn grant_expiry(&self) -> AppResult<Option<time::OffsetDateTime>> {
+        if self.role == HouseholdRole::Owner && self.until.is_some() {
+            return Err(AppError::bad_request("Owners can't be temporary"));
+        }
+        Ok(households::grant_expiry(self.until))
+    }
+}
+
+/// Emails the invitation. Anyone can be invited, they join once they've signed up with the
+/// address it was sent to.
+pub async fn post_invite(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path(household_id): Path<i32>,
+    RepeatedForm(form): RepeatedForm<MemberForm>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let email = form.email.trim().to_owned();
+    if !email.contains('@') {
+        return Err(AppError::bad_request("That isn't an email address"));
+    }
+    let grant_expires_at = form.grant_expiry()?;
+    households::invite(
+        pool,
+        user_id,
+        household_id,
+        &email,
+        form.role,
+        grant_expires_at,
+    )
+    .await?;
+    let message = format!("Invitation sent to {}", email);
+    render_households(pool, user_id, Some(message)).await
+}
+
+pub async fn post_update_member(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path((household_id, member_id)): Path<(i32, i32)>,
+    RepeatedForm(form): RepeatedForm<MemberForm>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let expires_at = form.grant_expiry()?;
+    if !db_api::update_member(
+        pool,
+        user_id,
+        household_id,
+        member_id,
+        form.role,
+        expires_at,
+    )
+    .await?
+    {
+        return Err(AppError::bad_request(NEEDS_AN_OWNER));
+    }
+    render_households(pool, user_id, Some("Member updated".to_owned())).await
+}
+
+pub async fn post_remove_member(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path((household_id, member_id)): Path<(i32, i32)>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    if !db_api::remove_member(pool, user_id, household_id, member_id).await? {
+        return Err(AppError::bad_request(NEEDS_AN_OWNER));
+    }
+    render_households(pool, user_id, Some("Member removed".to_owned())).await
+}
+
+/// A new location, a blank light level or orientation comes through as `None`.
+#[derive(Deserialize, Debug)]
+pub struct LocationForm {
+    pub name: String,
+    pub kind: locations::LocationKind,
+    pub light_level: Option<locations::LightLevel>,
+    pub orientation: Option<locations::Orientation>,
+}
+
+pub async fn post_add_location(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path(household_id): Path<i32>,
+    RepeatedForm(form): RepeatedForm<LocationForm>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(AppError::bad_request("Give the location a name"));
+    }
+    let location = locations::Location {
+        location_id: -1,
+        household_id,
+        name: name.to_owned(),
+        kind: form.kind,
+        light_level: form.light_level,
+        orientation: form.orientation,
+    };
+    if let Err(error) = db_api::add_location(pool, user_id, location).await {
+        return Err(match AppError::from(error) {
+            AppError::Conflict(_) => {
+                AppError::Conflict("The household already has a location by that name".to_owned())
+            }
+            error => error,
+        });
+    }
+    render_households(pool, user_id, Some(format!("Added {}", name))).await
+}
+
+pub async fn post_delete_location(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path((household_id, location_id)): Path<(i32, i32)>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    db_api::delete_location(pool, user_id, household_id, location_id).await?;
+    render_households(pool, user_id, Some("Location removed".to_owned())).await
+}
+
+/// Who a plant-sitter link is for and the days it works, both included.
+#[derive(Deserialize, Debug)]
+pub struct SitterLinkForm {
+    pub sitter_name: String,
+    pub from: time::Date,
+    pub until: time::Date,
+}
+
+pub async fn post_add_sitter_link(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path(household_id): Path<i32>,
+    Form(form): Form<SitterLinkForm>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let sitter_name = form.sitter_name.trim();
+    if sitter_name.is_empty() {
+        return Err(AppError::bad_request("Give the sitter's name"));
+    }
+    if form.until < form.from || form.until < schedule::today() {
+        return Err(AppError::bad_request(
+            "The last day has to be on or after the first, and not in the past",
+        ));
+    }
+    let starts_at = form.from.midnight().assume_utc();
+    let expires_at = households::grant_expiry(Some(form.until)).unwrap_or(starts_at);
+    let link = db_api::insert_sitter_link(
+        pool,
+        user_id,
+        household_id,
+        sitter_name,
+        starts_at,
+        expires_at,
+    )
+    .await?;
+    let message = format!("Link made for {}, copy it from the list", link.sitter_name);
+    render_households(pool, user_id, Some(message)).await
+}
+
+pub async fn post_revoke_sitter_link(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Path((household_id, link_id)): Path<(i32, i32)>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    db_api::revoke_sitter_link(pool, user_id, household_id, link_id).await?;
+    render_households(pool, user_id, Some("Link revoked".to_owned())).await
+}
+
+/// Where a plant-sitter link lands. No login, the token is the sitter's only way in.
+async fn get_sitter_checklist(
+    State(app): State<AppState>,
+    Query(query): Query<TokenQuery>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let link = match sitters::open(pool, &query.token).await {
+        Ok(link) => Ok(link),
+        Err(AppError::BadRequest(message)) => Err(message),
+        Err(error) => return Err(error),
+    };
+    let plants = match &link {
+        Ok(link) => sitter_plants(pool, link).await?,
+        Err(_) => Vec::new(),
+    };
+
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <AccountPage title="Plant-sitting">
+                {match link {
+                    Ok(link) => view! { cx,
+                        <SitterChecklist token=query.token link=link plants=plants message=None/>
+                    }
+                    .into_view(cx),
+                    Err(message) => view! { cx, <p>{message}</p> }.into_view(cx),
+                }}
+            </AccountPage>
+        }
+    });
+    Ok(Html(html))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SitterTaskForm {
+    pub token: String,
+    pub plant_id: i32,
+    pub task_id: i32,
+}
+
+/// Ticks a task off through a sitter link, the link is checked again as it may have been revoked
+/// since the page was loaded.
+async fn post_sitter_task_done(
+    State(app): State<AppState>,
+    Form(form): Form<SitterTaskForm>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let link = sitters::open(pool, &form.token).await?;
+    db_api::add_sitter_care_event(pool, link.link_id, form.plant_id, form.task_id).await?;
+    let plants = sitter_plants(pool, &link).await?;
+    let message = Some("Thanks, noted".to_owned());
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <SitterChecklist token=form.token link=link plants=plants message=message/>
+        }
+    });
+    Ok(Html(html))
+}
+
+async fn sitter_plants(pool: &Pool<Postgres>, link: &sitters::SitterLink) -> AppResult<Vec<Plant>> {
+    let plants = db_api::get_household_plants(pool, link.household_id).await?;
+    Ok(sitters::checklist(plants, link.last_day()))
+}
+
+const NEEDS_AN_OWNER: &str = "A household needs at least one owner who isn't temporary";
+
+async fn render_households(
+    pool: &Pool<Postgres>,
+    user_id: i32,
+    message: Option<String>,
+) -> AppResult<Html<String>> {
+    let households = db_api::get_households(pool, user_id).await?;
+    let members = db_api::get_household_members(pool, user_id).await?;
+    let locations = db_api::get_locations(pool, user_id).await?;
+    let sitter_links = db_api::get_sitter_links(pool, user_id)
+        .await?
+        .into_iter()
+        .map(|link| {
+            let url = link.url()?;
+            Ok((link, url))
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <HouseholdsView
+                households=households
+                members=members
+                locations=locations
+                sitter_links=sitter_links
+                message=message
+            />
+        }
+    });
+    Ok(Html(html))
+}
+
+/// Where the link in an invitation lands. Behind the login, so the invitee signs up or logs in
+/// first and then follows the link again.
+async fn get_join_household(
+    State(app): State<AppState>,
+    Extension(user_id): Extension<i32>,
+    Query(query): Query<TokenQuery>,
+) -> AppResult<Html<String>> {
+    let pool = &app.db_pool;
+    let message = match households::join(pool, &query.token, user_id).await {
+        Ok(household_id) => {
+            let name = db_api::get_households(pool, user_id)
+                .await?
+                .into_iter()
+                .find(|household| household.household_id == household_id)
+                .map(|household| household.name)
+                .unwrap_or_default();
+            format!("You've joined {}, its plants are now with yours.", name)
+        }
+        Err(AppError::BadRequest(message)) => message,
+        Err(error) => return Err(error),
+    };
+
+    let html = leptos::ssr::render_to_string(move |cx| {
+        view! { cx,
+            <AccountPage title="Join a household">
+                <p>{message}</p>
+                <a href="/">"Back to your plants"</a>
+            </AccountPage>
+        }
+    });
+    Ok(Html(html))
+}
+
+/// These need a Postgres server at `DATABASE_URL`, each test gets its own freshly migrated
+/// database. Run them with `cargo test -- --ignored`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two signed up users in households of their own, the first's holding a plant with one task.
+    pub(crate) struct Fixture {
+        pub app: App,
+        pub owner: i32,
+        pub other: i32,
+        pub plant: Plant,
+    }
+
+    /// The defaults, plus the secret everything signed needs.
+    pub(crate) fn init_config() {
+        CONFIG.get_or_init(|| {
+            let mut config = Config::default();
+            config.auth.jwt_secret = "test secret".to_owned();
+            config
+        });
+    }
+
+    pub(crate) async fn fixture(pool: Pool<Postgres>) -> Fixture {
+        init_config();
+        let mut users = Vec::new();
+        for name in ["owner", "other"] {
+            let user = User {
+                user_id: 0,
+                first_name: name.to_owned(),
+                last_name: "Test".to_owned(),
+                email: format!("{}@example.com", name),
+                password_hash: "not a real hash".to_owned(),
+                phone: None,
+            };
+            users.push(db_api::add_user_to_db(&pool, user).await.unwrap());
+        }
+        let plant = Plant {
+            plant_id: 0,
+            user_id: users[0],
+            household_id: db_api::get_default_household(&pool, users[0])
+                .await
+                .unwrap(),
+            location_id: None,
+            botanical_name: "Ficus lyrata".to_owned(),
+            common_name: "Fiddle leaf".to_owned(),
+            tags: Vec::new(),
+            tasks: vec![CareTask {
+                task_id: -1,
+                plant_id: 0,
+                name: "Water".to_owned(),
+                interval_days: Some(7),
+                last_done: None,
+                optional: false,
+            }],
+        };
+        let plant_id = db_api::add_plant_to_db(&pool, plant).await.unwrap();
+        let plant = db_api::get_plant_from_id(&pool, users[0], plant_id)
+            .await
+            .unwrap();
+        let storage =
+            LocalStorage::new(std::env::temp_dir().join("plant-track-test-photos")).unwrap();
+        Fixture {
+            app: App {
+                db_pool: pool,
+                storage: Arc::new(storage),
+            },
+            owner: users[0],
+            other: users[1],
+            plant,
+        }
+    }
+
+    fn form_for(plant: &Plant, user_id: i32) -> PlantForm {
+        PlantForm {
+            plant_id: plant.plant_id,
+            user_id,
+            household_id: plant.household_id,
+            location_id: plant.location_id,
+            botanical_name: "Hijacked".to_owned(),
+            common_name: "Hijacked".to_owned(),
+            tags: String::new(),
+            task_id: vec![],
+            task_name: vec![],
+            task_interval: vec![],
+            task_last_done: vec![],
+            task_optional: vec![],
+        }
+    }
+
+    #[test]
+    fn plant_forms_reject_intervals_under_a_day() {
+        let plant = Plant {
+            plant_id: 1,
+            user_id: 1,
+            household_id: 1,
+            location_id: None,
+            botanical_name: String::new(),
+            common_name: String::new(),
+            tags: Vec::new(),
+            tasks: Vec::new(),
+        };
+        let with_interval = |interval| PlantForm {
+            task_id: vec![-1],
+            task_name: vec!["Water".to_owned()],
+            task_interval: vec![interval],
+            task_last_done: vec![None],
+            task_optional: vec![false],
+            ..form_for(&plant, 1)
+        };
+        for interval in [Some(0), Some(-3)] {
+            assert!(matches!(
+                Plant::try_from(with_interval(interval)),
+                Err(AppError::BadRequest(_))
+            ));
+        }
+        for interval in [Some(1), None] {
+            assert!(Plant::try_from(with_interval(interval)).is_ok());
+        }
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn journal_notes_are_only_changed_by_their_author(pool: Pool<Postgres>) {
+        let f = fixture(pool).await;
+        let pool = &f.app.db_pool;
+        let plant_id = f.plant.plant_id;
+        // the other user looks after the plant too, so only authorship stops them
+        let expires_at = time::OffsetDateTime::now_utc() + time::Duration::days(1);
+        let invitation_id = db_api::insert_invitation(
+            pool,
+            f.owner,
+            f.plant.household_id,
+            "other@example.com",
+            HouseholdRole::Caretaker,
+            None,
+            expires_at,
+        )
+        .await
+        .unwrap();
+        db_api::accept_invitation(pool, invitation_id, f.other)
+            .await
+            .unwrap();
+
+        let add = |user_id: i32, comment: &str| {
+            post_add_comment(
+                State(f.app.clone()),
+                Extension(user_id),
+                Path(plant_id),
+                Form(CommentForm {
+                    comment: comment.to_owned(),
+                }),
+            )
+        };
+        let Html(panel) = add(f.owner, "Repotted into terracotta").await.unwrap();
+        assert!(panel.contains("Repotted into terracotta"));
+        add(f.owner, "   ").await.unwrap();
+        add(f.other, "Watered while you were away").await.unwrap();
+
+        let comments = db_api::get_comments(pool, f.owner, plant_id).await.unwrap();
+        let notes: Vec<&str> = comments.iter().map(|c| c.comment.as_str()).collect();
+        assert_eq!(
+            notes,
+            ["Watered while you were away", "Repotted into terracotta"]
+        );
+        let (theirs, mine) = (comments[0].comment_id, comments[1].comment_id);
+
+        let edit = |user_id: i32, comment_id: i32| {
+            post_edit_comment(
+                State(f.app.clone()),
+                Extension(user_id),
+                Path((plant_id, comment_id)),
+                Form(CommentForm {
+                    comment: "Repotted into a glazed pot".to_owned(),
+                }),
+            )
+        };
+        assert!(matches!(edit(f.other, mine).await, Err(AppError::NotFound)));
+        let Html(panel) = edit(f.owner, mine).await.unwrap();
+        assert!(panel.contains("Repotted into a glazed pot"));
+        let edited = db_api::get_comment(pool, f.owner, plant_id, mine)
+            .await
+            .unwrap();
+        assert_eq!(edited.comment, "Repotted into a glazed pot");
+        assert!(edited.time_edited.is_some());
+        let untouched = db_api::get_comment(pool, f.owner, plant_id, theirs)
+            .await
+            .unwrap();
+        assert_eq!(untouched.time_edited, None);
+
+        let delete = |user_id: i32, comment_id: i32| {
+            post_delete_comment(
+                State(f.app.clone()),
+                Extension(user_id),
+                Path((plant_id, comment_id)),
+            )
+        };
+        assert!(matches!(
+            delete(f.owner, theirs).await,
+            Err(AppError::NotFound)
+        ));
+        assert!(matches!(
+            delete(f.other, mine).await,
+            Err(AppError::NotFound)
+        ));
+        delete(f.owner, mine).await.unwrap();
+        let comments = db_api::get_comments(pool, f.other, plant_id).await.unwrap();
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].comment_id, theirs);
+        assert!(matches!(
+            delete(f.owner, mine).await,
+            Err(AppError::NotFound)
+        ));
+    }
+
+    /// Storage that can't delete anything.
+    struct StuckStorage;
+
+    #[axum::async_trait]
+    impl PhotoStorage for StuckStorage {
+        async fn save(&self, _key: &str, _bytes: Vec<u8>) -> Result<()> {
+            Ok(())
+        }
+
+        async fn load(&self, key: &str) -> Result<Vec<u8>> {
+            Err(anyhow::anyhow!("{} isn't here", key))
+        }
+
+        async fn delete(&self, _key: &str) -> Result<()> {
+            Err(anyhow::anyhow!("read-only file system"))
+        }
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn photos_outlive_storage_failures(pool: Pool<Postgres>) {
+        let f = fixture(pool).await;
+        let plant_id = f.plant.plant_id;
+        let photo_id = db_api::add_photo(
+            &f.app.db_pool,
+            f.owner,
+            plant_id,
+            "/photos/never-saved.jpg".to_owned(),
+            Some("/photos/never-saved-thumb.jpg".to_owned()),
+        )
+        .await
+        .unwrap();
+        let photos = || db_api::get_photos(&f.app.db_pool, f.owner, plant_id);
+
+        let stuck = App {
+            storage: Arc::new(StuckStorage),
+            ..f.app.clone()
+        };
+        assert!(matches!(
+            remove_photo(&stuck, f.owner, plant_id, photo_id).await,
+            Err(AppError::Internal(_))
+        ));
+        assert_eq!(photos().await.unwrap().len(), 1);
+
+        // the files never made it to this storage, which is as good as deleted
+        remove_photo(&f.app, f.owner, plant_id, photo_id)
+            .await
+            .unwrap();
+        assert!(photos().await.unwrap().is_empty());
+        assert!(matches!(
+            remove_photo(&f.app, f.owner, plant_id, photo_id).await,
+            Err(AppError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn broken_uploads_say_what_went_wrong() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let router = Router::new()
+            .route(
+                "/upload",
+                post(|multipart: Multipart| async move {
+                    read_photo_upload(multipart)
+                        .await
+                        .map(|bytes| String::from_utf8(bytes).unwrap())
+                }),
+            )
+            .layer(DefaultBodyLimit::max(256));
+        let upload = |body: String| {
+            let request = axum::http::Request::post("/upload")
+                .header("content-type", "multipart/form-data; boundary=X")
+                .body(Body::from(body))
+                .unwrap();
+            router.clone().oneshot(request)
+        };
+        let form = |name: &str, contents: &str| {
+            format!(
+                "--X\r\ncontent-disposition: form-data; name=\"{}\"; filename=\"a.jpg\"\r\n\r\n{}\r\n--X--\r\n",
+                name, contents
+            )
+        };
+
+        let response = upload(form("photo", "fake jpeg")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = upload(form("photo", &"x".repeat(1024))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let response = upload(form("caption", "no photo")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        // cut off before the closing boundary
+        let response =
+            upload("--X\r\ncontent-disposition: form-data; name=\"photo\"\r\n\r\nfake".to_owned())
+                .await
+                .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    fn snippet_parts(snippet: &str) -> Vec<(String, bool)> {
+        let snippet = snippet
+            .replace('[', HIGHLIGHT_START)
+            .replace(']', HIGHLIGHT_END);
+        CommentMatch {
+            comment_id: 1,
+            plant_id: 1,
+            snippet,
+            rank: 1.0,
+        }
+        .snippet_parts()
+    }
+
+    fn part(text: &str, highlighted: bool) -> (String, bool) {
+        (text.to_owned(), highlighted)
+    }
+
+    #[test]
+    fn snippets_split_on_highlights() {
+        assert_eq!(
+            snippet_parts("repotted after the move"),
+            [part("repotted after the move", false)]
+        );
+        assert!(snippet_parts("").is_empty());
+        assert_eq!(
+            snippet_parts("[Repotted] into a bigger [pot] today"),
+            [
+                part("Repotted", true),
+                part(" into a bigger ", false),
+                part("pot", true),
+                part(" today", false),
+            ]
+        );
+        assert_eq!(
+            snippet_parts("new leaf, [repotted]"),
+            [part("new leaf, ", false), part("repotted", true)]
+        );
+        assert_eq!(
+            snippet_parts("[fed][repotted]"),
+            [part("fed", true), part("repotted", true)]
+        );
+    }
+
+    #[test]
+    fn unterminated_highlights_are_plain_text() {
+        // ts_headline can cut a snippet off part way through a match
+        assert_eq!(
+            snippet_parts("fed and [repotted after"),
+            [part("fed and ", false), part("repotted after", false)]
+        );
+        assert_eq!(
+            snippet_parts("[fed] and [repotted"),
+            [
+                part("fed", true),
+                part(" and ", false),
+                part("repotted", false)
+            ]
+        );
+    }
+
+    fn preferences_form() -> NotificationPreferencesForm {
+        NotificationPreferencesForm {
+            channels: vec!["email".to_owned()],
+            frequency: DigestFrequency::Daily,
+            weekly_day: 1,
+            send_hour: 8,
+            timezone: "Europe/London".to_owned(),
+            task_names: Vec::new(),
+            quiet_days: Vec::new(),
+            webhook_url: None,
+            push_service: PushService::Ntfy,
+            push_url: None,
+            push_token: None,
+        }
+    }
+
+    #[test]
+    fn notification_preferences_are_validated() {
+        let check = |form: NotificationPreferencesForm| form.into_preferences(1).validate();
+        assert_eq!(check(preferences_form()), Ok(()));
+
+        let unknown_channel = NotificationPreferencesForm {
+            channels: vec!["email".to_owned(), "pigeon".to_owned()],
+            ..preferences_form()
+        };
+        assert_eq!(
+            check(unknown_channel),
+            Err("Unknown channel: pigeon".to_owned())
+        );
+
+        for send_hour in [-1, 24] {
+            let form = NotificationPreferencesForm {
+                send_hour,
+                ..preferences_form()
+            };
+            assert!(check(form).is_err());
+        }
+        let midnight = NotificationPreferencesForm {
+            send_hour: 0,
+            ..preferences_form()
+        };
+        assert_eq!(check(midnight), Ok(()));
+
+        let weekly_on_a_quiet_day = NotificationPreferencesForm {
+            frequency: DigestFrequency::Weekly,
+            weekly_day: 6,
+            quiet_days: vec![6, 7],
+            ..preferences_form()
+        };
+        assert!(check(weekly_on_a_quiet_day).is_err());
+        // the weekly day only matters for weekly digests
+        let daily_with_quiet_days = NotificationPreferencesForm {
+            weekly_day: 6,
+            quiet_days: vec![6, 7],
+            ..preferences_form()
+        };
+        assert_eq!(check(daily_with_quiet_days), Ok(()));
+    }
+
+    #[test]
+    fn notification_channels_need_somewhere_to_send() {
+        let check = |form: NotificationPreferencesForm| form.into_preferences(1).validate();
+        for channel in ["webhook", "push"] {
+            // blank inputs are sent as empty strings, which count as missing
+            for url in [
+                None,
+                Some("  ".to_owned()),
+                Some("example.com/hook".to_owned()),
+            ] {
+                let form = NotificationPreferencesForm {
+                    channels: vec![channel.to_owned()],
+                    webhook_url: url.clone(),
+                    push_url: url,
+                    ..preferences_form()
+                };
+                assert!(check(form).is_err(), "{} accepted without a URL", channel);
+            }
+            let form = NotificationPreferencesForm {
+                channels: vec![channel.to_owned()],
+                webhook_url: Some("https://example.com/hook".to_owned()),
+                push_url: Some("https://ntfy.sh/plants".to_owned()),
+                ..preferences_form()
+            };
+            assert_eq!(check(form), Ok(()));
+        }
+
+        let gotify_without_token = NotificationPreferencesForm {
+            channels: vec!["push".to_owned()],
+            push_service: PushService::Gotify,
+            push_url: Some("https://gotify.example.com".to_owned()),
+            push_token: Some(String::new()),
+            ..preferences_form()
+        };
+        assert!(check(gotify_without_token).is_err());
+    }
+
+    #[test]
+    fn preference_forms_drop_blanks() {
+        let preferences = NotificationPreferencesForm {
+            webhook_url: Some(" https://example.com/hook ".to_owned()),
+            push_url: Some(String::new()),
+            ..preferences_form()
+        }
+        .into_preferences(7);
+        assert_eq!(preferences.user_id, 7);
+        assert_eq!(preferences.task_names, None);
+        assert_eq!(
+            preferences.webhook_url.as_deref(),
+            Some("https://example.com/hook")
+        );
+        assert_eq!(preferences.push_url, None);
+
+        let some_tasks = NotificationPreferencesForm {
+            task_names: vec!["Water".to_owned()],
+            ..preferences_form()
+        }
+        .into_preferences(7);
+        assert_eq!(some_tasks.task_names, Some(vec!["Water".to_owned()]));
+    }
+
+    #[sqlx::test(migrations = false)]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn legacy_sentinel_schedules_become_untracked(pool: Pool<Postgres>) {
+        use schedule::DueStatus;
+        use sqlx::Executor;
+
+        // a database made with the old resources/script.sql, whose add form filled in
+        // 3000-11-11 and 100000 for anything left blank
+        pool.execute(include_str!(
+            "../migrations/20231001000000_initial_schema.sql"
+        ))
+        .await
+        .unwrap();
+        pool.execute(
+            r#"
+            INSERT INTO users (first_name, last_name, email)
+            VALUES ('Alice', 'Johnson', 'alice.johnson@example.com');
+            INSERT INTO plants (user_id, botanical_name, common_name, last_fed, feed_interval,
+                last_potted, potting_interval, last_pruned, pruning_interval)
+            VALUES (1, 'Ficus lyrata', 'Fiddle leaf', '3000-11-11', 100000,
+                '2023-01-01', 180, '3000-11-11', 60);
+            "#,
+        )
+        .await
+        .unwrap();
+        db_api::run_migrations(&pool).await.unwrap();
+
+        let plant = db_api::get_plant_from_id(&pool, 1, 1).await.unwrap();
+        let task = |name: &str| plant.tasks.iter().find(|task| task.name == name).unwrap();
+        let today = schedule::today();
+        assert_eq!(task("Fertilise").interval_days, None);
+        assert_eq!(task("Fertilise").last_done, None);
+        assert_eq!(schedule::task_due_status(task("Fertilise"), today), None);
+        assert_eq!(schedule::task_next_due(task("Fertilise"), today), None);
+        // a real schedule that was never done is due, not done in the year 3000
+        assert_eq!(task("Prune").last_done, None);
+        assert_eq!(
+            schedule::task_due_status(task("Prune"), today),
+            Some(DueStatus::DueToday)
+        );
+        assert_eq!(
+            task("Repot").last_done,
+            Some(time::macros::date!(2023 - 01 - 01))
+        );
+        assert_eq!(task("Repot").interval_days, Some(180));
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn forms_cannot_claim_another_user(pool: Pool<Postgres>) {
+        let f = fixture(pool).await;
+        let result = post_add_plant(
+            State(f.app.clone()),
+            Extension(f.other),
+            RepeatedForm(form_for(&f.plant, f.owner)),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::Forbidden)));
+
+        let result = post_update_plant(
+            State(f.app.clone()),
+            Extension(f.other),
+            RepeatedForm(form_for(&f.plant, f.owner)),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::Forbidden)));
+
+        let plants = db_api::get_all_plants(&f.app.db_pool, f.owner, N_PLANTS.to_string())
+            .await
+            .unwrap();
+        assert_eq!(plants.len(), 1);
+        assert_eq!(plants[0].common_name, f.plant.common_name);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn plants_cannot_take_another_plants_task(pool: Pool<Postgres>) {
+        let f = fixture(pool).await;
+        let pool = &f.app.db_pool;
+        let other_plant_id = db_api::add_plant_to_db(pool, f.plant.clone())
+            .await
+            .unwrap();
+        let mut other_plant = db_api::get_plant_from_id(pool, f.owner, other_plant_id)
+            .await
+            .unwrap();
+        other_plant.tasks[0].task_id = f.plant.tasks[0].task_id;
+        other_plant.tasks[0].name = "Hijacked".to_owned();
+
+        let result = db_api::update_plant(pool, other_plant, f.owner).await;
+        assert!(matches!(
+            result.map_err(AppError::from),
+            Err(AppError::NotFound)
+        ));
+        let plant = db_api::get_plant_from_id(pool, f.owner, f.plant.plant_id)
+            .await
+            .unwrap();
+        assert_eq!(plant.tasks[0].name, "Water");
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn forms_cannot_touch_another_users_plant(pool: Pool<Postgres>) {
+        let f = fixture(pool).await;
+        let plant_id = f.plant.plant_id;
+        let task_id = f.plant.tasks[0].task_id;
+        let comment_id = db_api::add_comment(&f.app.db_pool, f.owner, plant_id, "Mine".into())
+            .await
+            .unwrap();
+
+        // the form names the other user, so this gets past the form check to the database
+        let result = post_update_plant(
+            State(f.app.clone()),
+            Extension(f.other),
+            RepeatedForm(form_for(&f.plant, f.other)),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::NotFound)));
+
+        let result = post_task_done(
+            State(f.app.clone()),
+            Extension(f.other),
+            Path((plant_id, task_id)),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::NotFound)));
+
+        let result = post_delete_task(
+            State(f.app.clone()),
+            Extension(f.other),
+            Path((plant_id, task_id)),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::NotFound)));
+
+        let result = post_edit_comment(
+            State(f.app.clone()),
+            Extension(f.other),
+            Path((plant_id, comment_id)),
+            Form(CommentForm {
+                comment: "Hijacked".to_owned(),
+            }),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::NotFound)));
+
+        let result = post_delete_comment(
+            State(f.app.clone()),
+            Extension(f.other),
+            Path((plant_id, comment_id)),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::NotFound)));
+
+        let plant = db_api::get_plant_from_id(&f.app.db_pool, f.owner, plant_id)
+            .await
+            .unwrap();
+        assert_eq!(plant.common_name, f.plant.common_name);
+        assert_eq!(plant.tasks.len(), 1);
+        let comment = db_api::get_comment(&f.app.db_pool, f.owner, plant_id, comment_id)
+            .await
+            .unwrap();
+        assert_eq!(comment.comment, "Mine");
+    }
 }
@@ -0,0 +1,532 @@
+use anyhow::{anyhow, Result};
+use axum::async_trait;
+use leptos::view;
+use leptos::*;
+use mail_send::mail_builder::MessageBuilder;
+use serde::Serialize;
+use time::Date;
+
+use crate::components::*;
+use crate::config::SmtpConfig;
+use crate::schedule::{self, DueStatus};
+use crate::{NotificationPreferences, Plant, PushService};
+
+const DIGEST_TITLE: &str = "Plants in need of your care!";
+
+/// How long a webhook or push server gets to answer. Digests go out one user at a time, so a
+/// server that never answers mustn't hold up everyone after them.
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// The client webhook and push notifiers share, see `REQUEST_TIMEOUT`.
+pub fn http_client() -> reqwest::Client {
+    client_with_timeout(REQUEST_TIMEOUT)
+}
+
+fn client_with_timeout(timeout: std::time::Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .connect_timeout(CONNECT_TIMEOUT.min(timeout))
+        .build()
+        .expect("the HTTP client's TLS backend should initialise")
+}
+
+/// One user's reminder: the plants that need attention on `date`, their local day.
+#[derive(Debug, Clone)]
+pub struct Digest {
+    pub user_id: i32,
+    pub date: Date,
+    pub plants: Vec<Plant>,
+}
+
+/// Somewhere a digest can be delivered. Each is built for a single user out of their
+/// notification preferences, so it already knows where to send.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// The channel name from `NOTIFICATION_CHANNELS`, for logging.
+    fn channel(&self) -> &'static str;
+    async fn send(&self, digest: &Digest) -> Result<()>;
+}
+
+/// A notifier for every channel the user has turned on. Email is left out while `smtp` isn't
+/// configured, or without an `email` the user has verified.
+pub fn notifiers_for(
+    preferences: &NotificationPreferences,
+    email: Option<String>,
+    smtp: &SmtpConfig,
+    client: &reqwest::Client,
+) -> Vec<Box<dyn Notifier>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+    if let Some(email) = email.filter(|_| preferences.has_channel("email") && smtp.is_enabled()) {
+        notifiers.push(Box::new(EmailNotifier {
+            smtp: smtp.clone(),
+            to: email,
+        }));
+    }
+    // validation makes sure the URLs are there whenever their channel is on
+    if let Some(url) = preferences
+        .webhook_url
+        .as_ref()
+        .filter(|_| preferences.has_channel("webhook"))
+    {
+        notifiers.push(Box::new(WebhookNotifier {
+            client: client.clone(),
+            url: url.clone(),
+        }));
+    }
+    if let Some(url) = preferences
+        .push_url
+        .as_ref()
+        .filter(|_| preferences.has_channel("push"))
+    {
+        notifiers.push(Box::new(PushNotifier {
+            client: client.clone(),
+            service: preferences.push_service,
+            url: url.clone(),
+            token: preferences.push_token.clone(),
+        }));
+    }
+    notifiers
+}
+
+/// Sends the digest as an HTML email, opening a new SMTP connection each time.
+pub struct EmailNotifier {
+    pub smtp: SmtpConfig,
+    pub to: String,
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn channel(&self) -> &'static str {
+        "email"
+    }
+
+    async fn send(&self, digest: &Digest) -> Result<()> {
+        let plants = digest.plants.clone();
+        let html = leptos::ssr::render_to_string(move |cx| {
+            view! {cx,
+                <EmailItem
+                    plants=plants
+                />
+            }
+        });
+        send_email(&self.smtp, &self.to, DIGEST_TITLE, html).await
+    }
+}
+
+/// Sends a single HTML email, connecting to the server just for it.
+pub async fn send_email(smtp: &SmtpConfig, to: &str, subject: &str, html: String) -> Result<()> {
+    let message = MessageBuilder::new()
+        .from((smtp.sender_name.as_str(), smtp.sender_email.as_str()))
+        .to(to)
+        .subject(subject)
+        .html_body(html);
+
+    let mut builder = mail_send::SmtpClientBuilder::new(smtp.host.as_str(), smtp.port)
+        .implicit_tls(smtp.implicit_tls);
+    if !smtp.username.is_empty() {
+        builder = builder.credentials((smtp.username.as_str(), smtp.password.as_str()));
+    }
+    builder.connect().await?.send(message).await?;
+    Ok(())
+}
+
+/// POSTs the digest as JSON to a URL of the user's choosing, see `WebhookPayload`.
+pub struct WebhookNotifier {
+    pub client: reqwest::Client,
+    pub url: String,
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn channel(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn send(&self, digest: &Digest) -> Result<()> {
+        self.client
+            .post(&self.url)
+            .json(&WebhookPayload::new(digest))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// A plain text push notification through an ntfy topic or a Gotify server.
+pub struct PushNotifier {
+    pub client: reqwest::Client,
+    pub service: PushService,
+    /// The topic URL for ntfy, the server URL for Gotify.
+    pub url: String,
+    pub token: Option<String>,
+}
+
+#[async_trait]
+impl Notifier for PushNotifier {
+    fn channel(&self) -> &'static str {
+        "push"
+    }
+
+    async fn send(&self, digest: &Digest) -> Result<()> {
+        let message = push_message(digest);
+        let request = match self.service {
+            PushService::Ntfy => {
+                let request = self
+                    .client
+                    .post(&self.url)
+                    .header("Title", DIGEST_TITLE)
+                    .header("Tags", "seedling")
+                    .body(message);
+                match &self.token {
+                    Some(token) => request.bearer_auth(token),
+                    None => request,
+                }
+            }
+            PushService::Gotify => {
+                let token = self
+                    .token
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("Gotify needs an application token"))?;
+                self.client
+                    .post(format!("{}/message", self.url.trim_end_matches('/')))
+                    .header("X-Gotify-Key", token)
+                    .json(&serde_json::json!({
+                        "title": DIGEST_TITLE,
+                        "message": message,
+                        "priority": 5,
+                    }))
+            }
+        };
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// The JSON body of a webhook digest.
+#[derive(Serialize, Debug)]
+pub struct WebhookPayload {
+    pub user_id: i32,
+    pub date: String,
+    pub plants: Vec<WebhookPlant>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WebhookPlant {
+    pub plant_id: i32,
+    pub common_name: String,
+    pub botanical_name: String,
+    pub due_tasks: Vec<WebhookTask>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WebhookTask {
+    pub task_id: i32,
+    pub name: String,
+    pub next_due: String,
+    /// 0 when due today.
+    pub days_overdue: i64,
+}
+
+impl WebhookPayload {
+    pub fn new(digest: &Digest) -> Self {
+        let plants = digest
+            .plants
+            .iter()
+            .map(|plant| WebhookPlant {
+                plant_id: plant.plant_id,
+                common_name: plant.common_name.clone(),
+                botanical_name: plant.botanical_name.clone(),
+                due_tasks: plant
+                    .tasks
+                    .iter()
+                    .filter_map(|task| {
+                        let next_due = schedule::task_next_due(task, digest.date)?;
+                        let days_overdue = match schedule::task_due_status(task, digest.date)? {
+                            DueStatus::Upcoming(_) => return None,
+                            DueStatus::DueToday => 0,
+                            DueStatus::Overdue(days) => days,
+                        };
+                        Some(WebhookTask {
+                            task_id: task.task_id,
+                            name: task.name.clone(),
+                            next_due: next_due.to_string(),
+                            days_overdue,
+                        })
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self {
+            user_id: digest.user_id,
+            date: digest.date.to_string(),
+            plants,
+        }
+    }
+}
+
+/// One line per plant naming its due tasks, short enough to read on a lock screen.
+pub fn push_message(digest: &Digest) -> String {
+    WebhookPayload::new(digest)
+        .plants
+        .into_iter()
+        .map(|plant| {
+            let tasks = plant
+                .due_tasks
+                .into_iter()
+                .map(|task| task.name)
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{}: {}", plant.common_name, tasks)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CareTask, DigestFrequency};
+    use time::macros::date;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn digest() -> Digest {
+        let task = |task_id, name: &str, interval_days, last_done| CareTask {
+            task_id,
+            plant_id: 1,
+            name: name.to_owned(),
+            interval_days: Some(interval_days),
+            last_done: Some(last_done),
+            optional: false,
+        };
+        Digest {
+            user_id: 7,
+            date: date!(2023 - 10 - 10),
+            plants: vec![Plant {
+                plant_id: 1,
+                user_id: 7,
+                household_id: 4,
+                location_id: None,
+                botanical_name: "Monstera deliciosa".to_owned(),
+                common_name: "Swiss cheese plant".to_owned(),
+                tags: Vec::new(),
+                tasks: vec![
+                    task(1, "Water", 7, date!(2023 - 10 - 01)),
+                    task(2, "Mist", 2, date!(2023 - 10 - 08)),
+                    task(3, "Repot", 365, date!(2023 - 06 - 01)),
+                ],
+            }],
+        }
+    }
+
+    /// Accepts a single HTTP request, answers it with `status` and returns the raw request.
+    async fn stand_in_server(status: u16) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or(0);
+                    if body.len() >= length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 {} Stand-in\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                status
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (url, handle)
+    }
+
+    /// Accepts connections and never answers them, like a hung webhook.
+    async fn silent_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+        url
+    }
+
+    fn preferences(channels: &[&str]) -> NotificationPreferences {
+        NotificationPreferences {
+            user_id: 7,
+            channels: channels.iter().map(|c| c.to_string()).collect(),
+            frequency: DigestFrequency::Daily,
+            weekly_day: 1,
+            send_hour: 8,
+            timezone: "UTC".to_owned(),
+            task_names: None,
+            quiet_days: Vec::new(),
+            webhook_url: Some("http://localhost/hook".to_owned()),
+            push_service: PushService::Ntfy,
+            push_url: Some("http://localhost/plants".to_owned()),
+            push_token: None,
+        }
+    }
+
+    #[test]
+    fn payload_only_lists_due_tasks() {
+        let payload = WebhookPayload::new(&digest());
+        let tasks = &payload.plants[0].due_tasks;
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].name, "Water");
+        assert_eq!(tasks[0].next_due, "2023-10-08");
+        assert_eq!(tasks[0].days_overdue, 2);
+        assert_eq!(tasks[1].name, "Mist");
+        assert_eq!(tasks[1].days_overdue, 0);
+    }
+
+    #[test]
+    fn push_message_is_a_line_per_plant() {
+        assert_eq!(push_message(&digest()), "Swiss cheese plant: Water, Mist");
+    }
+
+    fn channels_for(preferences: &NotificationPreferences, smtp: &SmtpConfig) -> Vec<&'static str> {
+        notifiers_for(
+            preferences,
+            Some("a@b.c".to_owned()),
+            smtp,
+            &reqwest::Client::new(),
+        )
+        .iter()
+        .map(|notifier| notifier.channel())
+        .collect()
+    }
+
+    #[test]
+    fn notifiers_follow_channels() {
+        let smtp = SmtpConfig {
+            host: "smtp.example.com".to_owned(),
+            ..SmtpConfig::default()
+        };
+        let all = preferences(&["email", "webhook", "push"]);
+        assert_eq!(channels_for(&all, &smtp), ["email", "webhook", "push"]);
+        assert_eq!(channels_for(&preferences(&["push"]), &smtp), ["push"]);
+        // email is skipped rather than failing every digest while smtp isn't set up
+        assert_eq!(
+            channels_for(&all, &SmtpConfig::default()),
+            ["webhook", "push"]
+        );
+        let unverified = notifiers_for(&all, None, &smtp, &reqwest::Client::new());
+        assert_eq!(unverified.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn webhook_posts_json() {
+        let (url, server) = stand_in_server(200).await;
+        let notifier = WebhookNotifier {
+            client: reqwest::Client::new(),
+            url: format!("{}/hook", url),
+        };
+        notifier.send(&digest()).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /hook "));
+        assert!(request.contains("content-type: application/json"));
+        let body: serde_json::Value =
+            serde_json::from_str(request.split_once("\r\n\r\n").unwrap().1).unwrap();
+        assert_eq!(body["user_id"], 7);
+        assert_eq!(body["date"], "2023-10-10");
+        assert_eq!(body["plants"][0]["due_tasks"][0]["name"], "Water");
+    }
+
+    #[tokio::test]
+    async fn webhook_error_status_fails() {
+        let (url, _server) = stand_in_server(500).await;
+        let notifier = WebhookNotifier {
+            client: reqwest::Client::new(),
+            url,
+        };
+        assert!(notifier.send(&digest()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn servers_that_never_answer_time_out() {
+        let url = silent_server().await;
+        let client = client_with_timeout(std::time::Duration::from_millis(200));
+        let webhook = WebhookNotifier {
+            client: client.clone(),
+            url: url.clone(),
+        };
+        let push = PushNotifier {
+            client,
+            service: PushService::Ntfy,
+            url,
+            token: None,
+        };
+        let notifiers: [&dyn Notifier; 2] = [&webhook, &push];
+        for notifier in notifiers {
+            let result =
+                tokio::time::timeout(std::time::Duration::from_secs(10), notifier.send(&digest()))
+                    .await
+                    .expect("the client should give up before the test does");
+            let error = result.unwrap_err();
+            assert!(error
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(reqwest::Error::is_timeout));
+        }
+    }
+
+    #[tokio::test]
+    async fn ntfy_posts_text_to_topic() {
+        let (url, server) = stand_in_server(200).await;
+        let notifier = PushNotifier {
+            client: reqwest::Client::new(),
+            service: PushService::Ntfy,
+            url: format!("{}/plants", url),
+            token: Some("tk_secret".to_owned()),
+        };
+        notifier.send(&digest()).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /plants "));
+        assert!(request.contains("title: Plants in need of your care!"));
+        assert!(request.contains("authorization: Bearer tk_secret"));
+        assert!(request.ends_with("\r\n\r\nSwiss cheese plant: Water, Mist"));
+    }
+
+    #[tokio::test]
+    async fn gotify_posts_message_with_app_token() {
+        let (url, server) = stand_in_server(200).await;
+        let notifier = PushNotifier {
+            client: reqwest::Client::new(),
+            service: PushService::Gotify,
+            url: format!("{}/", url),
+            token: Some("app-token".to_owned()),
+        };
+        notifier.send(&digest()).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /message "));
+        assert!(request.contains("x-gotify-key: app-token"));
+        let body: serde_json::Value =
+            serde_json::from_str(request.split_once("\r\n\r\n").unwrap().1).unwrap();
+        assert_eq!(body["message"], "Swiss cheese plant: Water, Mist");
+    }
+}
@@ -0,0 +1,197 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use axum::async_trait;
+use image::{ImageError, ImageFormat, ImageOutputFormat};
+
+use crate::error::{AppError, AppResult};
+
+/// Longest edge, in pixels, of a generated thumbnail.
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// Where uploaded photo bytes live. Keys are generated by the app and are flat file names, the
+/// database only ever stores the key.
+#[async_trait]
+pub trait PhotoStorage: Send + Sync {
+    async fn save(&self, key: &str, bytes: Vec<u8>) -> Result<()>;
+    async fn load(&self, key: &str) -> Result<Vec<u8>>;
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// Stores photos as files in a single directory on the local filesystem.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+        Ok(Self { root })
+    }
+
+    fn path_for(&self, key: &str) -> Result<PathBuf> {
+        if !is_valid_key(key) {
+            return Err(anyhow!("Invalid photo key: {}", key));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl PhotoStorage for LocalStorage {
+    async fn save(&self, key: &str, bytes: Vec<u8>) -> Result<()> {
+        tokio::fs::write(self.path_for(key)?, bytes).await?;
+        Ok(())
+    }
+
+    async fn load(&self, key: &str) -> Result<Vec<u8>> {
+        Ok(tokio::fs::read(self.path_for(key)?).await?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        tokio::fs::remove_file(self.path_for(key)?).await?;
+        Ok(())
+    }
+}
+
+/// Keys must be a plain file name so they can never escape the storage root.
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with('.')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// An upload that decoded as an image, along with its JPEG thumbnail.
+pub struct ProcessedPhoto {
+    pub extension: &'static str,
+    pub original: Vec<u8>,
+    pub thumbnail: Vec<u8>,
+}
+
+/// Checks the upload is an image we understand and generates its thumbnail. Uploads that aren't
+/// are a `BadRequest`. Decoding is CPU heavy so this should be run with `spawn_blocking`.
+pub fn process_upload(bytes: Vec<u8>) -> AppResult<ProcessedPhoto> {
+    let unreadable = |_: ImageError| AppError::bad_request("That image couldn't be read");
+    let format = image::guess_format(&bytes).map_err(unreadable)?;
+    let extension = match format {
+        ImageFormat::Jpeg => "jpg",
+        ImageFormat::Png => "png",
+        ImageFormat::WebP => "webp",
+        ImageFormat::Gif => "gif",
+        _ => {
+            return Err(AppError::bad_request(format!(
+                "Unsupported image format: {:?}",
+                format
+            )))
+        }
+    };
+    let image = image::load_from_memory_with_format(&bytes, format).map_err(unreadable)?;
+
+    let mut thumbnail = Vec::new();
+    image
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .to_rgb8()
+        .write_to(
+            &mut Cursor::new(&mut thumbnail),
+            ImageOutputFormat::Jpeg(80),
+        )
+        .map_err(|e| AppError::Internal(e.into()))?;
+
+    Ok(ProcessedPhoto {
+        extension,
+        original: bytes,
+        thumbnail,
+    })
+}
+
+pub fn content_type_for(key: &str) -> &'static str {
+    match key.rsplit('.').next() {
+        Some("jpg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::RgbImage::new(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn keys_cannot_leave_the_storage_root() {
+        assert!(is_valid_key("12-1696930000.jpg"));
+        assert!(is_valid_key("12-1696930000-thumb.jpg"));
+        for key in [
+            "",
+            "..",
+            "../secrets.toml",
+            ".env",
+            "photos/1.jpg",
+            "/etc/passwd",
+            "..\\windows\\win.ini",
+            "C:\\photo.jpg",
+            "1.jpg\0",
+        ] {
+            assert!(!is_valid_key(key), "{:?} was allowed", key);
+        }
+    }
+
+    #[test]
+    fn thumbnails_fit_the_longest_edge() {
+        let processed = process_upload(png(1024, 512)).unwrap();
+        assert_eq!(processed.extension, "png");
+        assert_eq!(processed.original, png(1024, 512));
+
+        let thumbnail = image::load_from_memory(&processed.thumbnail).unwrap();
+        assert_eq!(
+            image::guess_format(&processed.thumbnail).unwrap(),
+            ImageFormat::Jpeg
+        );
+        assert_eq!(
+            (thumbnail.width(), thumbnail.height()),
+            (THUMBNAIL_SIZE, THUMBNAIL_SIZE / 2)
+        );
+
+        let thumbnail =
+            image::load_from_memory(&process_upload(png(300, 900)).unwrap().thumbnail).unwrap();
+        assert_eq!(thumbnail.height(), THUMBNAIL_SIZE);
+    }
+
+    #[test]
+    fn uploads_that_arent_images_are_bad_requests() {
+        let mut truncated = png(64, 64);
+        truncated.truncate(40);
+        // a bitmap is an image, just not one that's accepted
+        let bitmap = b"BM\0\0\0\0\0\0\0\0\0\0\0\0".to_vec();
+        for bytes in [b"not an image".to_vec(), Vec::new(), truncated, bitmap] {
+            assert!(matches!(
+                process_upload(bytes),
+                Err(AppError::BadRequest(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn content_types_follow_the_extension() {
+        assert_eq!(content_type_for("1-2.jpg"), "image/jpeg");
+        assert_eq!(content_type_for("1-2-thumb.jpg"), "image/jpeg");
+        assert_eq!(content_type_for("1-2.png"), "image/png");
+        assert_eq!(content_type_for("1-2.webp"), "image/webp");
+        assert_eq!(content_type_for("1-2.gif"), "image/gif");
+        assert_eq!(content_type_for("1-2.bmp"), "application/octet-stream");
+        assert_eq!(content_type_for("no-extension"), "application/octet-stream");
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::Result;
+use sqlx::{Pool, Postgres};
+use time::{Duration, OffsetDateTime, Time};
+
+use crate::notify::{self, Digest};
+use crate::{config, db_api, DueDigest};
+
+/// Checks for due digests at the top of every hour, forever. Meant to be spawned from `main`.
+/// Each user picks their own send hour and timezone, so there's no single time of day to wait
+/// for. A check right at startup catches up on anything missed while the server was down,
+/// anyone already sent today's digest is skipped.
+pub async fn run(pool: Pool<Postgres>) {
+    let client = notify::http_client();
+    loop {
+        match send_due_digests(&pool, &client).await {
+            Ok(0) => {}
+            Ok(sent) => eprintln!("Sent {} reminder digests", sent),
+            Err(e) => eprintln!("Error sending reminder digests: {:#}", e),
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let wait = next_run(now) - now;
+        tokio::time::sleep(wait.try_into().unwrap_or_default()).await;
+    }
+}
+
+/// The start of the hour after `now`.
+pub fn next_run(now: OffsetDateTime) -> OffsetDateTime {
+    let hour = Time::from_hms(now.hour(), 0, 0).unwrap();
+    now.replace_time(hour) + Duration::hours(1)
+}
+
+/// Notifies every user whose digest is due, following their notification preferences, and
+/// who has plants that need attention, through each channel they picked. One user failing
+/// is logged and doesn't hold up the rest. Returns how many were sent.
+pub async fn send_due_digests(pool: &Pool<Postgres>, client: &reqwest::Client) -> Result<usize> {
+    let mut sent = 0;
+    for due in db_api::get_due_digests(pool).await? {
+        match send_digest(pool, client, &due).await {
+            Ok(true) => sent += 1,
+            Ok(false) => {}
+            Err(e) => eprintln!(
+                "Error sending reminder digest to user {}: {:#}",
+                due.preferences.user_id, e
+            ),
+        }
+    }
+    Ok(sent)
+}
+
+/// Sends one user's digest, `false` when there's nothing to send or it was already sent. A
+/// digest is recorded before it is sent and the record removed again if it doesn't get
+/// through, so the next run retries it. A crash part way through can skip someone for the day
+/// but never notifies them twice.
+async fn send_digest(
+    pool: &Pool<Postgres>,
+    client: &reqwest::Client,
+    due: &DueDigest,
+) -> Result<bool> {
+    let preferences = &due.preferences;
+    let user_id = preferences.user_id;
+    let email = db_api::get_verified_email(pool, user_id).await?;
+    let notifiers = notify::notifiers_for(preferences, email, &config().smtp, client);
+    if notifiers.is_empty() {
+        return Ok(false);
+    }
+    let plants = db_api::get_plants_that_need_attention(
+        pool,
+        user_id,
+        preferences.task_names.as_deref(),
+        due.local_date,
+    )
+    .await?;
+    if plants.is_empty() {
+        return Ok(false);
+    }
+    if !db_api::claim_reminder_digest(pool, user_id, due.local_date).await? {
+        return Ok(false);
+    }
+
+    let digest = Digest {
+        user_id,
+        date: due.local_date,
+        plants,
+    };
+    let mut delivered = false;
+    for notifier in notifiers {
+        match notifier.send(&digest).await {
+            Ok(()) => delivered = true,
+            Err(e) => eprintln!(
+                "Error sending {} reminder digest to user {}: {:#}",
+                notifier.channel(),
+                user_id,
+                e
+            ),
+        }
+    }
+    if !delivered {
+        db_api::release_reminder_digest(pool, user_id, due.local_date).await?;
+    }
+    Ok(delivered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn next_run_is_top_of_next_hour() {
+        assert_eq!(
+            next_run(datetime!(2023-09-10 06:15:42 UTC)),
+            datetime!(2023-09-10 07:00 UTC)
+        );
+        assert_eq!(
+            next_run(datetime!(2023-09-10 07:00 UTC)),
+            datetime!(2023-09-10 08:00 UTC)
+        );
+    }
+
+    #[test]
+    fn next_run_crosses_midnight() {
+        assert_eq!(
+            next_run(datetime!(2023-12-31 23:59 UTC)),
+            datetime!(2024-01-01 00:00 UTC)
+        );
+    }
+}
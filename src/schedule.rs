@@ -0,0 +1,198 @@
+use std::fmt;
+
+use time::{Date, Duration};
+
+use super::CareTask;
+
+/// Where a task sits relative to "today".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DueStatus {
+    /// Due this many days from today, always > 0.
+    Upcoming(i64),
+    DueToday,
+    /// Was due this many days ago, always > 0.
+    Overdue(i64),
+}
+
+impl fmt::Display for DueStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DueStatus::Upcoming(1) => write!(f, "1 day"),
+            DueStatus::Upcoming(days) => write!(f, "{} days", days),
+            DueStatus::DueToday => write!(f, "Due today"),
+            DueStatus::Overdue(1) => write!(f, "Overdue by 1 day"),
+            DueStatus::Overdue(days) => write!(f, "Overdue by {} days", days),
+        }
+    }
+}
+
+/// The date a task is next due. Saturates at `Date::MAX` rather than panicking on absurd
+/// intervals.
+pub fn next_due(last_done: Date, interval_days: i32) -> Date {
+    last_done
+        .checked_add(Duration::days(interval_days as i64))
+        .unwrap_or(Date::MAX)
+}
+
+/// Days from `today` until the task is next due. Negative when overdue.
+pub fn days_until_due(last_done: Date, interval_days: i32, today: Date) -> i64 {
+    (next_due(last_done, interval_days) - today).whole_days()
+}
+
+pub fn due_status(last_done: Date, interval_days: i32, today: Date) -> DueStatus {
+    let days = days_until_due(last_done, interval_days, today);
+    match days.cmp(&0) {
+        std::cmp::Ordering::Greater => DueStatus::Upcoming(days),
+        std::cmp::Ordering::Equal => DueStatus::DueToday,
+        std::cmp::Ordering::Less => DueStatus::Overdue(-days),
+    }
+}
+
+/// `None` when the task isn't tracked. A tracked task that has never been done is due today.
+pub fn task_next_due(task: &CareTask, today: Date) -> Option<Date> {
+    let interval_days = task.interval_days?;
+    Some(
+        task.last_done
+            .map_or(today, |last_done| next_due(last_done, interval_days)),
+    )
+}
+
+/// `None` when the task isn't tracked. A tracked task that has never been done is due today.
+pub fn task_due_status(task: &CareTask, today: Date) -> Option<DueStatus> {
+    let interval_days = task.interval_days?;
+    Some(task.last_done.map_or(DueStatus::DueToday, |last_done| {
+        due_status(last_done, interval_days, today)
+    }))
+}
+
+pub fn today() -> Date {
+    time::OffsetDateTime::now_utc().date()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Month;
+
+    fn date(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn next_due_adds_interval_to_last_done() {
+        assert_eq!(
+            next_due(date(2023, Month::August, 1), 30),
+            date(2023, Month::August, 31)
+        );
+    }
+
+    #[test]
+    fn next_due_crosses_leap_day() {
+        assert_eq!(
+            next_due(date(2024, Month::February, 28), 1),
+            date(2024, Month::February, 29)
+        );
+        assert_eq!(
+            next_due(date(2024, Month::February, 28), 2),
+            date(2024, Month::March, 1)
+        );
+        assert_eq!(
+            next_due(date(2023, Month::February, 28), 1),
+            date(2023, Month::March, 1)
+        );
+    }
+
+    #[test]
+    fn yearly_interval_over_leap_year() {
+        let today = date(2024, Month::March, 1);
+        assert_eq!(
+            due_status(date(2023, Month::March, 1), 365, today),
+            DueStatus::Overdue(1)
+        );
+        assert_eq!(
+            due_status(date(2023, Month::March, 1), 366, today),
+            DueStatus::DueToday
+        );
+    }
+
+    #[test]
+    fn zero_interval_is_due_on_last_done() {
+        let today = date(2023, Month::September, 10);
+        assert_eq!(due_status(today, 0, today), DueStatus::DueToday);
+        assert_eq!(
+            due_status(date(2023, Month::September, 7), 0, today),
+            DueStatus::Overdue(3)
+        );
+    }
+
+    #[test]
+    fn future_last_done_is_upcoming() {
+        let today = date(2023, Month::September, 10);
+        let last_done = date(2023, Month::September, 15);
+        assert_eq!(days_until_due(last_done, 7, today), 12);
+        assert_eq!(due_status(last_done, 0, today), DueStatus::Upcoming(5));
+    }
+
+    #[test]
+    fn overdue_counts_days_past_due() {
+        let today = date(2023, Month::September, 10);
+        assert_eq!(
+            due_status(date(2023, Month::August, 1), 30, today),
+            DueStatus::Overdue(10)
+        );
+        assert_eq!(
+            due_status(date(2023, Month::August, 1), 40, today),
+            DueStatus::DueToday
+        );
+        assert_eq!(
+            due_status(date(2023, Month::August, 1), 41, today),
+            DueStatus::Upcoming(1)
+        );
+    }
+
+    #[test]
+    fn huge_interval_saturates() {
+        assert_eq!(next_due(date(2023, Month::August, 1), i32::MAX), Date::MAX);
+    }
+
+    fn task(interval_days: Option<i32>, last_done: Option<Date>) -> CareTask {
+        CareTask {
+            task_id: 1,
+            plant_id: 1,
+            name: "Water".into(),
+            interval_days,
+            last_done,
+            optional: false,
+        }
+    }
+
+    #[test]
+    fn untracked_task_has_no_status() {
+        let today = date(2023, Month::September, 10);
+        assert_eq!(task_due_status(&task(None, Some(today)), today), None);
+        assert_eq!(task_due_status(&task(None, None), today), None);
+        assert_eq!(task_next_due(&task(None, Some(today)), today), None);
+    }
+
+    #[test]
+    fn never_done_task_is_due() {
+        let today = date(2023, Month::September, 10);
+        assert_eq!(
+            task_due_status(&task(Some(7), None), today),
+            Some(DueStatus::DueToday)
+        );
+        assert_eq!(task_next_due(&task(Some(7), None), today), Some(today));
+        assert_eq!(
+            task_due_status(&task(Some(7), Some(date(2023, Month::September, 1))), today),
+            Some(DueStatus::Overdue(2))
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(DueStatus::Upcoming(3).to_string(), "3 days");
+        assert_eq!(DueStatus::Upcoming(1).to_string(), "1 day");
+        assert_eq!(DueStatus::DueToday.to_string(), "Due today");
+        assert_eq!(DueStatus::Overdue(2).to_string(), "Overdue by 2 days");
+    }
+}
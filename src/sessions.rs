@@ -0,0 +1,238 @@
+use axum::http::header::{self, HeaderName};
+use axum::response::AppendHeaders;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Postgres};
+use time::{Duration, OffsetDateTime};
+
+use crate::auth_memes::{create_token, AuthError};
+use crate::error::AppResult;
+use crate::{config, db_api};
+
+/// Holds the short lived access token, a JWT naming the user and their session.
+pub const ACCESS_COOKIE: &str = "token";
+/// Holds the refresh token, `<session_id>.<secret>`. Only the secret's hash is stored.
+pub const REFRESH_COOKIE: &str = "refresh";
+
+/// A page load fires several htmx requests at once, and when the access cookie has expired they
+/// all refresh with the same token. Those that lose the race within this long still get in.
+const REUSE_GRACE: Duration = Duration::seconds(30);
+
+/// A signed in device. See the `sessions` migration.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Session {
+    pub session_id: i32,
+    pub user_id: i32,
+    pub refresh_token_hash: String,
+    pub previous_token_hash: Option<String>,
+    pub time_made: OffsetDateTime,
+    pub time_refreshed: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+/// What signing in or refreshing hands out. `refresh_token` is `None` for a request that lost
+/// a refresh race, the winner has the new one.
+#[derive(Debug)]
+pub struct Tokens {
+    pub user_id: i32,
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+}
+
+/// Signs `user_id` in on a new device.
+pub async fn start(pool: &Pool<Postgres>, user_id: i32) -> AppResult<Tokens> {
+    let secret = new_secret();
+    let session_id = db_api::insert_session(pool, user_id, &hash(&secret), expiry()).await?;
+    Ok(Tokens {
+        user_id,
+        access_token: create_token(user_id, session_id)?,
+        refresh_token: Some(format!("{}.{}", session_id, secret)),
+    })
+}
+
+/// Exchanges a refresh token for a new access token and a new refresh token. The old refresh
+/// token stops working, and if it turns up again later it must have been copied, so the whole
+/// session is revoked.
+pub async fn refresh(pool: &Pool<Postgres>, refresh_token: &str) -> AppResult<Tokens> {
+    let (session_id, secret) = parse(refresh_token).ok_or(AuthError::InvalidToken)?;
+    let presented = hash(secret);
+    let secret = new_secret();
+    let rotated =
+        db_api::rotate_session(pool, session_id, &presented, &hash(&secret), expiry()).await?;
+    if let Some(user_id) = rotated {
+        return Ok(Tokens {
+            user_id,
+            access_token: create_token(user_id, session_id)?,
+            refresh_token: Some(format!("{}.{}", session_id, secret)),
+        });
+    }
+
+    let session = db_api::get_session(pool, session_id)
+        .await?
+        .ok_or(AuthError::InvalidToken)?;
+    let now = OffsetDateTime::now_utc();
+    let live = session.revoked_at.is_none() && session.expires_at > now;
+    if live && session.previous_token_hash.as_deref() == Some(presented.as_str()) {
+        if now - session.time_refreshed < REUSE_GRACE {
+            return Ok(Tokens {
+                user_id: session.user_id,
+                access_token: create_token(session.user_id, session_id)?,
+                refresh_token: None,
+            });
+        }
+        db_api::revoke_session(pool, session.user_id, session_id).await?;
+    }
+    Err(AuthError::InvalidToken.into())
+}
+
+/// Signs out the session `refresh_token` belongs to. Anything but its current token is ignored,
+/// so a guessed session id can't be used to sign someone else out.
+pub async fn end(pool: &Pool<Postgres>, refresh_token: &str) -> AppResult<()> {
+    let Some((session_id, secret)) = parse(refresh_token) else {
+        return Ok(());
+    };
+    if let Some(session) = db_api::get_session(pool, session_id).await? {
+        if session.refresh_token_hash == hash(secret) {
+            db_api::revoke_session(pool, session.user_id, session_id).await?;
+        }
+    }
+    Ok(())
+}
+
+/// `Set-Cookie` headers for the tokens. The access cookie expires with its token, after which
+/// `check_client` uses the refresh cookie.
+pub fn set_cookies(tokens: &Tokens) -> AppendHeaders<Vec<(HeaderName, String)>> {
+    let auth = &config().auth;
+    let mut cookies = vec![cookie(
+        ACCESS_COOKIE,
+        &tokens.access_token,
+        Duration::minutes(auth.access_token_minutes),
+    )];
+    if let Some(refresh_token) = &tokens.refresh_token {
+        cookies.push(cookie(
+            REFRESH_COOKIE,
+            refresh_token,
+            Duration::days(auth.token_lifetime_days),
+        ));
+    }
+    AppendHeaders(cookies)
+}
+
+/// `Set-Cookie` headers that remove both cookies.
+pub fn clear_cookies() -> AppendHeaders<Vec<(HeaderName, String)>> {
+    AppendHeaders(vec![
+        cookie(ACCESS_COOKIE, "", Duration::ZERO),
+        cookie(REFRESH_COOKIE, "", Duration::ZERO),
+    ])
+}
+
+fn cookie(name: &str, value: &str, max_age: Duration) -> (HeaderName, String) {
+    (
+        header::SET_COOKIE,
+        format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}",
+            name,
+            value,
+            max_age.whole_seconds()
+        ),
+    )
+}
+
+fn expiry() -> OffsetDateTime {
+    OffsetDateTime::now_utc() + Duration::days(config().auth.token_lifetime_days)
+}
+
+fn new_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Secrets are random, so a plain hash is enough to keep the table from being a list of live
+/// tokens.
+fn hash(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+fn parse(refresh_token: &str) -> Option<(i32, &str)> {
+    let (session_id, secret) = refresh_token.split_once('.')?;
+    if secret.is_empty() {
+        return None;
+    }
+    Some((session_id.parse().ok()?, secret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+
+    #[test]
+    fn refresh_tokens_name_their_session() {
+        assert_eq!(parse("12.abc"), Some((12, "abc")));
+        assert_eq!(parse("12."), None);
+        assert_eq!(parse("abc"), None);
+        assert_eq!(parse("x.abc"), None);
+    }
+
+    #[test]
+    fn secrets_are_unique_and_hashed() {
+        let secret = new_secret();
+        assert_eq!(secret.len(), 64);
+        assert_ne!(secret, new_secret());
+        assert_eq!(hash(&secret), hash(&secret));
+        assert_ne!(hash(&secret), secret);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn refresh_tokens_rotate(pool: Pool<Postgres>) {
+        let f = crate::tests::fixture(pool).await;
+        let pool = &f.app.db_pool;
+        let first = start(pool, f.owner).await.unwrap();
+        let first_refresh = first.refresh_token.unwrap();
+
+        let second = refresh(pool, &first_refresh).await.unwrap();
+        assert_eq!(second.user_id, f.owner);
+        let second_refresh = second.refresh_token.unwrap();
+        assert_ne!(second_refresh, first_refresh);
+        let third = refresh(pool, &second_refresh).await.unwrap();
+        assert!(third.refresh_token.is_some());
+
+        // far too early to tell a copied token from a race
+        let raced = refresh(pool, &second_refresh).await.unwrap();
+        assert!(raced.refresh_token.is_none());
+        assert!(matches!(
+            refresh(pool, &first_refresh).await,
+            Err(AppError::Unauthorized(_))
+        ));
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn signed_out_sessions_cant_refresh(pool: Pool<Postgres>) {
+        let f = crate::tests::fixture(pool).await;
+        let pool = &f.app.db_pool;
+        let here = start(pool, f.owner).await.unwrap().refresh_token.unwrap();
+        let elsewhere = start(pool, f.owner).await.unwrap().refresh_token.unwrap();
+        let other_user = start(pool, f.other).await.unwrap().refresh_token.unwrap();
+
+        // a guessed secret doesn't sign anyone out
+        let session_id = parse(&here).unwrap().0;
+        end(pool, &format!("{}.guess", session_id)).await.unwrap();
+        let here = refresh(pool, &here).await.unwrap().refresh_token.unwrap();
+
+        end(pool, &here).await.unwrap();
+        assert!(refresh(pool, &here).await.is_err());
+        let elsewhere = refresh(pool, &elsewhere)
+            .await
+            .unwrap()
+            .refresh_token
+            .unwrap();
+
+        db_api::revoke_all_sessions(pool, f.owner).await.unwrap();
+        assert!(refresh(pool, &elsewhere).await.is_err());
+        assert!(refresh(pool, &other_user).await.is_ok());
+    }
+}
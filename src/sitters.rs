@@ -0,0 +1,209 @@
+use sqlx::{Pool, Postgres};
+use time::{Date, OffsetDateTime};
+
+use crate::auth_memes::{create_sitter_token, decode_sitter_token};
+use crate::error::{AppError, AppResult};
+use crate::households::grant_until;
+use crate::{config, db_api, schedule, Plant};
+
+/// A link that lets someone without an account tick off care in a household between two days,
+/// such as a neighbour looking after the plants over a holiday. What they do is logged against
+/// the link.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SitterLink {
+    pub link_id: i32,
+    pub household_id: i32,
+    pub created_by: i32,
+    pub sitter_name: String,
+    pub starts_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    pub time_made: OffsetDateTime,
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+impl SitterLink {
+    pub fn first_day(&self) -> Date {
+        self.starts_at.date()
+    }
+
+    pub fn last_day(&self) -> Date {
+        grant_until(Some(self.expires_at)).unwrap_or(self.first_day())
+    }
+
+    /// The address to hand the sitter.
+    pub fn url(&self) -> AppResult<String> {
+        let token = create_sitter_token(self.link_id, self.household_id, self.expires_at)?;
+        Ok(format!(
+            "{}/sit?token={}",
+            config().server.public_url.trim_end_matches('/'),
+            token
+        ))
+    }
+}
+
+/// The link `token` is for, as long as it can be used right now.
+pub async fn open(pool: &Pool<Postgres>, token: &str) -> AppResult<SitterLink> {
+    let unusable = || AppError::bad_request("That link has run out or was cancelled");
+    let claims = decode_sitter_token(token).map_err(|_| unusable())?;
+    let link = db_api::get_sitter_link(pool, claims.link_id)
+        .await?
+        .filter(|link| link.household_id == claims.household_id && link.revoked_at.is_none())
+        .ok_or_else(unusable)?;
+    let now = OffsetDateTime::now_utc();
+    if now < link.starts_at {
+        return Err(AppError::bad_request(format!(
+            "That link works from {}",
+            link.first_day()
+        )));
+    }
+    if now >= link.expires_at {
+        return Err(unusable());
+    }
+    Ok(link)
+}
+
+/// The tasks due by the sitter's last day, soonest first, on the plants that have any.
+pub fn checklist(plants: Vec<Plant>, last_day: Date) -> Vec<Plant> {
+    let today = schedule::today();
+    plants
+        .into_iter()
+        .filter_map(|mut plant| {
+            plant.tasks.retain(|task| {
+                schedule::task_next_due(task, today).is_some_and(|due| due <= last_day)
+            });
+            plant
+                .tasks
+                .sort_by_key(|task| schedule::task_next_due(task, today));
+            (!plant.tasks.is_empty()).then_some(plant)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CareTask;
+    use time::macros::date;
+
+    fn task(name: &str, interval_days: Option<i32>, last_done: Date) -> CareTask {
+        CareTask {
+            task_id: 1,
+            plant_id: 1,
+            name: name.to_owned(),
+            interval_days,
+            last_done: Some(last_done),
+            optional: false,
+        }
+    }
+
+    fn plant(name: &str, tasks: Vec<CareTask>) -> Plant {
+        Plant {
+            plant_id: 1,
+            user_id: 1,
+            household_id: 1,
+            location_id: None,
+            botanical_name: name.to_owned(),
+            common_name: name.to_owned(),
+            tags: Vec::new(),
+            tasks,
+        }
+    }
+
+    #[test]
+    fn checklists_only_have_what_falls_due() {
+        let last_day = schedule::today() + time::Duration::days(7);
+        let long_ago = date!(2023 - 01 - 01);
+        let plants = checklist(
+            vec![
+                plant(
+                    "Fern",
+                    vec![
+                        task("Repot", Some(10_000), long_ago),
+                        task("Mist", Some(1), schedule::today()),
+                        task("Water", Some(3), long_ago),
+                        task("Talk to", None, long_ago),
+                    ],
+                ),
+                plant("Cactus", vec![task("Water", Some(10_000), long_ago)]),
+            ],
+            last_day,
+        );
+        assert_eq!(plants.len(), 1);
+        let names: Vec<&str> = plants[0].tasks.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["Water", "Mist"]);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres server at DATABASE_URL"]
+    async fn sitters_log_care_until_revoked(pool: Pool<Postgres>) {
+        let f = crate::tests::fixture(pool).await;
+        let pool = &f.app.db_pool;
+        let household_id = f.plant.household_id;
+        let (plant_id, task_id) = (f.plant.plant_id, f.plant.tasks[0].task_id);
+        let now = OffsetDateTime::now_utc();
+        let (starts_at, expires_at) = (
+            now - time::Duration::hours(1),
+            now + time::Duration::days(3),
+        );
+
+        // only owners make links
+        assert!(db_api::insert_sitter_link(
+            pool,
+            f.other,
+            household_id,
+            "Sam",
+            starts_at,
+            expires_at
+        )
+        .await
+        .is_err());
+        let link =
+            db_api::insert_sitter_link(pool, f.owner, household_id, "Sam", starts_at, expires_at)
+                .await
+                .unwrap();
+        let token = create_sitter_token(link.link_id, household_id, expires_at).unwrap();
+        let link = open(pool, &token).await.unwrap();
+        db_api::add_sitter_care_event(pool, link.link_id, plant_id, task_id)
+            .await
+            .unwrap();
+        let events = db_api::get_care_events(pool, f.owner, plant_id)
+            .await
+            .unwrap();
+        assert_eq!(events[0].done_by, "Sam");
+        assert_eq!(events[0].sitter_link_id, Some(link.link_id));
+
+        // a link that hasn't started yet can't be used
+        let later = db_api::insert_sitter_link(
+            pool,
+            f.owner,
+            household_id,
+            "Pat",
+            now + time::Duration::days(1),
+            expires_at,
+        )
+        .await
+        .unwrap();
+        let later_token = create_sitter_token(later.link_id, household_id, expires_at).unwrap();
+        assert!(open(pool, &later_token).await.is_err());
+        assert!(
+            db_api::add_sitter_care_event(pool, later.link_id, plant_id, task_id)
+                .await
+                .is_err()
+        );
+
+        db_api::revoke_sitter_link(pool, f.owner, household_id, link.link_id)
+            .await
+            .unwrap();
+        assert!(open(pool, &token).await.is_err());
+        assert!(
+            db_api::add_sitter_care_event(pool, link.link_id, plant_id, task_id)
+                .await
+                .is_err()
+        );
+        assert!(db_api::get_sitter_links(pool, f.owner)
+            .await
+            .unwrap()
+            .iter()
+            .all(|link| link.sitter_name == "Pat"));
+    }
+}